@@ -0,0 +1,236 @@
+//! Crash recovery for unsaved buffers.
+//!
+//! Windows report their dirty buffer with `autosave_update` as it changes,
+//! and a background thread writes pending buffers to the app data dir every
+//! [`AUTOSAVE_INTERVAL`]. Snapshots are removed once the document is saved
+//! or discarded, so any left over at startup belong to a session that
+//! crashed and are offered for restore.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::save;
+
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A buffer as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// File stem of the snapshot, unique across sessions.
+    pub id: String,
+    /// The document's file, `None` for a buffer that was never saved.
+    pub path: Option<String>,
+    pub content: String,
+    /// Seconds since the Unix epoch.
+    pub saved_at: u64,
+}
+
+struct Buffer {
+    snapshot: Snapshot,
+    /// Changed since it was last written.
+    pending: bool,
+}
+
+pub struct Autosave {
+    dir: PathBuf,
+    /// Distinguishes this run's snapshots from those of earlier sessions.
+    session: u128,
+    buffers: Mutex<HashMap<String, Buffer>>,
+    recovered: Mutex<Vec<Snapshot>>,
+}
+
+impl Autosave {
+    /// Opens the snapshot directory and collects what earlier sessions left
+    /// behind.
+    pub fn open(dir: PathBuf) -> Self {
+        let mut recovered: Vec<Snapshot> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let text = fs::read_to_string(&path).ok()?;
+                serde_json::from_str(&text).ok()
+            })
+            .collect();
+        recovered.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.saved_at));
+
+        Autosave {
+            dir,
+            session: now().as_millis(),
+            buffers: Mutex::default(),
+            recovered: Mutex::new(recovered),
+        }
+    }
+
+    /// Records the current contents of the buffer in window `label`.
+    pub fn update(&self, label: &str, path: Option<String>, content: String) {
+        let snapshot = Snapshot {
+            id: format!("{label}-{}", self.session),
+            path,
+            content,
+            saved_at: now().as_secs(),
+        };
+        self.buffers.lock().unwrap().insert(
+            label.to_string(),
+            Buffer {
+                snapshot,
+                pending: true,
+            },
+        );
+    }
+
+    /// Forgets the buffer in window `label` once it is saved or discarded.
+    pub fn clear(&self, label: &str) {
+        if let Some(buffer) = self.buffers.lock().unwrap().remove(label) {
+            let _ = fs::remove_file(self.snapshot_path(&buffer.snapshot.id));
+        }
+    }
+
+    /// Snapshots left by earlier sessions, newest first.
+    pub fn recovered(&self) -> Vec<Snapshot> {
+        self.recovered.lock().unwrap().clone()
+    }
+
+    /// Deletes a recovered snapshot, whether it was restored or declined.
+    pub fn discard(&self, id: &str) {
+        self.recovered.lock().unwrap().retain(|s| s.id != id);
+        let _ = fs::remove_file(self.snapshot_path(id));
+    }
+
+    /// Writes every buffer that changed since the last flush.
+    pub fn flush(&self) {
+        let mut buffers = self.buffers.lock().unwrap();
+        let pending = buffers.values_mut().filter(|b| b.pending);
+        for buffer in pending {
+            if let Err(e) = self.write(&buffer.snapshot) {
+                eprintln!("autosave: {e}");
+                continue;
+            }
+            buffer.pending = false;
+        }
+    }
+
+    fn write(&self, snapshot: &Snapshot) -> Result<(), save::SaveError> {
+        let path = self.snapshot_path(&snapshot.id);
+        fs::create_dir_all(&self.dir).map_err(|e| save::SaveError::new(&self.dir, e))?;
+        save::save(&path, &serde_json::to_string(snapshot).unwrap(), false)
+    }
+
+    fn snapshot_path(&self, id: &str) -> PathBuf {
+        self.dir.join(Path::new(id).with_extension("json"))
+    }
+}
+
+/// Flushes the app's [`Autosave`] state every [`AUTOSAVE_INTERVAL`].
+pub fn start(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(AUTOSAVE_INTERVAL);
+        app.state::<Autosave>().flush();
+    });
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh snapshot directory; not created, as on first start.
+    fn dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bmermaid-autosave-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// The snapshots on disk, by file name.
+    fn stored(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn writes_pending_buffers_and_clears_them() {
+        let dir = dir("write");
+        let autosave = Autosave::open(dir.clone());
+        assert!(autosave.recovered().is_empty());
+
+        autosave.update(
+            "main",
+            Some("/a.mmd".to_string()),
+            "flowchart TD\n".to_string(),
+        );
+        autosave.update("document-1", None, "pie\n".to_string());
+        autosave.flush();
+        let main = format!("main-{}.json", autosave.session);
+        let other = format!("document-1-{}.json", autosave.session);
+        assert_eq!(stored(&dir), [other.clone(), main.clone()]);
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(dir.join(&main)).unwrap()).unwrap();
+        assert_eq!(snapshot.path.as_deref(), Some("/a.mmd"));
+        assert_eq!(snapshot.content, "flowchart TD\n");
+
+        // Nothing pending: a snapshot removed by hand stays gone.
+        fs::remove_file(dir.join(&other)).unwrap();
+        autosave.flush();
+        assert_eq!(stored(&dir), [main.as_str()]);
+
+        autosave.clear("main");
+        autosave.clear("document-1");
+        assert!(stored(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restores_what_a_crashed_session_left() {
+        let dir = dir("restore");
+        let crashed = Autosave::open(dir.clone());
+        crashed.update("main", None, "flowchart TD\n".to_string());
+        crashed.flush();
+        let older = Snapshot {
+            id: "main-1".to_string(),
+            path: Some("/b.mmd".to_string()),
+            content: "pie\n".to_string(),
+            saved_at: 1,
+        };
+        fs::write(
+            dir.join("main-1.json"),
+            serde_json::to_string(&older).unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        fs::write(dir.join("notes.txt"), "{}").unwrap();
+
+        let autosave = Autosave::open(dir.clone());
+        let recovered = autosave.recovered();
+        let contents: Vec<_> = recovered.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, ["flowchart TD\n", "pie\n"]);
+
+        autosave.discard("main-1");
+        assert_eq!(autosave.recovered().len(), 1);
+        assert!(!dir.join("main-1.json").exists());
+        autosave.discard(&recovered[0].id);
+        assert!(autosave.recovered().is_empty());
+        assert_eq!(stored(&dir), ["broken.json", "notes.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
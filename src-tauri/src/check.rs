@@ -0,0 +1,291 @@
+use serde::Serialize;
+
+use crate::parse::{self, Diagram, Fix, ParseError, Span};
+
+/// Code reported for every parse error.
+pub const SYNTAX_CODE: &str = "syntax";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A problem resolved to 1-based line/column positions for reporting, in
+/// the CLI as well as in the editor.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the check that produced the diagnostic.
+    pub code: &'static str,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub message: String,
+    pub fix: Option<SuggestedFix>,
+}
+
+impl Diagnostic {
+    /// `<file>:<line>:<column>: <message>`, the form editors and terminals
+    /// jump to.
+    pub fn located(&self, file: &str) -> String {
+        format!("{file}:{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// A [`Fix`] resolved to line/column positions.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestedFix {
+    pub title: String,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub replacement: String,
+}
+
+/// Maps byte offsets to 1-based line and column numbers. Columns count
+/// characters, not bytes, so they line up with what editors display.
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.line_starts[line]..offset].chars().count();
+        (line + 1, column + 1)
+    }
+
+    pub fn diagnostic(&self, error: &ParseError) -> Diagnostic {
+        self.report(
+            error.span,
+            Severity::Error,
+            SYNTAX_CODE,
+            &error.message,
+            error.fix.as_ref(),
+        )
+    }
+
+    /// A diagnostic for `span` from any check, syntax or lint.
+    pub fn report(
+        &self,
+        span: Span,
+        severity: Severity,
+        code: &'static str,
+        message: &str,
+        fix: Option<&Fix>,
+    ) -> Diagnostic {
+        let (line, column) = self.position(span.start);
+        let (end_line, end_column) = self.position(span.end);
+        Diagnostic {
+            severity,
+            code,
+            line,
+            column,
+            end_line,
+            end_column,
+            message: message.to_string(),
+            fix: fix.map(|fix| self.fix(fix)),
+        }
+    }
+
+    fn fix(&self, fix: &Fix) -> SuggestedFix {
+        let (line, column) = self.position(fix.span.start);
+        let (end_line, end_column) = self.position(fix.span.end);
+        SuggestedFix {
+            title: fix.title.clone(),
+            line,
+            column,
+            end_line,
+            end_column,
+            replacement: fix.replacement.clone(),
+        }
+    }
+}
+
+/// [`check_source`] errors resolved to line/column diagnostics.
+pub fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let index = LineIndex::new(source);
+    check_source(source)
+        .iter()
+        .map(|e| index.diagnostic(e))
+        .collect()
+}
+
+/// Validates a Mermaid source. Diagram types with a native parser report
+/// its errors; the rest get a structural check of balanced blocks.
+pub fn check_source(source: &str) -> Vec<ParseError> {
+    let parsed = parse::parse(source);
+    match parsed.document.as_ref().map(|d| &d.diagram) {
+        Some(Diagram::Unsupported { keyword }) => check_blocks(source, &keyword.value),
+        _ => parsed.errors,
+    }
+}
+
+/// Checks that `{ }` bodies balance.
+fn check_blocks(source: &str, keyword: &str) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    let lines = lines_with_offsets(source)
+        .filter(|(_, line)| !is_trivia(line))
+        .skip_while(|(_, line)| !line.trim_start().starts_with(keyword))
+        .skip(1);
+
+    let mut open: Vec<Span> = Vec::new();
+    for (offset, line) in lines {
+        let trimmed = line.trim();
+        if trimmed == "}" {
+            let start = offset + leading_ws(line);
+            if open.pop().is_none() {
+                errors.push(error(Span::new(start, start + 1), "unmatched `}`"));
+            }
+        } else if trimmed.ends_with('{') {
+            let brace = offset + line.rfind('{').unwrap();
+            open.push(Span::new(brace, brace + 1));
+        }
+    }
+
+    errors.extend(open.into_iter().map(|span| error(span, "unclosed `{`")));
+    errors
+}
+
+fn error(span: Span, message: impl Into<String>) -> ParseError {
+    ParseError {
+        span,
+        message: message.into(),
+        fix: None,
+    }
+}
+
+fn lines_with_offsets(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line.trim_end_matches(['\n', '\r'])))
+    })
+}
+
+fn is_trivia(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with("%%")
+}
+
+fn leading_ws(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_count_characters() {
+        let source = "a\nßé x\nlast";
+        let index = LineIndex::new(source);
+        assert_eq!(index.position(0), (1, 1));
+        assert_eq!(index.position(1), (1, 2));
+        assert_eq!(index.position(2), (2, 1));
+        assert_eq!(index.position(source.find('x').unwrap()), (2, 4));
+        // The last line has no newline; offsets past the end are clamped.
+        assert_eq!(index.position(source.find("last").unwrap()), (3, 1));
+        assert_eq!(index.position(source.len()), (3, 5));
+        assert_eq!(index.position(source.len() + 10), (3, 5));
+        assert_eq!(LineIndex::new("").position(0), (1, 1));
+    }
+
+    #[test]
+    fn diagnostics_have_line_and_column() {
+        let diagnostics = diagnostics("flowchart TD\n  é -->");
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.code, SYNTAX_CODE);
+        assert_eq!(
+            (
+                diagnostic.line,
+                diagnostic.column,
+                diagnostic.end_line,
+                diagnostic.end_column
+            ),
+            (2, 8, 2, 8)
+        );
+        assert_eq!(diagnostic.located("a.mmd"), "a.mmd:2:8: expected a node id");
+        assert_eq!(
+            serde_json::to_value(diagnostic).unwrap(),
+            serde_json::json!({
+                "severity": "error",
+                "code": "syntax",
+                "line": 2,
+                "column": 8,
+                "endLine": 2,
+                "endColumn": 8,
+                "message": "expected a node id",
+                "fix": null
+            })
+        );
+    }
+
+    #[test]
+    fn fixes_get_positions() {
+        let index = LineIndex::new("ab\ncdé");
+        let fix = Fix {
+            title: "Replace".to_string(),
+            span: Span::new(3, 7),
+            replacement: "x".to_string(),
+        };
+        let diagnostic = index.report(
+            Span::new(0, 2),
+            Severity::Warning,
+            "lint",
+            "message",
+            Some(&fix),
+        );
+        let fix = diagnostic.fix.unwrap();
+        assert_eq!(
+            (fix.line, fix.column, fix.end_line, fix.end_column),
+            (2, 1, 2, 4)
+        );
+        assert_eq!(fix.replacement, "x");
+    }
+
+    #[test]
+    fn unbalanced_blocks() {
+        let source = "%% header\nrequirementDiagram\n  requirement r {\n    id: 1\n  }\n  }\n  element e {\n";
+        let messages: Vec<_> = diagnostics(source)
+            .iter()
+            .map(|d| d.located("r.mmd"))
+            .collect();
+        assert_eq!(
+            messages,
+            ["r.mmd:6:3: unmatched `}`", "r.mmd:7:13: unclosed `{`"]
+        );
+        assert!(check_blocks("  }\ngantt\n", "gantt").is_empty());
+    }
+
+    #[test]
+    fn unsupported_diagrams_only_get_the_block_check() {
+        assert!(check_source("pie\n  \"a\" : 1\n  what is this\n").is_empty());
+        assert!(check_source("gitGraph\n  commit\n").is_empty());
+        let errors = check_source("journey\n  }\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "unmatched `}`");
+        // Diagrams with a parser report its errors instead.
+        assert!(!check_source("flowchart TD\n  what is this {\n  }\n").is_empty());
+    }
+}
@@ -0,0 +1,1255 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{App, AppHandle, Listener, Manager};
+use tauri_plugin_cli::{CliExt, Matches, SubcommandMatches};
+
+use crate::check::{self, Severity};
+use crate::config::Config;
+use crate::pdf::{self, PdfOptions};
+use crate::postprocess::PostProcessOptions;
+use crate::raster::RasterOptions;
+use crate::schema::{FileInfo, FileRecord, Output};
+use crate::{completions, embed, export, format, lint, save, CliArgs};
+
+/// Exit status for a diagram that failed to parse or render.
+pub const EXIT_DIAGRAM_ERROR: i32 = 1;
+/// Exit status for unreadable inputs, unwritable outputs, bad usage and a
+/// renderer that stops answering.
+pub const EXIT_USAGE_ERROR: i32 = 2;
+
+/// How long `render` waits for the webview to answer before giving up on
+/// the diagrams still to come. The first answer also waits for the page to
+/// load.
+const RENDER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The file argument that stands for stdin.
+pub const STDIN_ARG: &str = "-";
+/// How stdin is named in diagnostics.
+const STDIN_NAME: &str = "<stdin>";
+
+/// How long the editor, started without file arguments, waits for piped
+/// input to begin. A parent that keeps an idle pipe open must not stop the
+/// window from appearing; `-` waits as long as it takes.
+const STDIN_WAIT: Duration = Duration::from_millis(500);
+
+/// Options followed by a value, at the top level and in subcommands, so
+/// that value is not mistaken for a file when looking for file arguments
+/// ahead of the CLI plugin: `render -o -` writes to stdout and does not
+/// read stdin.
+const VALUE_OPTIONS: &[&str] = &[
+    "-t",
+    "--theme",
+    "--line",
+    "--column",
+    "--format",
+    "-o",
+    "--output",
+    "-f",
+    "--scale",
+    "--dpi",
+    "--width",
+    "--height",
+    "--background",
+    "--id-prefix",
+    "--styles",
+    "--page-size",
+    "--margin",
+    "--fit",
+    "--align",
+];
+
+/// Diagram source piped in on stdin, kept in Tauri's managed state.
+pub struct Stdin(pub Option<String>);
+
+impl Stdin {
+    /// Reads all of stdin when a file argument is `-`, or when the editor is
+    /// started without file arguments and stdin is a pipe or file that
+    /// starts delivering within [`STDIN_WAIT`]. Called before the app is
+    /// built, so the source is there before any window.
+    pub fn read() -> Stdin {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if positionals(&args).any(|arg| arg == STDIN_ARG) {
+            return match io::read_to_string(io::stdin()) {
+                Ok(source) => Stdin(Some(source)),
+                Err(e) => {
+                    eprintln!("bmermaid: stdin: {e}");
+                    Stdin(None)
+                }
+            };
+        }
+        if has_positional(&args) || !stdin_is_piped() {
+            return Stdin(None);
+        }
+
+        let (started, has_started) = mpsc::channel();
+        let (done, source) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            let _ = started.send(stdin.fill_buf().map(|_| ()));
+            let _ = done.send(io::read_to_string(stdin));
+        });
+        let source = match has_started.recv_timeout(STDIN_WAIT) {
+            Ok(Ok(())) => source.recv().unwrap_or_else(|e| Err(io::Error::other(e))),
+            Ok(Err(e)) => Err(e),
+            // Left reading in the background; nothing is waiting for it.
+            Err(_) => return Stdin(None),
+        };
+        match source {
+            Ok(source) if source.trim().is_empty() => Stdin(None),
+            Ok(source) => Stdin(Some(source)),
+            Err(e) => {
+                eprintln!("bmermaid: stdin: {e}");
+                Stdin(None)
+            }
+        }
+    }
+}
+
+/// Environment variables GTK finds its display through.
+#[cfg(all(desktop, unix, not(target_os = "macos")))]
+const DISPLAY_VARS: &[&str] = &["DISPLAY", "WAYLAND_DISPLAY"];
+
+/// Exits with [`EXIT_USAGE_ERROR`] when there is no display to build the app
+/// on, instead of letting GTK abort with a panic. Every command needs one,
+/// `render` included, whose diagrams are drawn in a hidden webview; headless
+/// machines can provide one with `xvfb-run`.
+#[cfg(all(desktop, unix, not(target_os = "macos")))]
+pub fn require_display() {
+    let found = DISPLAY_VARS
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()));
+    if !found {
+        eprintln!(
+            "bmermaid: no display found ({} unset); on a headless machine run under xvfb-run",
+            DISPLAY_VARS.join(" and ")
+        );
+        std::process::exit(EXIT_USAGE_ERROR);
+    }
+}
+
+/// Whether stdin is a pipe or a redirected file, rather than a terminal,
+/// `/dev/null` or a socket.
+#[cfg(unix)]
+fn stdin_is_piped() -> bool {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::FileTypeExt;
+
+    io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(fs::File::from)
+        .and_then(|file| file.metadata())
+        .is_ok_and(|metadata| metadata.file_type().is_fifo() || metadata.is_file())
+}
+
+#[cfg(not(unix))]
+fn stdin_is_piped() -> bool {
+    use std::io::IsTerminal;
+
+    !io::stdin().is_terminal()
+}
+
+/// Whether `args` name a file or subcommand.
+fn has_positional(args: &[String]) -> bool {
+    positionals(args).any(|arg| arg != STDIN_ARG)
+}
+
+/// The files and subcommands in `args`, `-` included, without options and
+/// their values.
+fn positionals(args: &[String]) -> impl Iterator<Item = &str> {
+    let mut args = args.iter();
+    std::iter::from_fn(move || {
+        while let Some(arg) = args.next() {
+            if VALUE_OPTIONS.contains(&arg.as_str()) {
+                args.next();
+            } else if arg == STDIN_ARG || !arg.starts_with('-') {
+                return Some(arg.as_str());
+            }
+        }
+        None
+    })
+}
+
+/// Reads a file argument, `-` being stdin.
+fn read_input(path: &Path, stdin: Option<&str>) -> io::Result<String> {
+    if path == Path::new(STDIN_ARG) {
+        return Ok(stdin.unwrap_or_default().to_string());
+    }
+    fs::read_to_string(path)
+}
+
+/// A file argument as diagnostics name it.
+fn display_name(path: &Path) -> String {
+    if path == Path::new(STDIN_ARG) {
+        return STDIN_NAME.to_string();
+    }
+    path.display().to_string()
+}
+
+/// Theme names the renderer accepts, with their display labels.
+pub const THEMES: &[(&str, &str)] = &[
+    ("default", "Default"),
+    ("dark", "Dark"),
+    ("tokyo-night", "Tokyo Night"),
+    ("dracula", "Dracula"),
+    ("github-dark", "GitHub Dark"),
+    ("nord", "Nord"),
+    ("one-dark", "One Dark"),
+    ("solarized-light", "Solarized Light"),
+    ("solarized-dark", "Solarized Dark"),
+    ("monokai", "Monokai"),
+];
+
+/// The parsed command line. Arguments the CLI plugin rejects are reported
+/// with usage on stderr, and `--help` and `--version` are printed; either
+/// way the status to exit with comes back as the error.
+pub fn matches(app: &App) -> Result<Matches, i32> {
+    let matches = match app.cli().matches() {
+        Ok(matches) => matches,
+        Err(tauri_plugin_cli::Error::ParseCli(e)) => {
+            eprint!("{e}");
+            return Err(EXIT_USAGE_ERROR);
+        }
+    };
+    if let Some(help) = matches.args.get("help") {
+        print!("{}", help.value.as_str().unwrap_or_default());
+        io::stdout().flush().ok();
+        return Err(0);
+    }
+    if matches.args.contains_key("version") {
+        let info = app.package_info();
+        println!("{} {}", info.name, info.version);
+        return Err(0);
+    }
+    Ok(matches)
+}
+
+pub fn arg_str(matches: &Matches, name: &str) -> Option<String> {
+    matches
+        .args
+        .get(name)
+        .and_then(|v| v.value.as_str())
+        .map(|s| s.to_string())
+}
+
+pub fn arg_list(matches: &Matches, name: &str) -> Vec<String> {
+    match matches.args.get(name).map(|v| &v.value) {
+        Some(serde_json::Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|s| s.to_string())
+            .collect(),
+        Some(serde_json::Value::String(value)) => vec![value.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Expands file arguments into the files they name. Patterns such as
+/// `docs/*.mmd` are expanded here, since not every shell does it, and every
+/// file is checked up front so nothing is processed when one is missing.
+/// Duplicates are dropped; on failure every bad argument is described.
+/// `-` is passed through for stdin.
+pub fn expand_files(args: &[String]) -> Result<Vec<PathBuf>, Vec<String>> {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for arg in args {
+        if arg == STDIN_ARG {
+            files.push(PathBuf::from(STDIN_ARG));
+            continue;
+        }
+        let path = Path::new(arg);
+        if path.exists() || !is_pattern(arg) {
+            match fs::metadata(path) {
+                Ok(metadata) if metadata.is_dir() => errors.push(format!("{arg}: is a directory")),
+                Ok(_) => files.push(path.to_path_buf()),
+                Err(e) => errors.push(format!("{arg}: {e}")),
+            }
+            continue;
+        }
+        let matched = match glob::glob(arg) {
+            Ok(paths) => paths
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                errors.push(format!("{arg}: invalid pattern: {}", e.msg));
+                continue;
+            }
+        };
+        if matched.is_empty() {
+            errors.push(format!("{arg}: no files match"));
+        }
+        files.extend(matched);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|file| seen.insert(fs::canonicalize(file).unwrap_or_else(|_| file.clone())));
+    Ok(files)
+}
+
+fn is_pattern(arg: &str) -> bool {
+    arg.contains(['*', '?', '['])
+}
+
+/// Splits a `:line` or `:line:column` suffix, as in compiler and grep
+/// output, off a file argument. An argument naming an existing file is
+/// taken as it is. Positions start at 1, as with `--line` and `--column`.
+pub fn split_position(arg: &str) -> Result<(&str, Option<u32>, Option<u32>), String> {
+    if Path::new(arg).exists() {
+        return Ok((arg, None, None));
+    }
+    let mut path = arg;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        let Some((head, number)) = path.rsplit_once(':') else {
+            break;
+        };
+        match number.parse::<u32>() {
+            Ok(number) if !head.is_empty() => {
+                numbers.push(number);
+                path = head;
+            }
+            _ => break,
+        }
+    }
+    if numbers.contains(&0) {
+        return Err(format!("{arg}: lines and columns start at 1"));
+    }
+    Ok(match numbers[..] {
+        [line] => (path, Some(line), None),
+        [column, line] => (path, Some(line), Some(column)),
+        _ => (arg, None, None),
+    })
+}
+
+/// The documents the editor opens with: an unsaved buffer for `stdin`, if
+/// any, then every file argument, exported images as unsaved buffers with
+/// their embedded source. `--line` and `--column` apply to the
+/// first of them that has no position of its own, which is the stdin buffer
+/// when there is one.
+pub fn open_args(matches: &Matches, stdin: Option<String>) -> Result<Vec<CliArgs>, Vec<String>> {
+    let theme = arg_str(matches, "theme");
+    let number = |name: &str| match arg_str(matches, name) {
+        Some(value) => value
+            .parse::<u32>()
+            .ok()
+            .filter(|&n| n > 0)
+            .map(Some)
+            .ok_or_else(|| {
+                vec![format!(
+                    "--{name}: expected a positive number, got `{value}`"
+                )]
+            }),
+        None => Ok(None),
+    };
+    let line = number("line")?;
+    let column = number("column")?;
+
+    let mut documents = Vec::new();
+    if let Some(source) = stdin {
+        documents.push(CliArgs {
+            theme: theme.clone(),
+            source: Some(source),
+            ..CliArgs::default()
+        });
+    }
+
+    let mut errors = Vec::new();
+    for arg in arg_list(matches, "files") {
+        if arg == STDIN_ARG {
+            continue;
+        }
+        let (pattern, file_line, file_column) = match split_position(&arg) {
+            Ok(split) => split,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let files = match expand_files(&[pattern.to_string()]) {
+            Ok(files) => files,
+            Err(e) => {
+                errors.extend(e);
+                continue;
+            }
+        };
+        for file in files {
+            let args = CliArgs {
+                file: Some(file.display().to_string()),
+                theme: theme.clone(),
+                line: file_line,
+                column: file_column,
+                ..CliArgs::default()
+            };
+            match embed::resolve(args) {
+                Ok(args) => documents.push(args),
+                Err(e) => errors.push(format!("{}: {e}", file.display())),
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    if let Some(first) = documents
+        .iter_mut()
+        .find(|document| document.line.is_none())
+    {
+        first.line = line;
+        first.column = column;
+    }
+    Ok(documents)
+}
+
+/// [`expand_files`] for a subcommand's `files`, reporting bad arguments on
+/// stderr.
+fn subcommand_files(matches: &Matches) -> Result<Vec<PathBuf>, i32> {
+    expand_files(&arg_list(matches, "files")).map_err(|errors| {
+        for error in errors {
+            eprintln!("{error}");
+        }
+        EXIT_USAGE_ERROR
+    })
+}
+
+/// Flags without a value come back as `true` when they were passed.
+pub fn arg_flag(matches: &Matches, name: &str) -> bool {
+    matches
+        .args
+        .get(name)
+        .is_some_and(|v| v.value == serde_json::Value::Bool(true))
+}
+
+/// Job handed to the hidden webview, which owns the Mermaid renderer.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderJob {
+    command: &'static str,
+    /// Rendered in order, each answered by its own `render-finished`.
+    sources: Vec<String>,
+    theme: Option<String>,
+}
+
+/// Payload of the `render-finished` event emitted by the webview.
+#[derive(Deserialize)]
+struct RenderOutcome {
+    svg: Option<String>,
+    error: Option<String>,
+}
+
+/// Runs a subcommand without showing a window and exits with its status
+/// once it is done.
+pub fn run_subcommand(app: &mut App, subcommand: &SubcommandMatches) {
+    let stdin = app.state::<Stdin>().0.clone();
+    let stdin = stdin.as_deref();
+    match subcommand.name.as_str() {
+        "render" => render(app, &subcommand.matches, stdin),
+        "check" => {
+            let code = check_files(&subcommand.matches, stdin);
+            app.handle().exit(code);
+        }
+        "lint" => {
+            let code = lint_files(&subcommand.matches, stdin);
+            app.handle().exit(code);
+        }
+        "info" => {
+            let code = info_files(&subcommand.matches, stdin);
+            app.handle().exit(code);
+        }
+        "fmt" => {
+            let code = format_files(&subcommand.matches, stdin);
+            app.handle().exit(code);
+        }
+        "completions" | "manpage" => {
+            let code = print_usage_docs(app, subcommand);
+            app.handle().exit(code);
+        }
+        name => {
+            eprintln!("bmermaid: unknown subcommand `{name}`");
+            app.handle().exit(EXIT_USAGE_ERROR);
+        }
+    }
+}
+
+/// Prints a completion script or the man page, both generated from the
+/// `plugins.cli` definition this process parsed its arguments with.
+fn print_usage_docs(app: &App, subcommand: &SubcommandMatches) -> i32 {
+    let definition = app
+        .config()
+        .plugins
+        .0
+        .get("cli")
+        .cloned()
+        .unwrap_or_default();
+    let root = match completions::Command::from_config(&definition) {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("bmermaid: {e}");
+            return EXIT_USAGE_ERROR;
+        }
+    };
+    let text = match subcommand.name.as_str() {
+        "manpage" => completions::manpage(&root, &app.package_info().version.to_string()),
+        _ => match arg_str(&subcommand.matches, "shell").as_deref() {
+            Some("bash") => completions::bash(&root),
+            Some("zsh") => completions::zsh(&root),
+            Some("fish") => completions::fish(&root),
+            shell => {
+                eprintln!(
+                    "bmermaid: unsupported shell `{}`",
+                    shell.unwrap_or_default()
+                );
+                return EXIT_USAGE_ERROR;
+            }
+        },
+    };
+    print!("{text}");
+    0
+}
+
+/// A `render` run waiting for the webview's results.
+struct RenderRun {
+    /// Input, output, source, SVG post-processing and record of each
+    /// diagram still to come, in order.
+    pending: VecDeque<(PathBuf, PathBuf, String, PostProcessOptions, FileRecord)>,
+    records: Vec<FileRecord>,
+    code: i32,
+    json: bool,
+    /// The image format every output is written in.
+    format: String,
+    raster: RasterOptions,
+    pdf: PdfOptions,
+    /// Whether SVG and PNG output carries the diagram's source.
+    embed_source: bool,
+    /// The PDF every diagram becomes a page of, with `-f pdf --output`.
+    document: Option<PathBuf>,
+    /// The SVG of each page of `document` so far.
+    pages: Vec<String>,
+    started: Instant,
+    /// When the previous result came in.
+    last: Instant,
+}
+
+/// Renders each file to `<file>.svg`, `.png` or `.pdf`, or to `--output`.
+/// The image of a diagram read from stdin, or with `--output -`, goes to
+/// stdout. A PDF `--output` gets a page per file. With `--format json` the
+/// image format follows the output's extension and a record per file is
+/// printed instead.
+///
+/// Diagrams are rendered by the Mermaid renderer in a hidden webview, so
+/// this needs a display even though no window is shown; headless machines
+/// can provide one with `xvfb-run`. The webview gets the job with its page
+/// and answers each diagram with a `render-finished` event; after
+/// [`RENDER_TIMEOUT`] without one the remaining diagrams fail.
+fn render(app: &mut App, matches: &Matches, stdin: Option<&str>) {
+    let started = Instant::now();
+    let handle = app.handle().clone();
+
+    let inputs = match subcommand_files(matches) {
+        Ok(inputs) => inputs,
+        Err(code) => {
+            handle.exit(code);
+            return;
+        }
+    };
+    let output = arg_str(matches, "output").map(PathBuf::from);
+    let format = arg_str(matches, "format");
+    let json = format.as_deref() == Some("json");
+    let extension = match format {
+        Some(format) if !json => format,
+        _ => output
+            .as_deref()
+            .and_then(Path::extension)
+            .and_then(|ext| ext.to_str())
+            .filter(|ext| export::IMAGE_FORMATS.contains(ext))
+            .unwrap_or("svg")
+            .to_string(),
+    };
+    let document = output.clone().filter(|_| extension == "pdf");
+    if output.is_some() && document.is_none() && inputs.len() > 1 {
+        eprintln!("bmermaid render: --output needs a single input file, or -f pdf");
+        handle.exit(EXIT_USAGE_ERROR);
+        return;
+    }
+    let options = raster_options(matches).and_then(|raster| Ok((raster, pdf_options(matches)?)));
+    let (raster, pdf) = match options {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("bmermaid render: {e}");
+            handle.exit(EXIT_USAGE_ERROR);
+            return;
+        }
+    };
+
+    let mut sources = Vec::new();
+    let mut pending = VecDeque::new();
+    for input in inputs {
+        let source = match read_input(&input, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("bmermaid render: {}: {}", input.display(), e);
+                handle.exit(EXIT_USAGE_ERROR);
+                return;
+            }
+        };
+        let output = output.clone().unwrap_or_else(|| {
+            if input == Path::new(STDIN_ARG) {
+                PathBuf::from(STDIN_ARG)
+            } else {
+                input.with_extension(&extension)
+            }
+        });
+        if json && output == Path::new(STDIN_ARG) {
+            eprintln!(
+                "bmermaid render: --format json prints to stdout; pass --output for the image"
+            );
+            handle.exit(EXIT_USAGE_ERROR);
+            return;
+        }
+        let post = if extension == "svg" {
+            match postprocess_options(matches, &input) {
+                Ok(post) => post,
+                Err(e) => {
+                    eprintln!("bmermaid render: {e}");
+                    handle.exit(EXIT_USAGE_ERROR);
+                    return;
+                }
+            }
+        } else {
+            PostProcessOptions::default()
+        };
+        pending.push_back((
+            input.clone(),
+            output,
+            source.clone(),
+            post,
+            FileRecord::new(display_name(&input), &source),
+        ));
+        sources.push(source);
+    }
+
+    let job = RenderJob {
+        command: "render",
+        sources,
+        theme: arg_str(matches, "theme"),
+    };
+
+    let run = Arc::new(Mutex::new(RenderRun {
+        pending,
+        records: Vec::new(),
+        code: 0,
+        json,
+        format: extension,
+        raster,
+        pdf,
+        embed_source: !arg_flag(matches, "no-source"),
+        document,
+        pages: Vec::new(),
+        started,
+        last: Instant::now(),
+    }));
+    let exit_handle = handle.clone();
+    let listening = Arc::clone(&run);
+    handle.listen("render-finished", move |event| {
+        let mut run = listening.lock().unwrap();
+        let Some((input, output, source, post, mut record)) = run.pending.pop_front() else {
+            return;
+        };
+        let code = match serde_json::from_str::<RenderOutcome>(event.payload()) {
+            Ok(RenderOutcome { svg: Some(svg), .. }) if run.document.is_some() => {
+                run.pages.push(svg);
+                record.output = Some(output.display().to_string());
+                0
+            }
+            Ok(RenderOutcome { svg: Some(svg), .. }) => {
+                match write_image(&output, &svg, &source, &post, &run) {
+                    Ok(()) => {
+                        record.output = Some(output.display().to_string());
+                        0
+                    }
+                    Err(e) => {
+                        let message = format!("{}: {e}", output.display());
+                        eprintln!("bmermaid render: {message}");
+                        record.error = Some(message);
+                        EXIT_USAGE_ERROR
+                    }
+                }
+            }
+            Ok(RenderOutcome { error, .. }) => {
+                let message = error.unwrap_or_else(|| "render failed".to_string());
+                eprintln!("{}: {message}", display_name(&input));
+                record.error = Some(message);
+                EXIT_DIAGRAM_ERROR
+            }
+            Err(e) => {
+                let message = format!("malformed renderer response: {e}");
+                eprintln!("bmermaid render: {message}");
+                record.error = Some(message);
+                EXIT_USAGE_ERROR
+            }
+        };
+        run.code = run.code.max(code);
+        let record = record.timed(run.last.elapsed());
+        run.records.push(record);
+        run.last = Instant::now();
+
+        if run.pending.is_empty() {
+            run.finish(&exit_handle);
+        }
+    });
+    watch_renderer(handle.clone(), run);
+
+    if let Err(e) = crate::main_window(&handle, &job) {
+        eprintln!("bmermaid render: {e}");
+        handle.exit(EXIT_USAGE_ERROR);
+    }
+}
+
+/// Fails the diagrams still waiting once the webview has not answered for
+/// [`RENDER_TIMEOUT`], so a page that never loads or a renderer that hangs
+/// ends the run instead of leaving it waiting forever.
+fn watch_renderer(handle: AppHandle, run: Arc<Mutex<RenderRun>>) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(500));
+        let mut run = run.lock().unwrap();
+        if run.pending.is_empty() {
+            return;
+        }
+        if run.last.elapsed() < RENDER_TIMEOUT {
+            continue;
+        }
+        let message = format!(
+            "the renderer did not answer within {}s",
+            RENDER_TIMEOUT.as_secs()
+        );
+        while let Some((input, _, _, _, mut record)) = run.pending.pop_front() {
+            eprintln!("{}: {message}", display_name(&input));
+            record.error = Some(message.clone());
+            let record = record.timed(run.last.elapsed());
+            run.records.push(record);
+        }
+        run.code = run.code.max(EXIT_USAGE_ERROR);
+        run.finish(&handle);
+        return;
+    });
+}
+
+/// `--scale`, `--dpi`, `--width`, `--height` and `--background`, which
+/// apply to PNG output.
+fn raster_options(matches: &Matches) -> Result<RasterOptions, String> {
+    let number = |name: &str| {
+        arg_str(matches, name)
+            .map(|value| {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|n| n.is_finite() && *n > 0.0)
+                    .ok_or_else(|| format!("--{name} needs a positive number, not `{value}`"))
+            })
+            .transpose()
+    };
+    let pixels = |name: &str| {
+        arg_str(matches, name)
+            .map(|value| {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("--{name} needs a number of pixels, not `{value}`"))
+            })
+            .transpose()
+    };
+    let scale = match (number("scale")?, number("dpi")?) {
+        (Some(_), Some(_)) => return Err("--scale and --dpi cannot be combined".to_string()),
+        (Some(scale), None) => scale,
+        (None, Some(dpi)) => RasterOptions::scale_for_dpi(dpi),
+        (None, None) => RasterOptions::default().scale,
+    };
+    let options = RasterOptions {
+        scale,
+        width: pixels("width")?,
+        height: pixels("height")?,
+        background: arg_str(matches, "background").filter(|color| color != "transparent"),
+    };
+    options.validate()?;
+    Ok(options)
+}
+
+/// `--page-size`, `--landscape`, `--margin`, `--fit` and `--align`, which
+/// apply to PDF output.
+fn pdf_options(matches: &Matches) -> Result<PdfOptions, String> {
+    let mut options = PdfOptions {
+        landscape: arg_flag(matches, "landscape"),
+        ..PdfOptions::default()
+    };
+    if let Some(size) = arg_str(matches, "page-size") {
+        options.page_size = size.parse()?;
+    }
+    if let Some(margin) = arg_str(matches, "margin") {
+        options.margin = pdf::parse_length(&margin)?;
+    }
+    if let Some(fit) = arg_str(matches, "fit") {
+        options.fit = fit.parse()?;
+    }
+    if let Some(align) = arg_str(matches, "align") {
+        options.align = align.parse()?;
+    }
+    options.validate()?;
+    Ok(options)
+}
+
+/// The `[export]` section of the `.bmermaid.toml` for `input`, with
+/// `--minify`, `--prefix-ids`, `--id-prefix`, `--styles` and
+/// `--embed-fonts` on top. These apply to SVG output.
+fn postprocess_options(matches: &Matches, input: &Path) -> Result<PostProcessOptions, String> {
+    let mut options = Config::for_file(input).map_err(|e| e.to_string())?.export;
+    options.minify |= arg_flag(matches, "minify");
+    options.prefix_ids |= arg_flag(matches, "prefix-ids");
+    options.embed_fonts |= arg_flag(matches, "embed-fonts");
+    if let Some(prefix) = arg_str(matches, "id-prefix") {
+        options.id_prefix = Some(prefix);
+    }
+    if let Some(styles) = arg_str(matches, "styles") {
+        options.styles = styles.parse()?;
+    }
+    options.validate()?;
+    Ok(options)
+}
+
+/// Writes the diagram in the run's image format, with its source embedded
+/// in SVG and PNG output unless `--no-source` was given.
+fn write_image(
+    output: &Path,
+    svg: &str,
+    source: &str,
+    post: &PostProcessOptions,
+    run: &RenderRun,
+) -> Result<(), String> {
+    let source = Some(source).filter(|_| run.embed_source);
+    let bytes = export::image(&run.format, svg, source, &run.raster, &run.pdf, post)?;
+    write_bytes(output, &bytes)
+}
+
+/// Writes `bytes` to `output`, `-` meaning stdout.
+fn write_bytes(output: &Path, bytes: &[u8]) -> Result<(), String> {
+    if output == Path::new(STDIN_ARG) {
+        io::stdout().write_all(bytes)
+    } else {
+        fs::write(output, bytes)
+    }
+    .map_err(|e| e.to_string())
+}
+
+impl RenderRun {
+    /// Ends the run once every diagram is accounted for: writes the PDF
+    /// `--output`, prints the JSON report and exits.
+    fn finish(&mut self, handle: &AppHandle) {
+        self.finish_document();
+        if self.json {
+            let records = std::mem::take(&mut self.records);
+            Output::new("render", records, self.code, self.started.elapsed()).print();
+        }
+        handle.exit(self.code);
+    }
+
+    /// Writes the pages collected for a PDF `--output`. The diagrams on them
+    /// lose their output when that fails.
+    fn finish_document(&mut self) {
+        let Some(document) = self.document.take() else {
+            return;
+        };
+        if self.pages.is_empty() {
+            return;
+        }
+        let written =
+            pdf::pdf(&self.pages, &self.pdf).and_then(|bytes| write_bytes(&document, &bytes));
+        if let Err(e) = written {
+            let message = format!("{}: {e}", document.display());
+            eprintln!("bmermaid render: {message}");
+            for record in &mut self.records {
+                if record.output.take().is_some() {
+                    record.error = Some(message.clone());
+                }
+            }
+            self.code = self.code.max(EXIT_USAGE_ERROR);
+        }
+    }
+}
+
+fn check_files(matches: &Matches, stdin: Option<&str>) -> i32 {
+    let started = Instant::now();
+    let files = match subcommand_files(matches) {
+        Ok(files) => files,
+        Err(code) => return code,
+    };
+    let json = arg_str(matches, "format").as_deref() == Some("json");
+
+    let mut code = 0;
+    let mut records = Vec::new();
+    for file in &files {
+        let file_started = Instant::now();
+        let path = display_name(file);
+        let source = match read_input(file, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                code = EXIT_USAGE_ERROR;
+                records.push(FileRecord::failed(path, e.to_string()));
+                continue;
+            }
+        };
+        let record = FileRecord::new(path, &source);
+        if !record.diagnostics.is_empty() && code == 0 {
+            code = EXIT_DIAGRAM_ERROR;
+        }
+        if !json {
+            for d in &record.diagnostics {
+                println!("{}", d.located(&record.file));
+            }
+        }
+        records.push(record.timed(file_started.elapsed()));
+    }
+
+    if json {
+        Output::new("check", records, code, started.elapsed()).print();
+    }
+    code
+}
+
+/// Reports syntax errors and lint findings, each file configured by its
+/// nearest `.bmermaid.toml`. Only error-level findings fail the run.
+fn lint_files(matches: &Matches, stdin: Option<&str>) -> i32 {
+    let started = Instant::now();
+    let files = match subcommand_files(matches) {
+        Ok(files) => files,
+        Err(code) => return code,
+    };
+    let json = arg_str(matches, "format").as_deref() == Some("json");
+
+    let mut code = 0;
+    let mut records = Vec::new();
+    for file in &files {
+        let file_started = Instant::now();
+        let path = display_name(file);
+        let source = match read_input(file, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                code = EXIT_USAGE_ERROR;
+                records.push(FileRecord::failed(path, e.to_string()));
+                continue;
+            }
+        };
+        let config = match Config::for_file(file) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{e}");
+                code = EXIT_USAGE_ERROR;
+                records.push(FileRecord::failed(path, e.to_string()));
+                continue;
+            }
+        };
+        let mut record = FileRecord::new(path, &source);
+        record.diagnostics.extend(lint::lint(&source, &config));
+        if code == 0
+            && record
+                .diagnostics
+                .iter()
+                .any(|d| d.severity == Severity::Error)
+        {
+            code = EXIT_DIAGRAM_ERROR;
+        }
+        if !json {
+            for d in &record.diagnostics {
+                let severity = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Info => "info",
+                };
+                println!(
+                    "{}:{}:{}: {severity}: {} [{}]",
+                    record.file, d.line, d.column, d.message, d.code
+                );
+            }
+        }
+        records.push(record.timed(file_started.elapsed()));
+    }
+
+    if json {
+        Output::new("lint", records, code, started.elapsed()).print();
+    }
+    code
+}
+
+/// Describes each file: diagram type, size and syntax errors.
+fn info_files(matches: &Matches, stdin: Option<&str>) -> i32 {
+    let started = Instant::now();
+    let files = match subcommand_files(matches) {
+        Ok(files) => files,
+        Err(code) => return code,
+    };
+    let json = arg_str(matches, "format").as_deref() == Some("json");
+
+    let mut code = 0;
+    let mut records = Vec::new();
+    for file in &files {
+        let file_started = Instant::now();
+        let path = display_name(file);
+        let source = match read_input(file, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                code = EXIT_USAGE_ERROR;
+                records.push(FileRecord::failed(path, e.to_string()));
+                continue;
+            }
+        };
+        let mut record = FileRecord::new(path, &source);
+        let info = FileInfo::new(&source);
+        if !json {
+            let direction = info.direction.map(|d| format!(" {d}")).unwrap_or_default();
+            println!(
+                "{}: {}{direction}, {} lines, {} statements, {} errors",
+                record.file,
+                record.diagram_type.as_deref().unwrap_or("unknown"),
+                info.lines,
+                info.statements,
+                record.diagnostics.len()
+            );
+        }
+        record.info = Some(info);
+        records.push(record.timed(file_started.elapsed()));
+    }
+
+    if json {
+        Output::new("info", records, code, started.elapsed()).print();
+    }
+    code
+}
+
+/// Rewrites each file in canonical form, saved as the editor saves, so a
+/// `.bak` copy is kept when the nearest `.bmermaid.toml` sets `save.backup`.
+/// With `--check` nothing is written and files that would change are listed
+/// instead. Source from stdin is written to stdout.
+fn format_files(matches: &Matches, stdin: Option<&str>) -> i32 {
+    let check_only = arg_flag(matches, "check");
+    let files = match subcommand_files(matches) {
+        Ok(files) => files,
+        Err(code) => return code,
+    };
+
+    let mut code = 0;
+    for file in files {
+        let path = display_name(&file);
+        let source = match read_input(&file, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                code = EXIT_USAGE_ERROR;
+                continue;
+            }
+        };
+        let formatted = match format::format(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                let index = check::LineIndex::new(&source);
+                for error in &errors {
+                    let (line, column) = index.position(error.span.start);
+                    eprintln!("{path}:{line}:{column}: {}", error.message);
+                }
+                code = code.max(EXIT_DIAGRAM_ERROR);
+                continue;
+            }
+        };
+        if file == Path::new(STDIN_ARG) && !check_only {
+            print!("{formatted}");
+            continue;
+        }
+        if formatted == source {
+            continue;
+        }
+        if check_only {
+            println!("{path}: not formatted");
+            code = code.max(EXIT_DIAGRAM_ERROR);
+        } else {
+            // A broken config must not stand in the way of saving.
+            let backup = Config::for_file(&file)
+                .map(|config| config.save.backup)
+                .unwrap_or(false);
+            if let Err(e) = save::save(&file, &formatted, backup) {
+                eprintln!("{e}");
+                code = EXIT_USAGE_ERROR;
+            }
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use tauri_plugin_cli::ArgData;
+
+    use super::*;
+
+    /// Matches as the CLI plugin produces them.
+    fn matches(args: &[(&str, Value)]) -> Matches {
+        let mut matches = Matches::default();
+        for (name, value) in args {
+            let mut data = ArgData::default();
+            data.value = value.clone();
+            matches.args.insert(name.to_string(), data);
+        }
+        matches
+    }
+
+    /// A fresh directory with an empty file for each of `names`.
+    fn files(test: &str, names: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bmermaid-cli-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in names {
+            fs::write(dir.join(name), "flowchart TD\n").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn positions_after_file_arguments() {
+        assert_eq!(split_position("a.mmd"), Ok(("a.mmd", None, None)));
+        assert_eq!(split_position("a.mmd:12"), Ok(("a.mmd", Some(12), None)));
+        assert_eq!(
+            split_position("a.mmd:12:4"),
+            Ok(("a.mmd", Some(12), Some(4)))
+        );
+        assert_eq!(
+            split_position("docs/*.mmd:3"),
+            Ok(("docs/*.mmd", Some(3), None))
+        );
+        assert_eq!(split_position("a:1:2:3"), Ok(("a:1", Some(2), Some(3))));
+        assert_eq!(split_position(":12"), Ok((":12", None, None)));
+        assert_eq!(split_position("a.mmd:x"), Ok(("a.mmd:x", None, None)));
+    }
+
+    #[test]
+    fn positions_start_at_one() {
+        assert!(split_position("a.mmd:0").is_err());
+        assert!(split_position("a.mmd:3:0").is_err());
+        assert!(split_position("a.mmd:0:3").is_err());
+    }
+
+    #[test]
+    fn existing_files_keep_their_colons() {
+        let dir = files("colons", &["notes:12"]);
+        let path = dir.join("notes:12").display().to_string();
+        assert_eq!(split_position(&path), Ok((path.as_str(), None, None)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn positionals_skip_option_values() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert!(!has_positional(&args(&["-t", "dark", "--line", "3"])));
+        assert!(has_positional(&args(&["--theme", "nord", "a.mmd"])));
+        assert!(has_positional(&args(&["render"])));
+        assert!(!has_positional(&args(&["--help"])));
+        assert!(!has_positional(&args(&["-"])));
+    }
+
+    #[test]
+    fn stdin_is_a_file_argument_only() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let stdin = |list: &[&str]| positionals(&args(list)).any(|arg| arg == STDIN_ARG);
+        assert!(!stdin(&["render", "x.mmd", "-o", "-"]));
+        assert!(!stdin(&["render", "x.mmd", "--output", "-", "-f", "png"]));
+        assert!(stdin(&["render", "-", "-o", "-"]));
+        assert!(stdin(&["check", "--format", "json", "-"]));
+        assert!(stdin(&["-"]));
+    }
+
+    #[test]
+    fn value_options_match_the_cli_definition() {
+        let config: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        let mut commands = vec![&config["plugins"]["cli"]];
+        while let Some(command) = commands.pop() {
+            if let Some(subcommands) = command["subcommands"].as_object() {
+                commands.extend(subcommands.values());
+            }
+            for arg in command["args"].as_array().into_iter().flatten() {
+                if arg["takesValue"] != json!(true) || arg["index"].is_number() {
+                    continue;
+                }
+                let long = format!("--{}", arg["name"].as_str().unwrap());
+                assert!(VALUE_OPTIONS.contains(&long.as_str()), "{long}");
+                if let Some(short) = arg["short"].as_str() {
+                    assert!(
+                        VALUE_OPTIONS.contains(&format!("-{short}").as_str()),
+                        "-{short}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn themes_match_the_cli_definition() {
+        let config: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        let cli = &config["plugins"]["cli"];
+        let names: Vec<_> = THEMES.iter().map(|(name, _)| json!(name)).collect();
+        let theme_values = |command: &serde_json::Value| {
+            command["args"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|arg| arg["name"] == "theme")
+                .map(|arg| arg["possibleValues"].clone())
+                .unwrap()
+        };
+        assert_eq!(theme_values(cli), json!(names));
+        assert_eq!(theme_values(&cli["subcommands"]["render"]), json!(names));
+    }
+
+    #[test]
+    fn line_goes_to_the_first_document_without_a_position() {
+        let dir = files("line", &["a.mmd", "b.mmd"]);
+        let file = |name: &str| dir.join(name).display().to_string();
+        let matches = matches(&[
+            (
+                "files",
+                json!([format!("{}:3", file("a.mmd")), file("b.mmd")]),
+            ),
+            ("line", json!("9")),
+            ("column", json!("2")),
+        ]);
+
+        let documents = open_args(&matches, None).unwrap();
+        let positions: Vec<_> = documents.iter().map(|d| (d.line, d.column)).collect();
+        assert_eq!(positions, [(Some(3), None), (Some(9), Some(2))]);
+
+        let documents = open_args(&matches, Some("flowchart LR\n".to_string())).unwrap();
+        let positions: Vec<_> = documents.iter().map(|d| (d.line, d.column)).collect();
+        assert_eq!(
+            positions,
+            [(Some(9), Some(2)), (Some(3), None), (None, None)]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zero_lines_are_rejected() {
+        assert!(open_args(&matches(&[("line", json!("0"))]), None).is_err());
+        assert!(open_args(&matches(&[("files", json!(["a.mmd:0"]))]), None).is_err());
+    }
+}
@@ -0,0 +1,555 @@
+//! Shell completions and the man page, generated from the CLI definition in
+//! `plugins.cli` of `tauri.conf.json` so they cannot drift from what the
+//! parser accepts.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+const BIN: &str = "bmermaid";
+
+/// Options whose value is a number, colour or size rather than a path.
+const NON_PATH_ARGS: &[&str] = &[
+    "line",
+    "column",
+    "scale",
+    "dpi",
+    "width",
+    "height",
+    "background",
+    "page-size",
+    "margin",
+    "id-prefix",
+];
+
+/// The parts of the CLI plugin's command definition used here.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Command {
+    description: Option<String>,
+    long_description: Option<String>,
+    after_help: Option<String>,
+    #[serde(default)]
+    args: Vec<Arg>,
+    #[serde(default)]
+    subcommands: BTreeMap<String, Command>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Arg {
+    name: String,
+    short: Option<char>,
+    description: Option<String>,
+    #[serde(default)]
+    takes_value: bool,
+    #[serde(default)]
+    multiple: bool,
+    possible_values: Option<Vec<String>>,
+    index: Option<usize>,
+    #[serde(default)]
+    required: bool,
+}
+
+impl Arg {
+    fn is_positional(&self) -> bool {
+        self.index.is_some()
+    }
+
+    fn takes_path(&self) -> bool {
+        self.takes_value
+            && self.possible_values.is_none()
+            && !NON_PATH_ARGS.contains(&self.name.as_str())
+    }
+
+    fn description(&self) -> &str {
+        self.description.as_deref().unwrap_or_default()
+    }
+
+    fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if let Some(short) = self.short {
+            flags.push(format!("-{short}"));
+        }
+        flags.push(format!("--{}", self.name));
+        flags
+    }
+}
+
+impl Command {
+    /// Reads the definition from the `cli` entry of the app's plugin config.
+    pub fn from_config(config: &serde_json::Value) -> Result<Command, String> {
+        Command::deserialize(config).map_err(|e| format!("invalid CLI definition: {e}"))
+    }
+
+    fn options(&self) -> impl Iterator<Item = &Arg> {
+        self.args.iter().filter(|arg| !arg.is_positional())
+    }
+
+    fn positionals(&self) -> impl Iterator<Item = &Arg> {
+        self.args.iter().filter(|arg| arg.is_positional())
+    }
+
+    /// The values a positional argument takes; `None` for files.
+    fn positional_values(&self) -> Option<&[String]> {
+        self.positionals()
+            .find_map(|arg| arg.possible_values.as_deref())
+    }
+}
+
+pub fn bash(root: &Command) -> String {
+    let mut out = String::new();
+    let names: Vec<&str> = root.subcommands.keys().map(String::as_str).collect();
+    writeln!(out, "# bash completion for {BIN}").unwrap();
+    writeln!(out, "_{BIN}() {{").unwrap();
+    writeln!(out, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+    writeln!(out, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"").unwrap();
+    writeln!(out, "    local cmd=\"\" word").unwrap();
+    writeln!(
+        out,
+        "    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do"
+    )
+    .unwrap();
+    writeln!(out, "        case \"$word\" in").unwrap();
+    writeln!(
+        out,
+        "            {}) cmd=\"$word\"; break ;;",
+        names.join("|")
+    )
+    .unwrap();
+    writeln!(out, "        esac").unwrap();
+    writeln!(out, "    done").unwrap();
+    writeln!(out, "    case \"$cmd\" in").unwrap();
+    write_bash_command(&mut out, "\"\"", root, &names);
+    for (name, command) in &root.subcommands {
+        write_bash_command(&mut out, name, command, &[]);
+    }
+    writeln!(out, "    esac").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out, "complete -o filenames -o bashdefault -F _{BIN} {BIN}").unwrap();
+    out
+}
+
+fn write_bash_command(out: &mut String, pattern: &str, command: &Command, subcommands: &[&str]) {
+    writeln!(out, "        {pattern})").unwrap();
+    writeln!(out, "            case \"$prev\" in").unwrap();
+    for arg in command.options().filter(|arg| arg.takes_value) {
+        let completion = match &arg.possible_values {
+            Some(values) => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); ",
+                values.join(" ")
+            ),
+            None if arg.takes_path() => "COMPREPLY=($(compgen -f -- \"$cur\")); ".to_string(),
+            None => String::new(),
+        };
+        writeln!(
+            out,
+            "                {}) {completion}return ;;",
+            arg.flags().join("|")
+        )
+        .unwrap();
+    }
+    writeln!(out, "            esac").unwrap();
+
+    let flags: Vec<String> = command.options().flat_map(Arg::flags).collect();
+    writeln!(out, "            if [[ \"$cur\" == -* ]]; then").unwrap();
+    writeln!(
+        out,
+        "                COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        flags.join(" ")
+    )
+    .unwrap();
+    writeln!(out, "            else").unwrap();
+    let mut words = String::new();
+    if !subcommands.is_empty() {
+        write!(
+            words,
+            "$(compgen -W \"{}\" -- \"$cur\") ",
+            subcommands.join(" ")
+        )
+        .unwrap();
+    }
+    if let Some(values) = command.positional_values() {
+        write!(words, "$(compgen -W \"{}\" -- \"$cur\")", values.join(" ")).unwrap();
+    } else if command.positionals().next().is_some() {
+        words.push_str("$(compgen -f -- \"$cur\")");
+    }
+    writeln!(out, "                COMPREPLY=({})", words.trim_end()).unwrap();
+    writeln!(out, "            fi").unwrap();
+    writeln!(out, "            ;;").unwrap();
+}
+
+pub fn zsh(root: &Command) -> String {
+    let mut out = String::new();
+    writeln!(out, "#compdef {BIN}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "_{BIN}() {{").unwrap();
+    writeln!(out, "    local curcontext=\"$curcontext\" state line").unwrap();
+    writeln!(out, "    _arguments -C \\").unwrap();
+    for arg in root.options() {
+        writeln!(out, "        {} \\", zsh_option(arg)).unwrap();
+    }
+    writeln!(out, "        '1: :->first' \\").unwrap();
+    writeln!(out, "        '*:: :->rest'").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    case $state in").unwrap();
+    writeln!(out, "        first)").unwrap();
+    writeln!(out, "            local -a commands").unwrap();
+    writeln!(out, "            commands=(").unwrap();
+    for (name, command) in &root.subcommands {
+        let description = command.description.as_deref().unwrap_or_default();
+        writeln!(out, "                '{name}:{}'", zsh_escape(description)).unwrap();
+    }
+    writeln!(out, "            )").unwrap();
+    writeln!(out, "            _alternative 'commands:command:_describe command commands' 'files:file:_files'").unwrap();
+    writeln!(out, "            ;;").unwrap();
+    writeln!(out, "        rest)").unwrap();
+    writeln!(out, "            case $line[1] in").unwrap();
+    for (name, command) in &root.subcommands {
+        writeln!(out, "                {name})").unwrap();
+        let mut specs: Vec<String> = command.options().map(zsh_option).collect();
+        if let Some(values) = command.positional_values() {
+            specs.push(format!("'1:value:({})'", values.join(" ")));
+        } else if command.positionals().next().is_some() {
+            specs.push("'*:file:_files'".to_string());
+        }
+        if specs.is_empty() {
+            writeln!(out, "                    _message 'no more arguments'").unwrap();
+        } else {
+            writeln!(out, "                    _arguments \\").unwrap();
+            let specs = specs.join(" \\\n                        ");
+            writeln!(out, "                        {specs}").unwrap();
+        }
+        writeln!(out, "                    ;;").unwrap();
+    }
+    writeln!(out, "                *) _files ;;").unwrap();
+    writeln!(out, "            esac").unwrap();
+    writeln!(out, "            ;;").unwrap();
+    writeln!(out, "    esac").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "_{BIN} \"$@\"").unwrap();
+    out
+}
+
+fn zsh_option(arg: &Arg) -> String {
+    let description = zsh_escape(arg.description());
+    let value = if !arg.takes_value {
+        String::new()
+    } else {
+        match &arg.possible_values {
+            Some(values) => format!(":{}:({})", arg.name, values.join(" ")),
+            None if arg.takes_path() => format!(":{}:_files", arg.name),
+            None => format!(":{}: ", arg.name),
+        }
+    };
+    match arg.short {
+        Some(short) => format!(
+            "'(-{short} --{name})'{{-{short},--{name}}}'[{description}]{value}'",
+            name = arg.name
+        ),
+        None => format!("'--{}[{description}]{value}'", arg.name),
+    }
+}
+
+/// Escapes text for a single-quoted `_arguments` spec.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+pub fn fish(root: &Command) -> String {
+    let mut out = String::new();
+    writeln!(out, "# fish completion for {BIN}").unwrap();
+    for arg in root.options() {
+        write_fish_option(&mut out, "__fish_use_subcommand", arg);
+    }
+    for (name, command) in &root.subcommands {
+        let description = command.description.as_deref().unwrap_or_default();
+        writeln!(
+            out,
+            "complete -c {BIN} -n __fish_use_subcommand -f -a {name} -d '{}'",
+            fish_escape(description)
+        )
+        .unwrap();
+    }
+    for (name, command) in &root.subcommands {
+        let condition = format!("'__fish_seen_subcommand_from {name}'");
+        for arg in command.options() {
+            write_fish_option(&mut out, &condition, arg);
+        }
+        if let Some(values) = command.positional_values() {
+            writeln!(
+                out,
+                "complete -c {BIN} -n {condition} -f -a '{}'",
+                values.join(" ")
+            )
+            .unwrap();
+        } else if command.positionals().next().is_none() {
+            writeln!(out, "complete -c {BIN} -n {condition} -f").unwrap();
+        }
+    }
+    out
+}
+
+fn write_fish_option(out: &mut String, condition: &str, arg: &Arg) {
+    write!(out, "complete -c {BIN} -n {condition}").unwrap();
+    if let Some(short) = arg.short {
+        write!(out, " -s {short}").unwrap();
+    }
+    write!(out, " -l {}", arg.name).unwrap();
+    if arg.takes_value {
+        match &arg.possible_values {
+            Some(values) => write!(out, " -x -a '{}'", values.join(" ")).unwrap(),
+            None if arg.takes_path() => write!(out, " -r").unwrap(),
+            None => write!(out, " -x").unwrap(),
+        }
+    }
+    writeln!(out, " -d '{}'", fish_escape(arg.description())).unwrap();
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// A roff man page for section 1.
+pub fn manpage(root: &Command, version: &str) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".TH {} 1 \"\" \"{BIN} {version}\" \"User Commands\"",
+        BIN.to_uppercase()
+    )
+    .unwrap();
+    writeln!(out, ".SH NAME").unwrap();
+    writeln!(
+        out,
+        "{BIN} \\- {}",
+        roff(root.description.as_deref().unwrap_or_default())
+    )
+    .unwrap();
+
+    writeln!(out, ".SH SYNOPSIS").unwrap();
+    writeln!(out, ".B {BIN}").unwrap();
+    writeln!(out, "{}", roff_usage(root)).unwrap();
+    for (name, command) in &root.subcommands {
+        writeln!(out, ".br").unwrap();
+        writeln!(out, ".B {BIN} {name}").unwrap();
+        let usage = roff_usage(command);
+        if !usage.is_empty() {
+            writeln!(out, "{usage}").unwrap();
+        }
+    }
+
+    if let Some(description) = &root.long_description {
+        writeln!(out, ".SH DESCRIPTION").unwrap();
+        write_paragraphs(&mut out, description);
+    }
+
+    writeln!(out, ".SH OPTIONS").unwrap();
+    write_args(&mut out, root);
+
+    writeln!(out, ".SH COMMANDS").unwrap();
+    for (name, command) in &root.subcommands {
+        writeln!(out, ".SS {name}").unwrap();
+        if let Some(description) = &command.description {
+            write_paragraphs(&mut out, description);
+        }
+        write_args(&mut out, command);
+        if let Some(after_help) = &command.after_help {
+            writeln!(out, ".PP").unwrap();
+            write_preformatted(&mut out, after_help);
+        }
+    }
+
+    if let Some(after_help) = &root.after_help {
+        writeln!(out, ".SH EXAMPLES").unwrap();
+        let examples = after_help.strip_prefix("Examples:\n").unwrap_or(after_help);
+        write_preformatted(&mut out, examples);
+    }
+    out
+}
+
+fn roff_usage(command: &Command) -> String {
+    let mut usage = Vec::new();
+    if command.options().next().is_some() {
+        usage.push("[\\fIOPTIONS\\fR]".to_string());
+    }
+    for arg in command.positionals() {
+        let name = format!("\\fI{}\\fR", arg.name.to_uppercase());
+        let name = if arg.multiple {
+            format!("{name}...")
+        } else {
+            name
+        };
+        usage.push(if arg.required {
+            name
+        } else {
+            format!("[{name}]")
+        });
+    }
+    usage.join(" ")
+}
+
+fn write_args(out: &mut String, command: &Command) {
+    for arg in &command.args {
+        writeln!(out, ".TP").unwrap();
+        let head = if arg.is_positional() {
+            format!("\\fI{}\\fR", arg.name.to_uppercase())
+        } else {
+            let flags: Vec<String> = arg
+                .flags()
+                .iter()
+                .map(|flag| format!("\\fB{}\\fR", flag.replace('-', "\\-")))
+                .collect();
+            let value = if arg.takes_value {
+                format!(" \\fI<{}>\\fR", arg.name.to_uppercase())
+            } else {
+                String::new()
+            };
+            format!("{}{value}", flags.join(", "))
+        };
+        writeln!(out, "{head}").unwrap();
+        writeln!(out, "{}", roff(arg.description())).unwrap();
+        if let Some(values) = &arg.possible_values {
+            writeln!(out, ".br").unwrap();
+            writeln!(out, "Possible values: {}", roff(&values.join(", "))).unwrap();
+        }
+    }
+}
+
+fn write_paragraphs(out: &mut String, text: &str) {
+    for (i, paragraph) in text.split("\n\n").enumerate() {
+        if i > 0 {
+            writeln!(out, ".PP").unwrap();
+        }
+        writeln!(out, "{}", roff(paragraph)).unwrap();
+    }
+}
+
+fn write_preformatted(out: &mut String, text: &str) {
+    writeln!(out, ".nf").unwrap();
+    for line in text.lines() {
+        writeln!(out, "{}", roff(line)).unwrap();
+    }
+    writeln!(out, ".fi").unwrap();
+}
+
+/// Escapes text for roff: backslashes, and control characters that would
+/// start a request at the beginning of a line.
+fn roff(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{line}")
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The definition this app ships with.
+    fn shipped() -> Command {
+        let config: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        Command::from_config(&config["plugins"]["cli"]).unwrap()
+    }
+
+    /// A definition with one subcommand whose texts need escaping.
+    fn quoted() -> Command {
+        Command::from_config(&serde_json::json!({
+            "description": "Edit diagrams - fast",
+            "afterHelp": "Examples:\n  bmermaid a.mmd\n.hidden",
+            "args": [{
+                "name": "theme",
+                "short": "t",
+                "description": "The diagram's theme: [light] or dark",
+                "takesValue": true,
+                "possibleValues": ["light", "dark"]
+            }],
+            "subcommands": {
+                "check": {
+                    "description": "Don't render: check",
+                    "args": [{
+                        "name": "files",
+                        "index": 1,
+                        "takesValue": true,
+                        "multiple": true,
+                        "required": true
+                    }]
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn scripts_cover_subcommands_and_themes() {
+        let root = shipped();
+        let themes = root
+            .args
+            .iter()
+            .find(|arg| arg.name == "theme")
+            .and_then(|arg| arg.possible_values.clone())
+            .unwrap();
+        assert!(themes.contains(&"tokyo-night".to_string()));
+        for script in [bash(&root), zsh(&root), fish(&root)] {
+            for name in root.subcommands.keys() {
+                assert!(script.contains(name.as_str()), "{name}");
+            }
+            assert!(script.contains(&themes.join(" ")));
+        }
+        let names: Vec<&str> = root.subcommands.keys().map(String::as_str).collect();
+        assert!(bash(&root).contains(&format!("{}) cmd=\"$word\"", names.join("|"))));
+        assert!(fish(&root).contains("-n __fish_use_subcommand -f -a render"));
+    }
+
+    #[test]
+    fn escapes_descriptions() {
+        assert_eq!(zsh_escape("Don't: [x]"), "Don'\\''t\\: \\[x\\]");
+        assert_eq!(fish_escape("Don't: C:\\"), "Don\\'t: C:\\\\");
+
+        let root = quoted();
+        let zsh = zsh(&root);
+        assert!(zsh.contains("'check:Don'\\''t render\\: check'"));
+        assert!(zsh.contains(
+            "'(-t --theme)'{-t,--theme}'[The diagram'\\''s theme\\: \\[light\\] or dark]:theme:(light dark)'"
+        ));
+        let fish = fish(&root);
+        assert!(fish.contains("-f -a check -d 'Don\\'t render: check'"));
+        assert!(fish.contains(
+            "-s t -l theme -x -a 'light dark' -d 'The diagram\\'s theme: [light] or dark'"
+        ));
+    }
+
+    #[test]
+    fn manpage_is_roff() {
+        let page = manpage(&quoted(), "1.2.3");
+        assert!(page.starts_with(".TH BMERMAID 1 \"\" \"bmermaid 1.2.3\" \"User Commands\"\n"));
+        for line in [
+            "bmermaid \\- Edit diagrams \\- fast",
+            ".B bmermaid\n[\\fIOPTIONS\\fR]\n.br\n.B bmermaid check\n\\fIFILES\\fR...",
+            ".TP\n\\fB\\-t\\fR, \\fB\\-\\-theme\\fR \\fI<THEME>\\fR\n",
+            ".br\nPossible values: light, dark\n",
+            ".SS check\nDon't render: check\n.TP\n\\fIFILES\\fR\n",
+            ".SH EXAMPLES\n.nf\n  bmermaid a.mmd\n\\&.hidden\n.fi\n",
+        ] {
+            assert!(page.contains(line), "{line}");
+        }
+
+        let page = manpage(&shipped(), "0.1.0");
+        for name in shipped().subcommands.keys() {
+            assert!(page.contains(&format!(".SS {name}\n")), "{name}");
+        }
+    }
+}
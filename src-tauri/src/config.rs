@@ -0,0 +1,105 @@
+//! Project settings read from `.bmermaid.toml`.
+//!
+//! ```toml
+//! [lint]
+//! self-edge = "error"
+//! unreachable-node = "off"
+//!
+//! [save]
+//! backup = true
+//!
+//! [export]
+//! prefix-ids = true
+//! styles = "inline"
+//! embed-fonts = true
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::lint::{self, Level};
+use crate::postprocess::PostProcessOptions;
+
+pub const CONFIG_FILE: &str = ".bmermaid.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Severity overrides keyed by lint rule name.
+    #[serde(default)]
+    pub lint: HashMap<String, Level>,
+    #[serde(default)]
+    pub save: SaveConfig,
+    /// How exported SVGs are post-processed.
+    #[serde(default)]
+    pub export: PostProcessOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SaveConfig {
+    /// Keep the previous version of a saved file as `<file>.bak`.
+    #[serde(default)]
+    pub backup: bool,
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl Config {
+    /// Loads the nearest `.bmermaid.toml` in `dir` or one of its ancestors.
+    /// No file at all means the defaults.
+    pub fn discover(dir: &Path) -> Result<Config, ConfigError> {
+        match dir
+            .ancestors()
+            .map(|d| d.join(CONFIG_FILE))
+            .find(|p| p.is_file())
+        {
+            Some(path) => Config::load(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Like [`Config::discover`], starting from the directory of `file`,
+    /// which need not exist yet.
+    pub fn for_file(file: &Path) -> Result<Config, ConfigError> {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        let dir = file
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        Config::discover(dir)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let error = |message: String| ConfigError {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let config: Config = toml::from_str(&text).map_err(|e| error(e.message().to_string()))?;
+        if let Some(name) = config.lint.keys().find(|name| lint::rule(name).is_none()) {
+            return Err(error(format!("unknown lint rule `{name}`")));
+        }
+        config.export.validate().map_err(error)?;
+        Ok(config)
+    }
+
+    /// The configured level for `rule`, or its default.
+    pub fn level(&self, rule: &lint::Rule) -> Level {
+        self.lint.get(rule.name).copied().unwrap_or(rule.default)
+    }
+}
@@ -0,0 +1,150 @@
+//! Line diff between a file on disk and the editor's copy of it.
+
+use serde::Serialize;
+
+/// Above this many line pairs the middle of the diff is shown as a plain
+/// replacement rather than aligned.
+const MAX_TABLE: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineChange {
+    Unchanged,
+    /// Only in the new text.
+    Added,
+    /// Only in the old text.
+    Removed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub change: LineChange,
+    pub text: String,
+}
+
+/// Aligns the lines of `old` and `new` along their longest common
+/// subsequence.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let line = |change, text: &str| DiffLine {
+        change,
+        text: text.to_string(),
+    };
+    let mut lines: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|text| line(LineChange::Unchanged, text))
+        .collect();
+
+    if a.len() * b.len() > MAX_TABLE {
+        lines.extend(a.iter().map(|text| line(LineChange::Removed, text)));
+        lines.extend(b.iter().map(|text| line(LineChange::Added, text)));
+    } else {
+        // lcs[i][j]: length of the common subsequence of a[i..] and b[j..].
+        let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                lines.push(line(LineChange::Unchanged, a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                lines.push(line(LineChange::Removed, a[i]));
+                i += 1;
+            } else {
+                lines.push(line(LineChange::Added, b[j]));
+                j += 1;
+            }
+        }
+    }
+
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|text| line(LineChange::Unchanged, text)),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The diff as `" kept"`, `"+added"` and `"-removed"` lines.
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        diff_lines(old, new)
+            .into_iter()
+            .map(|line| {
+                let sign = match line.change {
+                    LineChange::Unchanged => ' ',
+                    LineChange::Added => '+',
+                    LineChange::Removed => '-',
+                };
+                format!("{sign}{}", line.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inserts() {
+        assert_eq!(diff("a\nc\n", "a\nb\nc\n"), [" a", "+b", " c"]);
+        assert_eq!(diff("b\n", "a\nb\nc\n"), ["+a", " b", "+c"]);
+    }
+
+    #[test]
+    fn deletes() {
+        assert_eq!(diff("a\nb\nc\n", "a\nc\n"), [" a", "-b", " c"]);
+        assert_eq!(diff("a\nb\nc", "b"), ["-a", " b", "-c"]);
+    }
+
+    #[test]
+    fn replaces() {
+        assert_eq!(diff("a\nb\nc\n", "a\nx\nc\n"), [" a", "-b", "+x", " c"]);
+        assert_eq!(
+            diff("a\nb\nc\nd\n", "x\nb\nd\ny\n"),
+            ["-a", "+x", " b", "-c", " d", "+y"]
+        );
+    }
+
+    #[test]
+    fn empty_sides() {
+        assert!(diff("", "").is_empty());
+        assert_eq!(diff("", "a\nb\n"), ["+a", "+b"]);
+        assert_eq!(diff("a\nb\n", ""), ["-a", "-b"]);
+        assert_eq!(diff("a\r\nb", "a\nb\n"), [" a", " b"]);
+    }
+
+    #[test]
+    fn large_changes_are_not_aligned() {
+        let old: String = (0..2001).map(|i| format!("{i}\n")).collect();
+        let new: String = (0..2001).map(|i| format!("{}\n", 2000 - i)).collect();
+        let lines = diff_lines(&old, &new);
+        assert_eq!(lines.len(), 4002);
+        assert!(lines[..2001]
+            .iter()
+            .all(|l| l.change == LineChange::Removed));
+        assert!(lines[2001..].iter().all(|l| l.change == LineChange::Added));
+    }
+}
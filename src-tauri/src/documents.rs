@@ -0,0 +1,279 @@
+//! Document windows.
+//!
+//! Every window edits one document. The webview reports the document's path
+//! and dirty flag with `update_document`, which keeps the title current and
+//! lets [`guard_close`] ask about unsaved changes before the window goes away.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{
+    AppHandle, Emitter, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent,
+};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+use crate::autosave::Autosave;
+use crate::embed;
+use crate::export::ExportDirs;
+use crate::watch::Watchers;
+use crate::CliArgs;
+
+/// Asks the window to save its document and close once that succeeded.
+pub const SAVE_AND_CLOSE_EVENT: &str = "save-and-close";
+
+/// Moves the cursor of a window whose file was opened again at a position.
+pub const GO_TO_EVENT: &str = "go-to";
+
+const APP_TITLE: &str = "Mermaid Editor";
+const LABEL_PREFIX: &str = "document-";
+
+const SAVE: &str = "Save";
+const DISCARD: &str = "Don't Save";
+
+/// A cursor position, 1-based.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Position {
+    line: u32,
+    column: u32,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Document {
+    path: Option<PathBuf>,
+    dirty: bool,
+}
+
+/// Documents keyed by window label, kept in Tauri's managed state.
+#[derive(Default)]
+pub struct Documents {
+    windows: Mutex<HashMap<String, Document>>,
+    next_id: AtomicUsize,
+}
+
+impl Documents {
+    pub fn update(&self, label: &str, path: Option<PathBuf>, dirty: bool) {
+        self.windows
+            .lock()
+            .unwrap()
+            .insert(label.to_string(), Document { path, dirty });
+    }
+
+    pub fn remove(&self, label: &str) {
+        self.windows.lock().unwrap().remove(label);
+    }
+
+    pub fn is_dirty(&self, label: &str) -> bool {
+        self.windows
+            .lock()
+            .unwrap()
+            .get(label)
+            .is_some_and(|document| document.dirty)
+    }
+
+    /// The file open in window `label`, `None` for an unsaved buffer.
+    pub fn path(&self, label: &str) -> Option<PathBuf> {
+        self.windows
+            .lock()
+            .unwrap()
+            .get(label)
+            .and_then(|document| document.path.clone())
+    }
+
+    /// The window that already has `path` open.
+    pub fn find(&self, path: &Path) -> Option<String> {
+        let path = canonical(path);
+        self.windows
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, document)| {
+                document
+                    .path
+                    .as_deref()
+                    .is_some_and(|p| canonical(p) == path)
+            })
+            .map(|(label, _)| label.clone())
+    }
+
+    fn next_label(&self) -> String {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{LABEL_PREFIX}{id}")
+    }
+}
+
+/// Opens a window for `args`: the file it names, an unsaved buffer with its
+/// source or the source embedded in the image it names, or an empty
+/// document. A file that is already open has its window focused instead.
+pub fn open(app: &AppHandle, args: CliArgs) -> tauri::Result<WebviewWindow> {
+    let args = embed::resolve(args)?;
+    let documents = app.state::<Documents>();
+    let path = args.file.as_ref().map(PathBuf::from);
+    if let Some(path) = &path {
+        let existing = documents
+            .find(path)
+            .and_then(|label| app.get_webview_window(&label));
+        if let Some(window) = existing {
+            window.unminimize()?;
+            window.set_focus()?;
+            if let Some(line) = args.line {
+                let position = Position {
+                    line,
+                    column: args.column.unwrap_or(1),
+                };
+                window.emit_to(window.label(), GO_TO_EVENT, position)?;
+            }
+            return Ok(window);
+        }
+    }
+
+    let window = WebviewWindowBuilder::new(app, documents.next_label(), WebviewUrl::default())
+        .title(title(path.as_deref(), false))
+        .inner_size(1200.0, 800.0)
+        .min_inner_size(800.0, 600.0)
+        .initialization_script(crate::cli_args_script(&args))
+        .build()?;
+    documents.update(window.label(), path, args.source.is_some());
+    guard_close(&window);
+    Ok(window)
+}
+
+/// `name.mmd • — Mermaid Editor`, with the dot while there are unsaved
+/// changes.
+pub fn title(path: Option<&Path>, dirty: bool) -> String {
+    let marker = if dirty { " •" } else { "" };
+    format!("{}{marker} — {APP_TITLE}", display_name(path))
+}
+
+fn display_name(path: Option<&Path>) -> String {
+    path.and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+/// Asks before closing a window with unsaved changes, and forgets the
+/// window's document once it is closed.
+pub fn guard_close(window: &WebviewWindow) {
+    let guarded = window.clone();
+    window.on_window_event(move |event| match event {
+        WindowEvent::CloseRequested { api, .. } => {
+            let app = guarded.app_handle();
+            if app.state::<Documents>().is_dirty(guarded.label()) {
+                api.prevent_close();
+                confirm_close(&guarded);
+            }
+        }
+        WindowEvent::Destroyed => {
+            let app = guarded.app_handle();
+            app.state::<Documents>().remove(guarded.label());
+            app.state::<Watchers>().unwatch(guarded.label());
+            app.state::<ExportDirs>().forget(guarded.label());
+        }
+        _ => {}
+    });
+}
+
+/// Closes every window, each asking about unsaved changes just as closing
+/// it by hand does. The app exits once the last one is gone; cancelling
+/// keeps that window, and the app, open.
+pub fn close_all(app: &AppHandle) {
+    for window in app.webview_windows().into_values() {
+        let _ = window.close();
+    }
+}
+
+fn confirm_close(window: &WebviewWindow) {
+    let path = window.state::<Documents>().path(window.label());
+    let name = display_name(path.as_deref());
+
+    let window = window.clone();
+    window
+        .dialog()
+        .message(format!(
+            "Do you want to save the changes you made to {name}?"
+        ))
+        .title("Unsaved Changes")
+        .kind(MessageDialogKind::Warning)
+        .parent(&window)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            SAVE.to_string(),
+            DISCARD.to_string(),
+            "Cancel".to_string(),
+        ))
+        .show_with_result(move |result| match result {
+            MessageDialogResult::Yes => save_and_close(&window),
+            MessageDialogResult::Custom(button) if button == SAVE => save_and_close(&window),
+            MessageDialogResult::No => discard_and_close(&window),
+            MessageDialogResult::Custom(button) if button == DISCARD => discard_and_close(&window),
+            _ => {}
+        });
+}
+
+/// The webview saves and then closes the window again, which passes the
+/// guard once the document is clean.
+fn save_and_close(window: &WebviewWindow) {
+    let _ = window.emit_to(window.label(), SAVE_AND_CLOSE_EVENT, ());
+}
+
+fn discard_and_close(window: &WebviewWindow) {
+    window.state::<Autosave>().clear(window.label());
+    let _ = window.destroy();
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_each_windows_document() {
+        let dir = std::env::temp_dir().join(format!("bmermaid-documents-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.mmd");
+        std::fs::write(&file, "flowchart TD\n").unwrap();
+
+        let documents = Documents::default();
+        documents.update("main", Some(file.clone()), false);
+        documents.update("document-1", None, true);
+        assert!(!documents.is_dirty("main"));
+        assert!(documents.is_dirty("document-1"));
+        assert!(!documents.is_dirty("document-2"));
+        assert_eq!(documents.path("main"), Some(file.clone()));
+        assert_eq!(documents.path("document-1"), None);
+
+        // Found however the path is spelled.
+        let spelled = dir.join(".").join("a.mmd");
+        assert_eq!(documents.find(&spelled).as_deref(), Some("main"));
+        assert_eq!(documents.find(&dir.join("b.mmd")), None);
+
+        documents.update("main", Some(file.clone()), true);
+        assert!(documents.is_dirty("main"));
+        documents.remove("main");
+        assert!(!documents.is_dirty("main"));
+        assert_eq!(documents.find(&file), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn labels_are_unique() {
+        let documents = Documents::default();
+        assert_eq!(documents.next_label(), "document-1");
+        assert_eq!(documents.next_label(), "document-2");
+    }
+
+    #[test]
+    fn titles() {
+        let path = Path::new("/tmp/diagrams/flow.mmd");
+        assert_eq!(title(Some(path), false), "flow.mmd — Mermaid Editor");
+        assert_eq!(title(Some(path), true), "flow.mmd • — Mermaid Editor");
+        assert_eq!(title(None, false), "Untitled — Mermaid Editor");
+        assert_eq!(title(None, true), "Untitled • — Mermaid Editor");
+    }
+}
@@ -0,0 +1,297 @@
+//! The Mermaid source carried inside exported images, so an exported SVG or
+//! PNG can be opened for editing again.
+//!
+//! SVGs get a `<metadata>` element with a `source` element in
+//! [`SVG_NAMESPACE`]; PNGs get an `iTXt` chunk with the keyword
+//! [`PNG_KEYWORD`]. `tEXt` chunks with that keyword are read as well.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::CliArgs;
+
+pub const SVG_NAMESPACE: &str = "urn:bmermaid:source";
+pub const PNG_KEYWORD: &str = "mermaid";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// `svg` with `source` in its metadata, replacing source embedded earlier.
+pub fn svg_with_source(svg: &str, source: &str) -> String {
+    let svg = strip_svg_source(svg);
+    let Some(end) = svg_start_tag_end(&svg) else {
+        return svg;
+    };
+    let metadata = format!(
+        "<metadata><mermaid:source xmlns:mermaid=\"{SVG_NAMESPACE}\">{}</mermaid:source></metadata>",
+        escape_xml(source)
+    );
+    let mut out = String::with_capacity(svg.len() + metadata.len());
+    out.push_str(&svg[..end]);
+    out.push_str(&metadata);
+    out.push_str(&svg[end..]);
+    out
+}
+
+/// The source embedded in `svg`, whatever prefix the namespace has.
+pub fn svg_source(svg: &str) -> Option<String> {
+    let document = roxmltree::Document::parse(svg).ok()?;
+    let node = document
+        .descendants()
+        .find(|node| node.has_tag_name((SVG_NAMESPACE, "source")))?;
+    Some(
+        node.descendants()
+            .filter(|node| node.is_text())
+            .filter_map(|node| node.text())
+            .collect(),
+    )
+}
+
+/// Removes the metadata [`svg_with_source`] adds.
+fn strip_svg_source(svg: &str) -> String {
+    let open = format!("<metadata><mermaid:source xmlns:mermaid=\"{SVG_NAMESPACE}\">");
+    let close = "</mermaid:source></metadata>";
+    let Some(start) = svg.find(&open) else {
+        return svg.to_string();
+    };
+    match svg[start..].find(close) {
+        Some(len) => format!("{}{}", &svg[..start], &svg[start + len + close.len()..]),
+        None => svg.to_string(),
+    }
+}
+
+/// The offset just past the `>` of the root `<svg …>` tag.
+fn svg_start_tag_end(svg: &str) -> Option<usize> {
+    let start = svg.find("<svg")?;
+    let mut quote = None;
+    for (i, c) in svg[start..].char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) if svg[..start + i].ends_with('/') => return None,
+            ('>', None) => return Some(start + i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Escapes text content; carriage returns too, which XML parsers would
+/// otherwise fold into newlines.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\r', "&#13;")
+}
+
+/// `png` with `source` in an `iTXt` chunk after the header, replacing source
+/// embedded earlier.
+pub fn png_with_source(png: &[u8], source: &str) -> Result<Vec<u8>, String> {
+    let chunks = png_chunks(png).ok_or("not a PNG image")?;
+    let mut data = Vec::with_capacity(PNG_KEYWORD.len() + 5 + source.len());
+    data.extend_from_slice(PNG_KEYWORD.as_bytes());
+    // Separator, uncompressed, no language tag or translated keyword.
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.extend_from_slice(source.as_bytes());
+
+    let mut out = Vec::with_capacity(png.len() + data.len() + 12);
+    out.extend_from_slice(PNG_SIGNATURE);
+    for chunk in chunks {
+        if text_chunk(&chunk).is_some() {
+            continue;
+        }
+        out.extend_from_slice(chunk.raw);
+        if chunk.kind == b"IHDR" {
+            write_chunk(&mut out, b"iTXt", &data);
+        }
+    }
+    Ok(out)
+}
+
+/// The source embedded in `png`.
+pub fn png_source(png: &[u8]) -> Option<String> {
+    png_chunks(png)?.iter().find_map(text_chunk)
+}
+
+struct Chunk<'a> {
+    kind: &'a [u8],
+    data: &'a [u8],
+    /// Length, type, data and CRC.
+    raw: &'a [u8],
+}
+
+fn png_chunks(png: &[u8]) -> Option<Vec<Chunk<'_>>> {
+    let mut rest = png.strip_prefix(PNG_SIGNATURE)?;
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let raw = rest.get(..len.checked_add(12)?)?;
+        chunks.push(Chunk {
+            kind: &raw[4..8],
+            data: &raw[8..8 + len],
+            raw,
+        });
+        rest = &rest[raw.len()..];
+    }
+    Some(chunks)
+}
+
+/// The text of a `tEXt` or uncompressed `iTXt` chunk with our keyword.
+fn text_chunk(chunk: &Chunk) -> Option<String> {
+    let (keyword, rest) = split_nul(chunk.data)?;
+    if keyword != PNG_KEYWORD.as_bytes() {
+        return None;
+    }
+    match chunk.kind {
+        // Latin-1.
+        b"tEXt" => Some(rest.iter().map(|&b| b as char).collect()),
+        b"iTXt" => {
+            let [0, _, rest @ ..] = rest else {
+                return None;
+            };
+            let (_language, rest) = split_nul(rest)?;
+            let (_translated, text) = split_nul(rest)?;
+            String::from_utf8(text.to_vec()).ok()
+        }
+        _ => None,
+    }
+}
+
+fn split_nul(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let nul = bytes.iter().position(|&b| b == 0)?;
+    Some((&bytes[..nul], &bytes[nul + 1..]))
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Whether `path` names an image format that can carry source.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg") || ext.eq_ignore_ascii_case("png"))
+}
+
+/// The source embedded in the image at `path`.
+pub fn read_source(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    let source = if bytes.starts_with(PNG_SIGNATURE) {
+        png_source(&bytes)
+    } else {
+        svg_source(&String::from_utf8_lossy(&bytes))
+    };
+    source.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no Mermaid source embedded in this image",
+        )
+    })
+}
+
+/// Turns a request to open an exported image into an unsaved buffer with
+/// its embedded source; other files are opened as they are.
+pub fn resolve(args: CliArgs) -> io::Result<CliArgs> {
+    match args.file.as_deref().map(Path::new) {
+        Some(path) if is_image(path) => Ok(CliArgs {
+            file: None,
+            source: Some(read_source(path)?),
+            ..args
+        }),
+        _ => Ok(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "flowchart TD\r\n    A[\"<a & b>\"] --> B\n    %% é\n";
+
+    /// A 1×1 PNG with just the chunks a decoder needs, plus `extra`.
+    fn png(extra: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]);
+        for (kind, data) in extra {
+            write_chunk(&mut png, kind, data);
+        }
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn svg_round_trip() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" data-x="a>b"><g/></svg>"#;
+        let with_source = svg_with_source(svg, SOURCE);
+        assert!(with_source
+            .starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" data-x="a>b"><metadata>"#));
+        assert_eq!(svg_source(&with_source).as_deref(), Some(SOURCE));
+
+        let replaced = svg_with_source(&with_source, "pie\n");
+        assert_eq!(replaced.matches("<metadata>").count(), 1);
+        assert_eq!(svg_source(&replaced).as_deref(), Some("pie\n"));
+
+        assert_eq!(svg_source(svg), None);
+        assert_eq!(svg_with_source("<svg/>", SOURCE), "<svg/>");
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let with_source = png_with_source(&png(&[]), SOURCE).unwrap();
+        assert_eq!(png_source(&with_source).as_deref(), Some(SOURCE));
+
+        let replaced = png_with_source(&with_source, "pie\n").unwrap();
+        assert_eq!(png_source(&replaced).as_deref(), Some("pie\n"));
+        let kinds: Vec<_> = png_chunks(&replaced)
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk.kind.to_vec())
+            .collect();
+        assert_eq!(kinds, [b"IHDR", b"iTXt", b"IEND"]);
+        for chunk in png_chunks(&replaced).unwrap() {
+            let crc = crc32fast::hash(&chunk.raw[4..chunk.raw.len() - 4]);
+            assert_eq!(chunk.raw[chunk.raw.len() - 4..], crc.to_be_bytes());
+        }
+
+        assert!(png_with_source(b"GIF89a", SOURCE).is_err());
+    }
+
+    #[test]
+    fn png_text_chunks() {
+        let png = png(&[
+            (b"tEXt", b"mermaid\0graph \xe9\n"),
+            (b"tEXt", b"Software\0x"),
+        ]);
+        assert_eq!(png_source(&png).as_deref(), Some("graph é\n"));
+        assert_eq!(png_source(&self::png(&[])), None);
+        // Cut off in the middle of a chunk.
+        assert_eq!(png_source(&png[..png.len() - 3]), None);
+    }
+
+    #[test]
+    fn opening_images() {
+        let dir = std::env::temp_dir().join(format!("bmermaid-embed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let svg = dir.join("a.SVG");
+        fs::write(&svg, svg_with_source("<svg></svg>", SOURCE)).unwrap();
+        let args = resolve(CliArgs {
+            file: Some(svg.display().to_string()),
+            ..CliArgs::default()
+        })
+        .unwrap();
+        assert_eq!((args.file, args.source.as_deref()), (None, Some(SOURCE)));
+
+        let bare = dir.join("b.png");
+        fs::write(&bare, png(&[])).unwrap();
+        assert!(read_source(&bare).is_err());
+        assert!(!is_image(Path::new("a.mmd")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
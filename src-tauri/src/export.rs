@@ -0,0 +1,322 @@
+//! Exporting the editor's diagram to a file chosen in a save dialog.
+//!
+//! The dialog proposes `<document stem>.<format>` in the directory the
+//! document was last exported to, or next to the document the first time.
+//! Those directories are kept in the app data dir, keyed by document; an
+//! untitled document remembers its directory for as long as its window is
+//! open.
+//!
+//! SVGs are post-processed as the `[export]` section of the document's
+//! `.bmermaid.toml` asks.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::WebviewWindow;
+use tauri_plugin_dialog::DialogExt;
+
+use crate::config::Config;
+use crate::embed;
+use crate::pdf::{self, PdfOptions};
+use crate::postprocess::{self, PostProcessOptions};
+use crate::raster::{self, RasterOptions};
+use crate::save;
+
+/// The stem proposed for a document that was never saved.
+const UNTITLED_STEM: &str = "diagram";
+
+/// The image formats the editor and `bmermaid render` write.
+pub const IMAGE_FORMATS: &[&str] = &["svg", "png", "pdf"];
+
+/// Last export directories.
+pub struct ExportDirs {
+    /// The JSON file the directories of saved documents are stored in.
+    file: PathBuf,
+    documents: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// Untitled documents by window label.
+    windows: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl ExportDirs {
+    pub fn load(file: PathBuf) -> Self {
+        let documents = fs::read_to_string(&file)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        ExportDirs {
+            file,
+            documents: Mutex::new(documents),
+            windows: Mutex::default(),
+        }
+    }
+
+    /// Where the document in window `label` was last exported to.
+    pub fn get(&self, label: &str, document: Option<&Path>) -> Option<PathBuf> {
+        let remembered = match document {
+            Some(document) => self
+                .documents
+                .lock()
+                .unwrap()
+                .get(&canonical(document))
+                .cloned(),
+            None => None,
+        };
+        remembered
+            .or_else(|| self.windows.lock().unwrap().get(label).cloned())
+            .filter(|dir| dir.is_dir())
+    }
+
+    pub fn set(&self, label: &str, document: Option<&Path>, dir: PathBuf) {
+        match document {
+            Some(document) => {
+                let mut documents = self.documents.lock().unwrap();
+                documents.insert(canonical(document), dir);
+                self.store(&documents);
+            }
+            None => {
+                self.windows.lock().unwrap().insert(label.to_string(), dir);
+            }
+        }
+    }
+
+    /// Drops what an untitled document in window `label` remembered.
+    pub fn forget(&self, label: &str) {
+        self.windows.lock().unwrap().remove(label);
+    }
+
+    fn store(&self, documents: &HashMap<PathBuf, PathBuf>) {
+        let result = match self.file.parent() {
+            Some(dir) => fs::create_dir_all(dir).map_err(|e| save::SaveError::new(dir, e)),
+            None => Ok(()),
+        }
+        .and_then(|()| {
+            save::save(
+                &self.file,
+                &serde_json::to_string(documents).unwrap(),
+                false,
+            )
+        });
+        if let Err(e) = result {
+            eprintln!("export directories: {e}");
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The diagram in `format`, with `source` embedded in SVG and PNG when
+/// given. SVGs are post-processed first.
+pub fn image(
+    format: &str,
+    svg: &str,
+    source: Option<&str>,
+    raster: &RasterOptions,
+    pdf: &PdfOptions,
+    post: &PostProcessOptions,
+) -> Result<Vec<u8>, String> {
+    match format {
+        "svg" => {
+            let svg = postprocess::postprocess(svg, post)?;
+            Ok(match source {
+                Some(source) => embed::svg_with_source(&svg, source),
+                None => svg,
+            }
+            .into_bytes())
+        }
+        "png" => {
+            let png = raster::png(svg, raster)?;
+            match source {
+                Some(source) => embed::png_with_source(&png, source),
+                None => Ok(png),
+            }
+        }
+        "pdf" => pdf::pdf(&[svg], pdf),
+        _ => Err(format!("unknown image format `{format}`")),
+    }
+}
+
+/// Asks where to export the document in `window` as `format`. `None` when
+/// the dialog was cancelled.
+pub fn choose_path(
+    window: &WebviewWindow,
+    dirs: &ExportDirs,
+    document: Option<&Path>,
+    format: &str,
+) -> Option<PathBuf> {
+    let stem = document
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| UNTITLED_STEM.to_string());
+    let dir = dirs
+        .get(window.label(), document)
+        .or_else(|| document.and_then(Path::parent).map(Path::to_path_buf))
+        .filter(|dir| !dir.as_os_str().is_empty());
+
+    let mut dialog = window
+        .dialog()
+        .file()
+        .set_title(format!("Export {}", format.to_uppercase()))
+        .set_file_name(format!("{stem}.{format}"))
+        .add_filter(format.to_uppercase(), &[format]);
+    if let Some(dir) = dir {
+        dialog = dialog.set_directory(dir);
+    }
+    #[cfg(desktop)]
+    {
+        dialog = dialog.set_parent(window);
+    }
+    dialog.blocking_save_file()?.into_path().ok()
+}
+
+/// Exports the document in `window`: asks for the file, writes the diagram
+/// to it and remembers its directory. Returns the path written.
+pub fn export(
+    window: &WebviewWindow,
+    dirs: &ExportDirs,
+    document: Option<&Path>,
+    format: &str,
+    svg: &str,
+    source: &str,
+) -> Result<Option<PathBuf>, String> {
+    if !IMAGE_FORMATS.contains(&format) {
+        return Err(format!("unknown image format `{format}`"));
+    }
+    let post = match document {
+        Some(document) => {
+            Config::for_file(document)
+                .map_err(|e| e.to_string())?
+                .export
+        }
+        None => PostProcessOptions::default(),
+    };
+    let Some(path) = choose_path(window, dirs, document, format) else {
+        return Ok(None);
+    };
+    let bytes = image(
+        format,
+        svg,
+        Some(source),
+        &RasterOptions::default(),
+        &PdfOptions::default(),
+        &post,
+    )?;
+    save::save_bytes(&path, &bytes, false).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        dirs.set(window.label(), document, dir.to_path_buf());
+    }
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10"><g id="a"><rect width="20" height="10"/></g></svg>"#;
+    const SOURCE: &str = "flowchart TD\n    A --> B\n";
+
+    /// A fresh directory with `names` as subdirectories.
+    fn dirs(test: &str, names: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bmermaid-export-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for name in names {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn image(format: &str, source: Option<&str>, post: &PostProcessOptions) -> Vec<u8> {
+        super::image(
+            format,
+            SVG,
+            source,
+            &RasterOptions::default(),
+            &PdfOptions::default(),
+            post,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn remembers_directories_per_document() {
+        let dir = dirs("documents", &["out", "other"]);
+        let file = dir.join("export-dirs.json");
+        let document = dir.join("a.mmd");
+        fs::write(&document, SOURCE).unwrap();
+
+        let dirs = ExportDirs::load(file.clone());
+        assert_eq!(dirs.get("main", Some(&document)), None);
+        dirs.set("main", Some(&document), dir.join("out"));
+        // Same document, another window and spelling.
+        let spelled = dir.join(".").join("a.mmd");
+        assert_eq!(
+            dirs.get("document-1", Some(&spelled)),
+            Some(dir.join("out"))
+        );
+        assert_eq!(dirs.get("main", Some(&dir.join("b.mmd"))), None);
+
+        let reloaded = ExportDirs::load(file);
+        assert_eq!(reloaded.get("main", Some(&document)), Some(dir.join("out")));
+
+        // A directory that is gone is not proposed.
+        fs::remove_dir(dir.join("out")).unwrap();
+        assert_eq!(reloaded.get("main", Some(&document)), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn untitled_documents_remember_per_window() {
+        let dir = dirs("windows", &["out"]);
+        let file = dir.join("export-dirs.json");
+        let dirs = ExportDirs::load(file.clone());
+        dirs.set("main", None, dir.join("out"));
+        assert_eq!(dirs.get("main", None), Some(dir.join("out")));
+        assert_eq!(dirs.get("document-1", None), None);
+        // Not stored: untitled documents do not outlive their window.
+        assert!(!file.exists());
+
+        dirs.forget("document-1");
+        assert_eq!(dirs.get("main", None), Some(dir.join("out")));
+        dirs.forget("main");
+        assert_eq!(dirs.get("main", None), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn images_by_format() {
+        let plain = PostProcessOptions::default();
+        let svg = String::from_utf8(image("svg", Some(SOURCE), &plain)).unwrap();
+        assert_eq!(embed::svg_source(&svg).as_deref(), Some(SOURCE));
+        let svg = String::from_utf8(image("svg", None, &plain)).unwrap();
+        assert_eq!(embed::svg_source(&svg), None);
+
+        let prefixed = PostProcessOptions {
+            prefix_ids: true,
+            id_prefix: Some("p-".to_string()),
+            ..PostProcessOptions::default()
+        };
+        let svg = String::from_utf8(image("svg", None, &prefixed)).unwrap();
+        assert!(svg.contains(r#"id="p-a""#));
+
+        let png = image("png", Some(SOURCE), &prefixed);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(embed::png_source(&png).as_deref(), Some(SOURCE));
+        assert_eq!(embed::png_source(&image("png", None, &plain)), None);
+
+        assert!(image("pdf", Some(SOURCE), &plain).starts_with(b"%PDF-"));
+
+        let unknown = super::image(
+            "gif",
+            SVG,
+            None,
+            &RasterOptions::default(),
+            &PdfOptions::default(),
+            &plain,
+        );
+        assert_eq!(unknown, Err("unknown image format `gif`".to_string()));
+    }
+}
@@ -0,0 +1,844 @@
+//! Canonical pretty-printer built on the parser's AST.
+//!
+//! Output uses four-space indentation, one statement per line and single
+//! spaces around arrows. Comments and `%%{init}%%` directives are printed
+//! where they were, and runs of blank lines collapse to one.
+
+use crate::parse::{
+    self, class, er, flowchart, sequence, state, Comment, Diagram, Document, Label, ParseError,
+};
+
+const INDENT: &str = "    ";
+
+/// Formats `source`, or returns its syntax errors: a document that does not
+/// parse cleanly is never rewritten. Diagram types without a native parser
+/// are returned unchanged.
+pub fn format(source: &str) -> Result<String, Vec<ParseError>> {
+    let parsed = parse::parse(source);
+    if !parsed.errors.is_empty() {
+        return Err(parsed.errors);
+    }
+    let Some(document) = parsed.document else {
+        return Ok(source.to_string());
+    };
+    if matches!(document.diagram, Diagram::Unsupported { .. }) {
+        return Ok(source.to_string());
+    }
+
+    let mut printer = Printer {
+        source,
+        out: String::new(),
+        depth: 0,
+        last: None,
+    };
+    printer.document(&document);
+    if source.contains("\r\n") {
+        return Ok(printer.out.replace('\n', "\r\n"));
+    }
+    Ok(printer.out)
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    out: String,
+    depth: usize,
+    /// Source offset of the previously printed line.
+    last: Option<usize>,
+}
+
+impl Printer<'_> {
+    /// Writes `text` as one line at the current depth. `start` is where the
+    /// line came from in the source; a blank line there is kept.
+    fn line(&mut self, start: usize, text: impl AsRef<str>) {
+        if let Some(last) = self.last {
+            if start > last && self.source[last..start].matches('\n').count() > 1 {
+                self.out.push('\n');
+            }
+        }
+        self.raw_line(text);
+        self.last = Some(start);
+    }
+
+    /// Writes a line without blank-line tracking, for the body of a
+    /// multi-line note.
+    fn raw_line(&mut self, text: impl AsRef<str>) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text.as_ref());
+        self.out.push('\n');
+    }
+
+    /// Prints `comment` on a line of its own, or after the statement it
+    /// trails in the source.
+    fn comment(&mut self, comment: &Comment) {
+        let start = comment.span.start;
+        let trailing = self
+            .last
+            .is_some_and(|last| last < start && !self.source[last..start].contains('\n'));
+        if trailing && self.out.ends_with('\n') {
+            self.out.pop();
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        } else {
+            self.line(start, &comment.text);
+        }
+    }
+
+    /// Prints the lines written by `f` one level deeper.
+    fn indented(&mut self, f: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        f(self);
+        self.depth -= 1;
+    }
+
+    fn document(&mut self, document: &Document) {
+        if let Some(front_matter) = &document.front_matter {
+            self.out.push_str("---\n");
+            self.out.push_str(&front_matter.value.replace("\r\n", "\n"));
+            self.out.push_str("---\n");
+            self.last = Some(front_matter.span.end.saturating_sub(3));
+        }
+        for comment in &document.preamble {
+            self.comment(comment);
+        }
+        match &document.diagram {
+            Diagram::Flowchart(diagram) => self.flowchart(diagram),
+            Diagram::Sequence(diagram) => self.sequence(diagram),
+            Diagram::Class(diagram) => self.class_diagram(diagram),
+            Diagram::State(diagram) => self.state_diagram(diagram),
+            Diagram::Er(diagram) => self.er_diagram(diagram),
+            Diagram::Unsupported { .. } => {}
+        }
+    }
+
+    fn flowchart(&mut self, diagram: &flowchart::Flowchart) {
+        let mut header = diagram.keyword.value.clone();
+        if let Some(direction) = &diagram.direction {
+            header.push(' ');
+            header.push_str(direction.value.as_str());
+        }
+        self.line(diagram.keyword.span.start, header);
+        self.indented(|p| p.flowchart_statements(&diagram.statements));
+    }
+
+    fn flowchart_statements(&mut self, statements: &[flowchart::Statement]) {
+        use flowchart::{LinkTargets, Statement};
+
+        for statement in statements {
+            let start = statement.span().start;
+            match statement {
+                Statement::Chain(chain) => {
+                    let mut text = node_group(&chain.groups[0]);
+                    for (link, group) in chain.links.iter().zip(&chain.groups[1..]) {
+                        text.push(' ');
+                        text.push_str(&flowchart_link(link));
+                        text.push(' ');
+                        text.push_str(&node_group(group));
+                    }
+                    self.line(start, text);
+                }
+                Statement::Subgraph(subgraph) => {
+                    let header = match (&subgraph.id, &subgraph.title) {
+                        (Some(id), Some(title)) => {
+                            format!("subgraph {}[{}]", id.value, label(title))
+                        }
+                        (Some(id), None) => format!("subgraph {}", id.value),
+                        (None, Some(title)) => format!("subgraph {}", label(title)),
+                        (None, None) => "subgraph".to_string(),
+                    };
+                    self.line(start, header);
+                    self.indented(|p| p.flowchart_statements(&subgraph.statements));
+                    self.line(subgraph.end.map_or(start, |end| end.start), "end");
+                }
+                Statement::Direction(direction) => {
+                    self.line(start, format!("direction {}", direction.value.as_str()));
+                }
+                Statement::ClassDef(class_def) => {
+                    let names = join(class_def.names.iter().map(|n| n.value.as_str()), ",");
+                    self.line(
+                        start,
+                        format!("classDef {names} {}", style_props(&class_def.styles)),
+                    );
+                }
+                Statement::Class(class) => {
+                    let nodes = join(class.nodes.iter().map(|n| n.value.as_str()), ",");
+                    self.line(start, format!("class {nodes} {}", class.class.value));
+                }
+                Statement::Style(style) => {
+                    self.line(
+                        start,
+                        format!("style {} {}", style.node.value, style_props(&style.styles)),
+                    );
+                }
+                Statement::LinkStyle(link_style) => {
+                    let targets = match &link_style.targets {
+                        LinkTargets::Default => "default".to_string(),
+                        LinkTargets::Indices(indices) => {
+                            let indices: Vec<String> =
+                                indices.iter().map(|i| i.value.to_string()).collect();
+                            indices.join(",")
+                        }
+                    };
+                    self.line(
+                        start,
+                        format!("linkStyle {targets} {}", style_props(&link_style.styles)),
+                    );
+                }
+                Statement::Click(click) => {
+                    self.line(
+                        start,
+                        format!("click {} {}", click.node.value, click.action.text),
+                    );
+                }
+                Statement::Comment(comment) => self.comment(comment),
+            }
+        }
+    }
+
+    fn sequence(&mut self, diagram: &sequence::SequenceDiagram) {
+        self.line(diagram.keyword.span.start, &diagram.keyword.value);
+        self.indented(|p| p.sequence_statements(&diagram.statements));
+    }
+
+    fn sequence_statements(&mut self, statements: &[sequence::Statement]) {
+        use sequence::{ActivationChange, NotePlacement, ParticipantKind, Statement};
+
+        for statement in statements {
+            let start = statement.span().start;
+            match statement {
+                Statement::Participant(participant) => {
+                    let kind = match participant.kind {
+                        ParticipantKind::Participant => "participant",
+                        ParticipantKind::Actor => "actor",
+                    };
+                    let create = if participant.created { "create " } else { "" };
+                    let mut text = format!("{create}{kind} {}", participant.id.value);
+                    if let Some(alias) = &participant.alias {
+                        text.push_str(" as ");
+                        text.push_str(&alias.text);
+                    }
+                    self.line(start, text);
+                }
+                Statement::Message(message) => {
+                    let activation = match message.activation.as_ref().map(|a| a.value) {
+                        Some(ActivationChange::Activate) => "+",
+                        Some(ActivationChange::Deactivate) => "-",
+                        None => "",
+                    };
+                    let text = format!(
+                        "{}{}{activation}{}:",
+                        message.from.value,
+                        message.arrow.as_str(),
+                        message.to.value
+                    );
+                    self.line(start, with_text(text, &message.text.text));
+                }
+                Statement::Activation(activation) => {
+                    let keyword = match activation.change {
+                        ActivationChange::Activate => "activate",
+                        ActivationChange::Deactivate => "deactivate",
+                    };
+                    self.line(start, format!("{keyword} {}", activation.participant.value));
+                }
+                Statement::Note(note) => {
+                    let placement = match note.placement {
+                        NotePlacement::LeftOf => "left of",
+                        NotePlacement::RightOf => "right of",
+                        NotePlacement::Over => "over",
+                    };
+                    let participants =
+                        join(note.participants.iter().map(|p| p.value.as_str()), ",");
+                    let text = format!("Note {placement} {participants}:");
+                    self.line(start, with_text(text, &note.text.text));
+                }
+                Statement::Block(block) => {
+                    for section in &block.sections {
+                        let mut header = section.keyword.value.clone();
+                        if let Some(label) = &section.label {
+                            header.push(' ');
+                            header.push_str(&label.text);
+                        }
+                        self.line(section.span.start, header);
+                        self.indented(|p| p.sequence_statements(&section.statements));
+                    }
+                    self.line(block.end.map_or(start, |end| end.start), "end");
+                }
+                Statement::Autonumber(autonumber) => {
+                    let mut text = "autonumber".to_string();
+                    if autonumber.off {
+                        text.push_str(" off");
+                    }
+                    for number in autonumber.start.iter().chain(&autonumber.step) {
+                        text.push_str(&format!(" {}", number.value));
+                    }
+                    self.line(start, text);
+                }
+                Statement::Title(title) => {
+                    self.line(start, with_text("title".to_string(), &title.text));
+                }
+                Statement::Destroy(destroy) => {
+                    self.line(start, format!("destroy {}", destroy.participant.value));
+                }
+                Statement::Link(link) => {
+                    let text = format!("{} {}:", link.keyword.value, link.participant.value);
+                    self.line(start, with_text(text, &link.text.text));
+                }
+                Statement::Accessibility(accessibility) => {
+                    let keyword = &accessibility.keyword.value;
+                    let text = &accessibility.text.text;
+                    if text.contains('\n') {
+                        self.line(start, format!("{keyword} {{"));
+                        self.indented(|p| {
+                            for line in text.lines() {
+                                p.raw_line(line);
+                            }
+                        });
+                        self.raw_line("}");
+                        self.last = Some(accessibility.span.end);
+                    } else {
+                        self.line(start, with_text(format!("{keyword}:"), text));
+                    }
+                }
+                Statement::Comment(comment) => self.comment(comment),
+            }
+        }
+    }
+
+    fn class_diagram(&mut self, diagram: &class::ClassDiagram) {
+        self.line(diagram.keyword.span.start, &diagram.keyword.value);
+        self.indented(|p| p.class_statements(&diagram.statements));
+    }
+
+    fn class_statements(&mut self, statements: &[class::Statement]) {
+        use class::Statement;
+
+        for statement in statements {
+            let start = statement.span().start;
+            match statement {
+                Statement::Class(class) => self.class(class),
+                Statement::Member(member) => {
+                    self.line(
+                        start,
+                        format!("{} : {}", member.class.value, class_member(&member.member)),
+                    );
+                }
+                Statement::Relationship(relationship) => {
+                    let mut text = relationship.from.value.clone();
+                    if let Some(cardinality) = &relationship.from_cardinality {
+                        text.push_str(&format!(" \"{}\"", cardinality.text));
+                    }
+                    text.push(' ');
+                    text.push_str(&relationship.relation.token());
+                    if let Some(cardinality) = &relationship.to_cardinality {
+                        text.push_str(&format!(" \"{}\"", cardinality.text));
+                    }
+                    text.push(' ');
+                    text.push_str(&relationship.to.value);
+                    if let Some(label) = &relationship.label {
+                        text = with_text(text + " :", &label.text);
+                    }
+                    self.line(start, text);
+                }
+                Statement::Annotation(annotation) => {
+                    self.line(
+                        start,
+                        format!("<<{}>> {}", annotation.name.value, annotation.class.value),
+                    );
+                }
+                Statement::Namespace(namespace) => {
+                    self.line(start, format!("namespace {} {{", namespace.name.value));
+                    self.indented(|p| p.class_statements(&namespace.statements));
+                    self.line(namespace.close.map_or(start, |close| close.start), "}");
+                }
+                Statement::Note(note) => {
+                    let text = match &note.class {
+                        Some(class) => format!("note for {} \"{}\"", class.value, note.text.text),
+                        None => format!("note \"{}\"", note.text.text),
+                    };
+                    self.line(start, text);
+                }
+                Statement::Direction(direction) => {
+                    self.line(start, format!("direction {}", direction.value.as_str()));
+                }
+                Statement::Verbatim(verbatim) => {
+                    let text = format!("{} {}", verbatim.keyword.value, verbatim.text.text);
+                    self.line(start, text.trim_end());
+                }
+                Statement::Comment(comment) => self.comment(comment),
+            }
+        }
+    }
+
+    fn class(&mut self, class: &class::Class) {
+        let mut header = format!("class {}", class.name.value);
+        if let Some(generic) = &class.generic {
+            header.push_str(&format!("~{}~", generic.value));
+        }
+        if let Some(title) = &class.label {
+            header.push_str(&format!("[{}]", label(title)));
+        }
+        for style_class in &class.classes {
+            header.push_str(":::");
+            header.push_str(&style_class.value);
+        }
+
+        // Body lines in source order, so comments stay next to the members
+        // they describe.
+        let mut body: Vec<(usize, String)> = class
+            .annotations
+            .iter()
+            .map(|a| (a.span.start, format!("<<{}>>", a.value)))
+            .chain(
+                class
+                    .members
+                    .iter()
+                    .map(|m| (m.span.start, class_member(m))),
+            )
+            .chain(
+                class
+                    .comments
+                    .iter()
+                    .map(|c| (c.span.start, c.text.clone())),
+            )
+            .collect();
+        body.sort_by_key(|&(start, _)| start);
+
+        let start = class.span.start;
+        if body.is_empty() {
+            self.line(start, header);
+            return;
+        }
+        self.line(start, header + " {");
+        self.indented(|p| {
+            for (start, text) in body {
+                p.line(start, text);
+            }
+        });
+        let close = class.body.map_or(start, |body| body.end.saturating_sub(1));
+        self.line(close, "}");
+    }
+
+    fn state_diagram(&mut self, diagram: &state::StateDiagram) {
+        self.line(diagram.keyword.span.start, &diagram.keyword.value);
+        self.indented(|p| p.state_statements(&diagram.statements));
+    }
+
+    fn state_statements(&mut self, statements: &[state::Statement]) {
+        use state::{NoteSide, Statement};
+
+        for statement in statements {
+            let start = statement.span().start;
+            match statement {
+                Statement::State(state) => self.state(state),
+                Statement::Transition(transition) => {
+                    let mut text = format!(
+                        "{} --> {}",
+                        state_ref(&transition.from),
+                        state_ref(&transition.to)
+                    );
+                    if let Some(label) = &transition.label {
+                        text = with_text(text + " :", &label.text);
+                    }
+                    self.line(start, text);
+                }
+                Statement::Description(description) => {
+                    let text = format!("{} :", description.state.value);
+                    self.line(start, with_text(text, &description.text.text));
+                }
+                Statement::Note(note) => {
+                    let side = match note.side {
+                        NoteSide::LeftOf => "left of",
+                        NoteSide::RightOf => "right of",
+                    };
+                    let header = format!("note {side} {}", note.state.value);
+                    let text = &note.text.text;
+                    if text.is_empty() || text.contains('\n') {
+                        self.line(start, header);
+                        self.indented(|p| {
+                            for line in text.lines() {
+                                p.raw_line(line);
+                            }
+                        });
+                        self.raw_line("end note");
+                        self.last = Some(note.span.end);
+                    } else {
+                        self.line(start, with_text(header + " :", text));
+                    }
+                }
+                Statement::Concurrency(_) => self.line(start, "--"),
+                Statement::Direction(direction) => {
+                    self.line(start, format!("direction {}", direction.value.as_str()));
+                }
+                Statement::Verbatim(verbatim) => {
+                    let text = format!("{} {}", verbatim.keyword.value, verbatim.text.text);
+                    self.line(start, text.trim_end());
+                }
+                Statement::Comment(comment) => self.comment(comment),
+            }
+        }
+    }
+
+    fn state(&mut self, state: &state::State) {
+        use state::StateKind;
+
+        let start = state.span.start;
+        let bare = state.label.is_none() && state.kind.is_none() && state.body.is_none();
+        let mut text = if bare || !state.classes.is_empty() {
+            // `Idle` or `Idle:::highlight : text`
+            let mut text = state.id.value.clone();
+            for class in &state.classes {
+                text.push_str(":::");
+                text.push_str(&class.value);
+            }
+            text
+        } else {
+            let mut text = "state ".to_string();
+            if let Some(title) = &state.label {
+                text.push_str(&format!("\"{}\" as ", title.text));
+            }
+            text.push_str(&state.id.value);
+            if let Some(kind) = &state.kind {
+                text.push_str(match kind.value {
+                    StateKind::Fork => " <<fork>>",
+                    StateKind::Join => " <<join>>",
+                    StateKind::Choice => " <<choice>>",
+                });
+            }
+            text
+        };
+        if let Some(description) = &state.description {
+            text = with_text(text + " :", &description.text);
+        }
+
+        let Some(body) = state.body else {
+            self.line(start, text);
+            return;
+        };
+        self.line(start, text + " {");
+        self.indented(|p| p.state_statements(&state.statements));
+        self.line(body.end.saturating_sub(1), "}");
+    }
+
+    fn er_diagram(&mut self, diagram: &er::ErDiagram) {
+        self.line(diagram.keyword.span.start, &diagram.keyword.value);
+        self.indented(|p| {
+            for statement in &diagram.statements {
+                p.er_statement(statement);
+            }
+        });
+    }
+
+    fn er_statement(&mut self, statement: &er::Statement) {
+        use er::{Key, Statement};
+
+        let start = statement.span().start;
+        match statement {
+            Statement::Entity(entity) => {
+                let mut header = entity_name(&entity.name.value);
+                if let Some(alias) = &entity.alias {
+                    header.push_str(&format!("[{}]", label(alias)));
+                }
+
+                let mut body: Vec<(usize, String)> = entity
+                    .attributes
+                    .iter()
+                    .map(|attribute| {
+                        let mut text = format!("{} {}", attribute.ty.value, attribute.name.value);
+                        let keys = join(
+                            attribute.keys.iter().map(|key| match key.value {
+                                Key::Primary => "PK",
+                                Key::Foreign => "FK",
+                                Key::Unique => "UK",
+                            }),
+                            ", ",
+                        );
+                        if !keys.is_empty() {
+                            text.push(' ');
+                            text.push_str(&keys);
+                        }
+                        if let Some(comment) = &attribute.comment {
+                            text.push_str(&format!(" \"{}\"", comment.text));
+                        }
+                        (attribute.span.start, text)
+                    })
+                    .chain(
+                        entity
+                            .comments
+                            .iter()
+                            .map(|c| (c.span.start, c.text.clone())),
+                    )
+                    .collect();
+                body.sort_by_key(|&(start, _)| start);
+
+                if body.is_empty() {
+                    self.line(start, header);
+                    return;
+                }
+                self.line(start, header + " {");
+                self.indented(|p| {
+                    for (start, text) in body {
+                        p.line(start, text);
+                    }
+                });
+                let close = entity.body.map_or(start, |body| body.end.saturating_sub(1));
+                self.line(close, "}");
+            }
+            Statement::Relationship(relationship) => {
+                let text = format!(
+                    "{} {} {} : {}",
+                    entity_name(&relationship.from.value),
+                    relationship.token(),
+                    entity_name(&relationship.to.value),
+                    label(&relationship.label)
+                );
+                self.line(start, text);
+            }
+            Statement::Direction(direction) => {
+                self.line(start, format!("direction {}", direction.value.as_str()));
+            }
+            Statement::Comment(comment) => self.comment(comment),
+        }
+    }
+}
+
+fn label(label: &Label) -> String {
+    if label.quoted {
+        format!("\"{}\"", label.text)
+    } else {
+        label.text.clone()
+    }
+}
+
+/// Appends ` text` to `prefix`, leaving no trailing space when `text` is
+/// empty.
+fn with_text(mut prefix: String, text: &str) -> String {
+    if !text.is_empty() {
+        prefix.push(' ');
+        prefix.push_str(text);
+    }
+    prefix
+}
+
+fn join<'s>(items: impl Iterator<Item = &'s str>, separator: &str) -> String {
+    items.collect::<Vec<_>>().join(separator)
+}
+
+fn node_group(nodes: &[flowchart::Node]) -> String {
+    let nodes: Vec<String> = nodes.iter().map(flowchart_node).collect();
+    nodes.join(" & ")
+}
+
+fn flowchart_node(node: &flowchart::Node) -> String {
+    let mut text = node.id.value.clone();
+    if let (Some(shape), Some(title)) = (node.shape, &node.label) {
+        let (open, close) = shape.delimiters();
+        text.push_str(open);
+        text.push_str(&label(title));
+        text.push_str(close);
+    }
+    for class in &node.classes {
+        text.push_str(":::");
+        text.push_str(&class.value);
+    }
+    text
+}
+
+/// Writes a link in its shortest form, with any text as `|label|`.
+fn flowchart_link(link: &flowchart::Link) -> String {
+    use flowchart::{ArrowHead, Stroke};
+
+    let head = |head: ArrowHead, at_start: bool| match head {
+        ArrowHead::Arrow if at_start => '<',
+        ArrowHead::Arrow => '>',
+        ArrowHead::Circle => 'o',
+        ArrowHead::Cross => 'x',
+    };
+
+    let mut text = String::new();
+    if let Some(start) = link.start {
+        text.push(head(start, true));
+    }
+    let line = |c: char, n: usize| c.to_string().repeat(n);
+    match link.stroke {
+        Stroke::Normal | Stroke::Thick => {
+            let c = if link.stroke == Stroke::Normal {
+                '-'
+            } else {
+                '='
+            };
+            let n = if link.end.is_some() {
+                link.length + 1
+            } else {
+                link.length + 2
+            };
+            text.push_str(&line(c, n));
+        }
+        Stroke::Dotted => {
+            text.push('-');
+            text.push_str(&line('.', link.length));
+            text.push('-');
+        }
+        Stroke::Invisible => text.push_str(&line('~', link.length + 2)),
+    }
+    if let Some(end) = link.end {
+        text.push(head(end, false));
+    }
+
+    if let Some(title) = &link.label {
+        // A raw `|` would end the label early, so such text gets quoted.
+        let title = if !title.quoted && title.text.contains('|') {
+            format!("\"{}\"", title.text)
+        } else {
+            label(title)
+        };
+        text.push_str(&format!("|{title}|"));
+    }
+    text
+}
+
+fn style_props(props: &[flowchart::StyleProp]) -> String {
+    let props: Vec<String> = props
+        .iter()
+        .map(|prop| format!("{}:{}", prop.key, prop.value))
+        .collect();
+    props.join(",")
+}
+
+fn class_member(member: &class::Member) -> String {
+    use class::{Classifier, MemberKind, Visibility};
+
+    let mut text = match member.visibility {
+        Some(Visibility::Public) => "+",
+        Some(Visibility::Private) => "-",
+        Some(Visibility::Protected) => "#",
+        Some(Visibility::Package) => "~",
+        None => "",
+    }
+    .to_string();
+    let classifier = match member.classifier {
+        Some(Classifier::Abstract) => "*",
+        Some(Classifier::Static) => "$",
+        None => "",
+    };
+    match &member.kind {
+        MemberKind::Attribute { text: attribute } => {
+            text.push_str(attribute);
+            text.push_str(classifier);
+        }
+        MemberKind::Method {
+            name,
+            params,
+            return_type,
+        } => {
+            text.push_str(&format!("{name}({params}){classifier}"));
+            if let Some(return_type) = return_type {
+                text.push(' ');
+                text.push_str(return_type);
+            }
+        }
+    }
+    text
+}
+
+fn state_ref(state: &state::StateRef) -> &str {
+    match state {
+        state::StateRef::Terminal(_) => "[*]",
+        state::StateRef::State(id) => &id.value,
+    }
+}
+
+/// Quotes entity names that are not plain identifiers.
+fn entity_name(name: &str) -> String {
+    if name
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-'))
+    {
+        name.to_string()
+    } else {
+        format!("\"{name}\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formats `source`, checking that the result is already formatted.
+    fn formatted(source: &str) -> String {
+        let out = format(source).unwrap();
+        assert_eq!(format(&out).unwrap(), out, "not idempotent:\n{out}");
+        out
+    }
+
+    #[test]
+    fn flowcharts() {
+        assert_eq!(
+            formatted(
+                "graph LR\n%% c\nA[Start]-->B{ok?} ;B -- yes --> C\n\n\n  subgraph S [Sub]\nC-.->D\nend\nclassDef x fill:#f00\n"
+            ),
+            "graph LR\n    %% c\n    A[Start] --> B{ok?}\n    B -->|yes| C\n\n    subgraph S[Sub]\n        C -.-> D\n    end\n    classDef x fill:#f00\n"
+        );
+    }
+
+    #[test]
+    fn trailing_comments() {
+        assert_eq!(
+            formatted("flowchart TD\nA-->B   %% note\n%% own line\nC ; D %% d\nsubgraph S %% s\nE\nend\n"),
+            "flowchart TD\n    A --> B %% note\n    %% own line\n    C\n    D %% d\n    subgraph S %% s\n        E\n    end\n"
+        );
+    }
+
+    #[test]
+    fn sequence_diagrams() {
+        assert_eq!(
+            formatted(
+                "sequenceDiagram\nparticipant A as Alice\n  A->>+B: hi\nloop every day\nB-->>-A: ok\nend\nNote over A,B: text\ncreate participant C\nA->>C: x\ndestroy C\naccTitle: T\naccDescr {\nline one\n  line two\n}\nlink  A :Wiki @ https://wiki\nlinks A: {\"a\": \"b\"}\n"
+            ),
+            "sequenceDiagram\n    participant A as Alice\n    A->>+B: hi\n    loop every day\n        B-->>-A: ok\n    end\n    Note over A,B: text\n    create participant C\n    A->>C: x\n    destroy C\n    accTitle: T\n    accDescr {\n        line one\n        line two\n    }\n    link A: Wiki @ https://wiki\n    links A: {\"a\": \"b\"}\n"
+        );
+    }
+
+    #[test]
+    fn class_diagrams() {
+        assert_eq!(
+            formatted(
+                "classDiagram\nclass Animal{\n+String name\n+eat(food)* bool\n}\nAnimal<|--Dog\nnamespace N{\nclass X\n}\nnote for Dog \"hi\"\n"
+            ),
+            "classDiagram\n    class Animal {\n        +String name\n        +eat(food)* bool\n    }\n    Animal <|-- Dog\n    namespace N {\n        class X\n    }\n    note for Dog \"hi\"\n"
+        );
+    }
+
+    #[test]
+    fn state_diagrams() {
+        assert_eq!(
+            formatted("stateDiagram-v2\n[*]-->Idle\nstate Active{\n[*]-->R\n--\n[*]-->L\n}\n"),
+            "stateDiagram-v2\n    [*] --> Idle\n    state Active {\n        [*] --> R\n        --\n        [*] --> L\n    }\n"
+        );
+        formatted("stateDiagram-v2\n  note left of Idle\n    first\n    second\n  end note\n");
+    }
+
+    #[test]
+    fn er_diagrams() {
+        assert_eq!(
+            formatted("erDiagram\nCUSTOMER||--o{ORDER:places\nORDER{\nint id PK\nstring note \"x\"\n}\n"),
+            "erDiagram\n    CUSTOMER ||--o{ ORDER : places\n    ORDER {\n        int id PK\n        string note \"x\"\n    }\n"
+        );
+    }
+
+    #[test]
+    fn line_endings_are_kept() {
+        assert_eq!(
+            formatted("flowchart TD\r\nA-->B\r\n"),
+            "flowchart TD\r\n    A --> B\r\n"
+        );
+    }
+
+    #[test]
+    fn broken_and_unsupported_documents() {
+        assert!(format("flowchart TD\n  subgraph S\n").is_err());
+        let pie = "pie\n\"a\" :   1\n";
+        assert_eq!(format(pie).unwrap(), pie);
+    }
+}
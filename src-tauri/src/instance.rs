@@ -0,0 +1,255 @@
+//! Single-instance mode.
+//!
+//! The first editor process listens on a Unix socket in the app's local data
+//! dir. Later invocations connect to it, send the files they were asked to
+//! open as one JSON line and exit, and the running instance opens each file
+//! in a window of its own. Elsewhere every invocation starts its own
+//! instance.
+
+use serde::{Deserialize, Serialize};
+#[cfg(not(unix))]
+use tauri::AppHandle;
+
+use crate::CliArgs;
+
+/// What a second invocation hands over to the running instance.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OpenRequest {
+    /// The documents to open, with absolute paths so they resolve the same
+    /// in the receiving process.
+    pub documents: Vec<CliArgs>,
+}
+
+impl OpenRequest {
+    /// Resolves file paths against this process's working directory.
+    pub fn new(mut documents: Vec<CliArgs>) -> Self {
+        for document in &mut documents {
+            if let Some(file) = &mut document.file {
+                if let Ok(path) = std::path::absolute(&*file) {
+                    *file = path.display().to_string();
+                }
+            }
+        }
+        OpenRequest { documents }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::claim;
+
+/// Where single-instance mode is unsupported, this process is always the
+/// primary instance.
+#[cfg(not(unix))]
+pub fn claim(_app: &AppHandle, _request: &OpenRequest) -> bool {
+    true
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs;
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::thread;
+
+    use tauri::{AppHandle, Manager};
+
+    use super::OpenRequest;
+
+    const SOCKET_FILE: &str = "instance.sock";
+
+    /// Makes this process the primary instance, or hands `request` to the one
+    /// already running. Returns `false` when this process should exit.
+    pub fn claim(app: &AppHandle, request: &OpenRequest) -> bool {
+        let Ok(dir) = app.path().app_local_data_dir() else {
+            return true;
+        };
+        let app = app.clone();
+        claim_in(&dir, request, move |request| handle(&app, request))
+    }
+
+    /// [`claim`] with the socket in `dir`. As the primary instance, requests
+    /// forwarded later go to `on_request` on a thread of their own.
+    fn claim_in(
+        dir: &Path,
+        request: &OpenRequest,
+        on_request: impl Fn(OpenRequest) + Send + 'static,
+    ) -> bool {
+        let socket = dir.join(SOCKET_FILE);
+
+        // Two instances starting together may both find no listener; the
+        // one that loses the bind forwards instead.
+        for _ in 0..2 {
+            match forward(&socket, request) {
+                Ok(()) => return false,
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    // Left behind by an instance that did not exit cleanly.
+                    let _ = fs::remove_file(&socket);
+                }
+                Err(_) => {}
+            }
+            match bind(dir, &socket) {
+                Ok(listener) => {
+                    listen(listener, on_request);
+                    return true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => continue,
+                Err(e) => {
+                    eprintln!("single instance: {}: {e}", socket.display());
+                    return true;
+                }
+            }
+        }
+        true
+    }
+
+    fn forward(socket: &Path, request: &OpenRequest) -> io::Result<()> {
+        let mut stream = UnixStream::connect(socket)?;
+        let mut line = serde_json::to_string(request).map_err(io::Error::other)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())
+    }
+
+    fn bind(dir: &Path, socket: &Path) -> io::Result<UnixListener> {
+        fs::create_dir_all(dir)?;
+        UnixListener::bind(socket)
+    }
+
+    /// Carries out a request forwarded by another invocation.
+    fn handle(app: &AppHandle, request: OpenRequest) {
+        if request.documents.is_empty() {
+            if let Some(window) = crate::menu::focused_window(app) {
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+            return;
+        }
+        for args in request.documents {
+            if let Err(e) = crate::documents::open(app, args) {
+                eprintln!("single instance: {e}");
+            }
+        }
+    }
+
+    fn listen(listener: UnixListener, on_request: impl Fn(OpenRequest) + Send + 'static) {
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let mut line = String::new();
+                if BufReader::new(stream).read_line(&mut line).is_err() {
+                    continue;
+                }
+                match serde_json::from_str::<OpenRequest>(&line) {
+                    Ok(request) => on_request(request),
+                    Err(e) => eprintln!("single instance: bad request: {e}"),
+                }
+            }
+        });
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        use super::*;
+        use crate::CliArgs;
+
+        /// A fresh directory for one test's socket.
+        fn socket_dir(name: &str) -> std::path::PathBuf {
+            let dir = std::env::temp_dir()
+                .join(format!("bmermaid-instance-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            dir
+        }
+
+        #[test]
+        fn second_claim_forwards_its_request() {
+            let dir = socket_dir("forward");
+            let (sender, received) = mpsc::channel();
+            let primary = claim_in(&dir, &OpenRequest::default(), move |request| {
+                sender.send(request).unwrap();
+            });
+            assert!(primary);
+
+            let request = OpenRequest::new(vec![CliArgs {
+                file: Some("diagram.mmd".to_string()),
+                line: Some(12),
+                column: Some(4),
+                ..CliArgs::default()
+            }]);
+            let second = claim_in(&dir, &request, |_| panic!("only the primary listens"));
+            assert!(!second);
+
+            let forwarded = received.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(forwarded.documents.len(), 1);
+            let document = &forwarded.documents[0];
+            assert_eq!(document.file, request.documents[0].file);
+            assert!(Path::new(document.file.as_deref().unwrap()).is_absolute());
+            assert_eq!((document.line, document.column), (Some(12), Some(4)));
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        /// Set, to its socket dir, in the test binary run as a second
+        /// invocation by [`another_process_forwards_and_exits`].
+        const CHILD_DIR: &str = "BMERMAID_INSTANCE_CHILD_DIR";
+
+        #[test]
+        fn another_process_forwards_and_exits() {
+            let dir = socket_dir("process");
+            let (sender, received) = mpsc::channel();
+            assert!(claim_in(&dir, &OpenRequest::default(), move |request| {
+                sender.send(request).unwrap();
+            }));
+
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "instance::unix::tests::child_claim",
+                    "--nocapture",
+                ])
+                .env(CHILD_DIR, &dir)
+                .status()
+                .unwrap();
+            assert!(status.success());
+
+            let forwarded = received.recv_timeout(Duration::from_secs(5)).unwrap();
+            let files: Vec<_> = forwarded
+                .documents
+                .iter()
+                .map(|d| d.file.as_deref())
+                .collect();
+            assert_eq!(files, [Some("/child.mmd")]);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        /// The second invocation of [`another_process_forwards_and_exits`];
+        /// does nothing in a normal test run.
+        #[test]
+        fn child_claim() {
+            let Some(dir) = std::env::var_os(CHILD_DIR) else {
+                return;
+            };
+            let request = OpenRequest::new(vec![CliArgs {
+                file: Some("/child.mmd".to_string()),
+                ..CliArgs::default()
+            }]);
+            let primary = claim_in(Path::new(&dir), &request, |_| {
+                panic!("the parent process is the primary")
+            });
+            assert!(!primary);
+        }
+
+        #[test]
+        fn stale_socket_is_taken_over() {
+            let dir = socket_dir("stale");
+            fs::create_dir_all(&dir).unwrap();
+            // Bound and dropped: the file stays but nothing listens.
+            drop(UnixListener::bind(dir.join(SOCKET_FILE)).unwrap());
+
+            assert!(claim_in(&dir, &OpenRequest::default(), |_| {}));
+            assert!(!claim_in(&dir, &OpenRequest::default(), |_| {}));
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
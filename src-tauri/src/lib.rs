@@ -1,37 +1,335 @@
+use std::path::{Path, PathBuf};
+
 use serde::Serialize;
-use tauri::Manager;
+use tauri::{AppHandle, Manager, State, WebviewWindow, WebviewWindowBuilder};
+
+mod autosave;
+mod check;
+mod cli;
+mod completions;
+mod config;
+mod diff;
+#[cfg(desktop)]
+mod documents;
+mod embed;
+mod export;
+mod format;
+#[cfg(desktop)]
+mod instance;
+mod lint;
+#[cfg(desktop)]
+mod menu;
+pub mod parse;
+mod pdf;
+mod postprocess;
+mod raster;
+mod recent;
+mod save;
+mod schema;
+mod subset;
+mod svg;
+mod watch;
+
+use schema::CliArgs;
+
+/// Syntax diagnostics for the editor's squiggles and problems list.
+#[tauri::command]
+fn parse_diagnostics(source: String) -> Vec<check::Diagnostic> {
+    check::diagnostics(&source)
+}
+
+/// Lint warnings for the editor, configured by the `.bmermaid.toml` nearest
+/// to `path` once the document has been saved.
+#[tauri::command]
+fn lint_diagnostics(
+    source: String,
+    path: Option<String>,
+) -> Result<Vec<check::Diagnostic>, String> {
+    let config = match path {
+        Some(path) => config::Config::for_file(Path::new(&path)).map_err(|e| e.to_string())?,
+        None => config::Config::default(),
+    };
+    Ok(lint::lint(&source, &config))
+}
+
+/// Writes the document atomically, keeping a `.bak` copy when the nearest
+/// `.bmermaid.toml` sets `save.backup`.
+#[tauri::command]
+fn save_document(
+    window: WebviewWindow,
+    watchers: State<'_, watch::Watchers>,
+    path: String,
+    content: String,
+) -> Result<(), save::SaveError> {
+    let path = PathBuf::from(path);
+    // A broken config must not stand in the way of saving.
+    let backup = config::Config::for_file(&path)
+        .map(|config| config.save.backup)
+        .unwrap_or(false);
+    watchers.write(window.label(), || save::save(&path, &content, backup))
+}
+
+/// Reports external changes to the window's document from now on.
+#[tauri::command]
+fn watch_document(window: WebviewWindow, watchers: State<'_, watch::Watchers>, path: String) {
+    watchers.watch(&window, PathBuf::from(path));
+}
+
+#[tauri::command]
+fn unwatch_document(window: WebviewWindow, watchers: State<'_, watch::Watchers>) {
+    watchers.unwatch(window.label());
+}
+
+/// Keeps the window's unsaved buffer for crash recovery.
+#[tauri::command]
+fn autosave_update(
+    window: WebviewWindow,
+    autosave: State<'_, autosave::Autosave>,
+    path: Option<String>,
+    content: String,
+) {
+    autosave.update(window.label(), path, content);
+}
+
+/// Drops the window's snapshot after the buffer is saved or discarded.
+#[tauri::command]
+fn autosave_clear(window: WebviewWindow, autosave: State<'_, autosave::Autosave>) {
+    autosave.clear(window.label());
+}
+
+/// Snapshots a crashed session left behind, newest first.
+#[tauri::command]
+fn recovered_snapshots(autosave: State<'_, autosave::Autosave>) -> Vec<autosave::Snapshot> {
+    autosave.recovered()
+}
+
+#[tauri::command]
+fn discard_snapshot(autosave: State<'_, autosave::Autosave>, id: String) {
+    autosave.discard(&id);
+}
+
+/// Records the window's document and shows it in the title bar.
+#[tauri::command]
+fn update_document(window: WebviewWindow, path: Option<String>, dirty: bool) {
+    #[cfg(desktop)]
+    {
+        let path = path.map(PathBuf::from);
+        window
+            .set_title(&documents::title(path.as_deref(), dirty))
+            .ok();
+        window
+            .state::<documents::Documents>()
+            .update(window.label(), path, dirty);
+    }
+    #[cfg(not(desktop))]
+    let _ = (window, path, dirty);
+}
+
+/// Opens `path`, or an empty document, in a window of its own.
+#[tauri::command]
+fn open_document(app: AppHandle, path: Option<String>) -> Result<(), String> {
+    #[cfg(desktop)]
+    documents::open(
+        &app,
+        CliArgs {
+            file: path,
+            ..CliArgs::default()
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    #[cfg(not(desktop))]
+    let _ = (app, path);
+    Ok(())
+}
 
-#[derive(Serialize)]
-struct CliArgs {
-    file: Option<String>,
-    theme: Option<String>,
+/// Recently opened or saved documents, most recent first.
+#[tauri::command]
+fn recent_files(recent: State<'_, recent::RecentFiles>) -> Vec<String> {
+    recent
+        .list()
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+/// Puts `path` at the top of the recent files and of "Open Recent".
+#[tauri::command]
+fn add_recent_file(app: AppHandle, recent: State<'_, recent::RecentFiles>, path: String) {
+    recent.add(Path::new(&path));
+    #[cfg(desktop)]
+    menu::refresh_recent(&app).ok();
+    #[cfg(not(desktop))]
+    let _ = app;
+}
+
+/// How the editor's `content` differs from the file at `path`.
+#[tauri::command]
+fn diff_with_disk(path: String, content: String) -> Result<Vec<diff::DiffLine>, String> {
+    let disk = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+    Ok(diff::diff_lines(&disk, &content))
+}
+
+/// The document in canonical form. Documents with syntax errors are left
+/// alone.
+#[tauri::command]
+fn format_document(source: String) -> Result<String, String> {
+    format::format(&source).map_err(|errors| match errors.len() {
+        1 => "cannot format: 1 syntax error".to_string(),
+        n => format!("cannot format: {n} syntax errors"),
+    })
+}
+
+/// Saves the diagram as an SVG, PNG or PDF file picked in a save dialog,
+/// with `source` embedded. `path` is the document's file. Returns the path
+/// written, or `None` when the dialog was cancelled. Async so the dialog
+/// and large images stay off the main thread.
+#[tauri::command]
+async fn export_diagram(
+    window: WebviewWindow,
+    exports: State<'_, export::ExportDirs>,
+    path: Option<String>,
+    format: String,
+    svg: String,
+    source: String,
+) -> Result<Option<String>, String> {
+    let document = path.map(PathBuf::from);
+    let written = export::export(
+        &window,
+        &exports,
+        document.as_deref(),
+        &format,
+        &svg,
+        &source,
+    )?;
+    Ok(written.map(|path| path.display().to_string()))
+}
+
+/// The Mermaid source embedded in an exported SVG or PNG.
+#[tauri::command]
+fn image_source(path: String) -> Result<String, String> {
+    embed::read_source(Path::new(&path)).map_err(|e| format!("{path}: {e}"))
+}
+
+/// Script that hands `args` to a window's webview as `window.__CLI_ARGS__`
+/// before any of the page's own scripts run.
+fn cli_args_script(args: &impl Serialize) -> String {
+    format!(
+        "window.__CLI_ARGS__ = {};",
+        serde_json::to_string(args).unwrap()
+    )
+}
+
+/// Builds the main window from its `tauri.conf.json` entry, which is not
+/// created on its own, with `args` for its webview. It stays hidden until
+/// shown.
+fn main_window(app: &AppHandle, args: &impl Serialize) -> tauri::Result<WebviewWindow> {
+    let config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == "main")
+        .cloned()
+        .unwrap_or_default();
+    WebviewWindowBuilder::from_config(app, &config)?
+        .initialization_script(cli_args_script(args))
+        .build()
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    #[cfg(all(desktop, unix, not(target_os = "macos")))]
+    cli::require_display();
+    let stdin = cli::Stdin::read();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_cli::init())
         .plugin(tauri_plugin_clipboard_manager::init())
+        .manage(watch::Watchers::default())
+        .manage(stdin)
+        .invoke_handler(tauri::generate_handler![
+            parse_diagnostics,
+            lint_diagnostics,
+            format_document,
+            export_diagram,
+            image_source,
+            save_document,
+            watch_document,
+            unwatch_document,
+            diff_with_disk,
+            autosave_update,
+            autosave_clear,
+            recovered_snapshots,
+            discard_snapshot,
+            recent_files,
+            add_recent_file,
+            update_document,
+            open_document
+        ])
         .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            app.manage(autosave::Autosave::open(data_dir.join("autosave")));
+            autosave::start(app.handle().clone());
+            app.manage(recent::RecentFiles::load(data_dir.join("recent.json")));
+            app.manage(export::ExportDirs::load(data_dir.join("export-dirs.json")));
+
             #[cfg(desktop)]
             {
-                use tauri_plugin_cli::CliExt;
-                match app.cli().matches() {
-                    Ok(matches) => {
-                        let file = matches.args.get("file").and_then(|v| v.value.as_str()).map(|s| s.to_string());
-                        let theme = matches.args.get("theme").and_then(|v| v.value.as_str()).map(|s| s.to_string());
-                        
-                        let args = CliArgs { file, theme };
-                        
-                        let window = app.get_webview_window("main").unwrap();
-                        window.eval(&format!("window.__CLI_ARGS__ = {};", serde_json::to_string(&args).unwrap())).ok();
+                app.manage(documents::Documents::default());
+                let matches = match cli::matches(app) {
+                    Ok(matches) => matches,
+                    Err(code) => {
+                        app.handle().exit(code);
+                        return Ok(());
+                    }
+                };
+                if let Some(subcommand) = &matches.subcommand {
+                    cli::run_subcommand(app, subcommand);
+                    return Ok(());
+                }
+
+                let stdin = app.state::<cli::Stdin>().0.clone();
+                let requested = match cli::open_args(&matches, stdin) {
+                    Ok(requested) => requested,
+                    Err(errors) => {
+                        for error in errors {
+                            eprintln!("{error}");
+                        }
+                        app.handle().exit(cli::EXIT_USAGE_ERROR);
+                        return Ok(());
                     }
-                    Err(_) => {}
+                };
+
+                let request = instance::OpenRequest::new(requested.clone());
+                if !instance::claim(app.handle(), &request) {
+                    app.handle().exit(0);
+                    return Ok(());
+                }
+
+                // The main window takes the first document, the rest get
+                // windows of their own.
+                let mut requested = requested.into_iter();
+                let args = requested.next().unwrap_or_else(|| CliArgs {
+                    theme: cli::arg_str(&matches, "theme"),
+                    ..CliArgs::default()
+                });
+
+                let window = main_window(app.handle(), &args)?;
+                for args in requested {
+                    documents::open(app.handle(), args)?;
                 }
+                documents::guard_close(&window);
+                window.show()?;
+
+                app.set_menu(menu::build(app.handle())?)?;
+                app.on_menu_event(menu::handle_event);
             }
+
+            #[cfg(not(desktop))]
+            main_window(app.handle(), &CliArgs::default())?.show()?;
             Ok(())
         })
         .run(tauri::generate_context!())
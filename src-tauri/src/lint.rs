@@ -0,0 +1,491 @@
+//! Warnings about diagrams that parse but are probably not what the author
+//! meant. Each rule has a default level that `.bmermaid.toml` can override.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::Deserialize;
+
+use crate::check::{Diagnostic, LineIndex, Severity};
+use crate::config::Config;
+use crate::parse::{self, flowchart, sequence, Diagram, Fix, Ident, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn severity(self) -> Option<Severity> {
+        match self {
+            Level::Off => None,
+            Level::Info => Some(Severity::Info),
+            Level::Warning => Some(Severity::Warning),
+            Level::Error => Some(Severity::Error),
+        }
+    }
+}
+
+pub struct Rule {
+    /// Name used in `.bmermaid.toml` and as the diagnostic code.
+    pub name: &'static str,
+    pub default: Level,
+}
+
+pub static UNDEFINED_NODE: Rule = Rule {
+    name: "undefined-node",
+    default: Level::Warning,
+};
+pub static UNUSED_CLASS_DEF: Rule = Rule {
+    name: "unused-class-def",
+    default: Level::Warning,
+};
+pub static DUPLICATE_LABEL: Rule = Rule {
+    name: "duplicate-label",
+    default: Level::Warning,
+};
+pub static SELF_EDGE: Rule = Rule {
+    name: "self-edge",
+    default: Level::Warning,
+};
+pub static UNREACHABLE_NODE: Rule = Rule {
+    name: "unreachable-node",
+    default: Level::Warning,
+};
+pub static DEPRECATED_GRAPH: Rule = Rule {
+    name: "deprecated-graph",
+    default: Level::Info,
+};
+pub static UNDECLARED_PARTICIPANT: Rule = Rule {
+    name: "undeclared-participant",
+    default: Level::Warning,
+};
+
+pub static RULES: &[&Rule] = &[
+    &UNDEFINED_NODE,
+    &UNUSED_CLASS_DEF,
+    &DUPLICATE_LABEL,
+    &SELF_EDGE,
+    &UNREACHABLE_NODE,
+    &DEPRECATED_GRAPH,
+    &UNDECLARED_PARTICIPANT,
+];
+
+pub fn rule(name: &str) -> Option<&'static Rule> {
+    RULES.iter().copied().find(|rule| rule.name == name)
+}
+
+struct Finding {
+    rule: &'static Rule,
+    span: Span,
+    message: String,
+    fix: Option<Fix>,
+}
+
+impl Finding {
+    fn new(rule: &'static Rule, span: Span, message: impl Into<String>) -> Self {
+        Finding {
+            rule,
+            span,
+            message: message.into(),
+            fix: None,
+        }
+    }
+}
+
+/// Lints `source` with the levels from `config`. Sources with syntax errors
+/// get no lint diagnostics; those errors come from [`crate::check`].
+pub fn lint(source: &str, config: &Config) -> Vec<Diagnostic> {
+    let parsed = parse::parse(source);
+    let Some(document) = parsed.document.filter(|_| parsed.errors.is_empty()) else {
+        return Vec::new();
+    };
+    let findings = match &document.diagram {
+        Diagram::Flowchart(diagram) => lint_flowchart(diagram),
+        Diagram::Sequence(diagram) => lint_sequence(diagram),
+        _ => Vec::new(),
+    };
+
+    let index = LineIndex::new(source);
+    let mut diagnostics: Vec<(usize, Diagnostic)> = findings
+        .iter()
+        .filter_map(|finding| {
+            let severity = config.level(finding.rule).severity()?;
+            let diagnostic = index.report(
+                finding.span,
+                severity,
+                finding.rule.name,
+                &finding.message,
+                finding.fix.as_ref(),
+            );
+            Some((finding.span.start, diagnostic))
+        })
+        .collect();
+    diagnostics.sort_by_key(|&(start, _)| start);
+    diagnostics.into_iter().map(|(_, d)| d).collect()
+}
+
+/// Nodes, subgraphs and links of a flowchart, flattened out of subgraphs.
+#[derive(Default)]
+struct Graph<'a> {
+    /// First occurrence of each node, in source order.
+    nodes: Vec<&'a flowchart::Node>,
+    /// Subgraph ids with the ids of every node nested inside them.
+    subgraphs: HashMap<&'a str, Vec<&'a str>>,
+    links: Vec<(
+        &'a flowchart::Node,
+        &'a flowchart::Link,
+        &'a flowchart::Node,
+    )>,
+}
+
+impl<'a> Graph<'a> {
+    fn collect(&mut self, statements: &'a [flowchart::Statement]) -> Vec<&'a str> {
+        let mut members = Vec::new();
+        for statement in statements {
+            match statement {
+                flowchart::Statement::Chain(chain) => {
+                    for node in chain.groups.iter().flatten() {
+                        members.push(node.id.value.as_str());
+                        if !self.nodes.iter().any(|n| n.id.value == node.id.value) {
+                            self.nodes.push(node);
+                        }
+                    }
+                    for (i, link) in chain.links.iter().enumerate() {
+                        for from in &chain.groups[i] {
+                            for to in &chain.groups[i + 1] {
+                                self.links.push((from, link, to));
+                            }
+                        }
+                    }
+                }
+                flowchart::Statement::Subgraph(subgraph) => {
+                    let nested = self.collect(&subgraph.statements);
+                    if let Some(id) = &subgraph.id {
+                        members.push(id.value.as_str());
+                        self.subgraphs.insert(id.value.as_str(), nested.clone());
+                    }
+                    members.extend(nested);
+                }
+                _ => {}
+            }
+        }
+        members
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.subgraphs.contains_key(id) || self.nodes.iter().any(|n| n.id.value == id)
+    }
+}
+
+fn lint_flowchart(diagram: &flowchart::Flowchart) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut graph = Graph::default();
+    graph.collect(&diagram.statements);
+
+    if diagram.keyword.value == "graph" {
+        let mut finding = Finding::new(
+            &DEPRECATED_GRAPH,
+            diagram.keyword.span,
+            "`graph` is a legacy alias of `flowchart`",
+        );
+        finding.fix = Some(Fix {
+            title: "Replace with `flowchart`".to_string(),
+            span: diagram.keyword.span,
+            replacement: "flowchart".to_string(),
+        });
+        findings.push(finding);
+    }
+
+    let mut class_defs: Vec<&Ident> = Vec::new();
+    let mut used_classes: HashSet<&str> = HashSet::new();
+    let mut styled: Vec<&Ident> = Vec::new();
+    visit_flowchart(&diagram.statements, &mut |statement| match statement {
+        flowchart::Statement::ClassDef(class_def) => class_defs.extend(&class_def.names),
+        flowchart::Statement::Class(class) => {
+            used_classes.insert(class.class.value.as_str());
+            styled.extend(&class.nodes);
+        }
+        flowchart::Statement::Style(style) => styled.push(&style.node),
+        flowchart::Statement::Click(click) => styled.push(&click.node),
+        // Nodes can pick up classes anywhere in a chain.
+        flowchart::Statement::Chain(chain) => used_classes.extend(
+            chain
+                .groups
+                .iter()
+                .flatten()
+                .flat_map(|node| &node.classes)
+                .map(|class| class.value.as_str()),
+        ),
+        _ => {}
+    });
+
+    for id in styled {
+        if !graph.contains(&id.value) {
+            findings.push(Finding::new(
+                &UNDEFINED_NODE,
+                id.span,
+                format!("`{}` is not a node in this diagram", id.value),
+            ));
+        }
+    }
+
+    // `default` applies to every node without being assigned.
+    for name in class_defs {
+        if name.value != "default" && !used_classes.contains(name.value.as_str()) {
+            findings.push(Finding::new(
+                &UNUSED_CLASS_DEF,
+                name.span,
+                format!("class `{}` is defined but never used", name.value),
+            ));
+        }
+    }
+
+    let mut labels: HashMap<&str, &str> = HashMap::new();
+    for node in &graph.nodes {
+        let Some(label) = &node.label else { continue };
+        match labels.get(label.text.as_str()) {
+            Some(first) => findings.push(Finding::new(
+                &DUPLICATE_LABEL,
+                label.span,
+                format!("`{}` has the same label as `{first}`", node.id.value),
+            )),
+            None => {
+                labels.insert(&label.text, &node.id.value);
+            }
+        }
+    }
+
+    for &(from, link, to) in &graph.links {
+        if from.id.value == to.id.value {
+            findings.push(Finding::new(
+                &SELF_EDGE,
+                link.span,
+                format!("`{}` links to itself", from.id.value),
+            ));
+        }
+    }
+
+    findings.extend(unreachable_nodes(&graph));
+    findings
+}
+
+/// Calls `f` on every statement, including those nested in subgraphs.
+fn visit_flowchart<'a>(
+    statements: &'a [flowchart::Statement],
+    f: &mut impl FnMut(&'a flowchart::Statement),
+) {
+    for statement in statements {
+        f(statement);
+        if let flowchart::Statement::Subgraph(subgraph) = statement {
+            visit_flowchart(&subgraph.statements, f);
+        }
+    }
+}
+
+/// Nodes that no path of links leads to from a starting node: the first
+/// node, and every linked node that no one-way link points to, so graphs
+/// with several sources are fine. Links without a single arrow direction
+/// are followed both ways, and reaching a subgraph reaches everything
+/// inside it.
+fn unreachable_nodes<'a>(graph: &Graph<'a>) -> Vec<Finding> {
+    let Some(first) = graph.nodes.first() else {
+        return Vec::new();
+    };
+    if graph.links.is_empty() {
+        return Vec::new();
+    }
+
+    let mut next: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut linked = HashSet::new();
+    let mut pointed_to = HashSet::new();
+    for (from, link, to) in &graph.links {
+        let (from, to) = (from.id.value.as_str(), to.id.value.as_str());
+        linked.extend([from, to]);
+        let (from, to, one_way) = match (link.start, link.end) {
+            (None, Some(_)) => (from, to, true),
+            (Some(_), None) => (to, from, true),
+            _ => (from, to, false),
+        };
+        next.entry(from).or_default().push(to);
+        if one_way {
+            pointed_to.insert(to);
+        } else {
+            next.entry(to).or_default().push(from);
+        }
+    }
+    for (&subgraph, members) in &graph.subgraphs {
+        for &member in members {
+            next.entry(subgraph).or_default().push(member);
+            next.entry(member).or_default().push(subgraph);
+        }
+    }
+
+    let starts = graph
+        .nodes
+        .iter()
+        .map(|node| node.id.value.as_str())
+        .filter(|id| linked.contains(id) && !pointed_to.contains(id));
+    let mut reached: HashSet<&str> = HashSet::from([first.id.value.as_str()]);
+    reached.extend(starts);
+    let mut queue: VecDeque<&str> = reached.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        for &to in next.get(id).into_iter().flatten() {
+            if reached.insert(to) {
+                queue.push_back(to);
+            }
+        }
+    }
+
+    graph
+        .nodes
+        .iter()
+        .filter(|node| !reached.contains(node.id.value.as_str()))
+        .map(|node| {
+            let id = &node.id.value;
+            let message = if linked.contains(id.as_str()) {
+                format!("`{id}` cannot be reached from any node that starts a path")
+            } else {
+                format!("`{id}` is not linked to any other node")
+            };
+            Finding::new(&UNREACHABLE_NODE, node.id.span, message)
+        })
+        .collect()
+}
+
+fn lint_sequence(diagram: &sequence::SequenceDiagram) -> Vec<Finding> {
+    let mut declared = HashSet::new();
+    let mut messages = Vec::new();
+    visit_sequence(&diagram.statements, &mut |statement| match statement {
+        sequence::Statement::Participant(participant) => {
+            declared.insert(participant.id.value.as_str());
+        }
+        sequence::Statement::Message(message) => messages.push(message),
+        _ => {}
+    });
+    // Diagrams that declare nobody rely on implicit participants throughout,
+    // which is a style choice rather than a mistake.
+    if declared.is_empty() {
+        return Vec::new();
+    }
+
+    let mut findings = Vec::new();
+    let mut reported = HashSet::new();
+    for message in messages {
+        for id in [&message.from, &message.to] {
+            if !declared.contains(id.value.as_str()) && reported.insert(id.value.as_str()) {
+                findings.push(Finding::new(
+                    &UNDECLARED_PARTICIPANT,
+                    id.span,
+                    format!(
+                        "`{}` is not declared with `participant` or `actor`",
+                        id.value
+                    ),
+                ));
+            }
+        }
+    }
+    findings
+}
+
+/// Calls `f` on every statement, including those nested in blocks.
+fn visit_sequence<'a>(
+    statements: &'a [sequence::Statement],
+    f: &mut impl FnMut(&'a sequence::Statement),
+) {
+    for statement in statements {
+        f(statement);
+        if let sequence::Statement::Block(block) = statement {
+            for section in &block.sections {
+                visit_sequence(&section.statements, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(code, message)` of every finding with the default levels.
+    fn findings(source: &str) -> Vec<(&'static str, String)> {
+        lint(source, &Config::default())
+            .into_iter()
+            .map(|d| (d.code, d.message))
+            .collect()
+    }
+
+    fn unreachable(source: &str) -> Vec<String> {
+        findings(source)
+            .into_iter()
+            .filter(|(code, _)| *code == UNREACHABLE_NODE.name)
+            .map(|(_, message)| message)
+            .collect()
+    }
+
+    #[test]
+    fn several_sources_are_reachable() {
+        assert!(unreachable("flowchart TD\n  A --> C\n  B --> C\n").is_empty());
+        assert!(unreachable("flowchart TD\n  A --> B\n  C --- D\n  E <-- F\n").is_empty());
+    }
+
+    #[test]
+    fn unlinked_nodes_and_closed_cycles() {
+        assert_eq!(
+            unreachable("flowchart TD\n  A --> B\n  C\n  D --> E --> D\n"),
+            [
+                "`C` is not linked to any other node",
+                "`D` cannot be reached from any node that starts a path",
+                "`E` cannot be reached from any node that starts a path",
+            ]
+        );
+    }
+
+    #[test]
+    fn subgraphs_reach_their_members() {
+        let source = "flowchart TD\n  X --> S\n  subgraph S\n    A --> C\n    B\n  end\n";
+        assert!(unreachable(source).is_empty());
+    }
+
+    #[test]
+    fn flowchart_rules() {
+        let source = "graph TD\n  A[Same] --> A\n  B[Same] --> A\n  classDef unused fill:#f00\n  style Z fill:#0f0\n";
+        let codes: Vec<_> = findings(source).into_iter().map(|(code, _)| code).collect();
+        assert_eq!(
+            codes,
+            [
+                "deprecated-graph",
+                "self-edge",
+                "duplicate-label",
+                "unused-class-def",
+                "undefined-node",
+            ]
+        );
+    }
+
+    #[test]
+    fn undeclared_participants() {
+        let source = "sequenceDiagram\n  participant A\n  A->>B: hi\n  B->>A: hello\n";
+        assert_eq!(
+            findings(source),
+            [(
+                "undeclared-participant",
+                "`B` is not declared with `participant` or `actor`".to_string()
+            )]
+        );
+        assert!(findings("sequenceDiagram\n  A->>B: hi\n").is_empty());
+    }
+
+    #[test]
+    fn levels_from_config() {
+        let config: Config =
+            toml::from_str("[lint]\nself-edge = \"error\"\nunreachable-node = \"off\"\n").unwrap();
+        let diagnostics = lint("flowchart TD\n  A --> A\n  B\n", &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "self-edge");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+}
@@ -0,0 +1,184 @@
+//! The native menu bar. Items that act on a document are forwarded to the
+//! focused window as events; the webview owns the actual behavior.
+
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow, Wry};
+
+use crate::cli::THEMES;
+use crate::documents;
+use crate::recent::RecentFiles;
+
+/// Emitted with the id of the menu item that was chosen, such as `save` or
+/// `theme:nord`.
+pub const MENU_EVENT: &str = "menu";
+/// Emitted with the path of a document picked from "Open Recent".
+pub const OPEN_RECENT_EVENT: &str = "open-recent";
+
+/// Items forwarded as [`MENU_EVENT`]: id, label and accelerator.
+const FILE_ITEMS: &[(&str, &str, Option<&str>)] = &[
+    ("new", "New", Some("CmdOrCtrl+N")),
+    ("open", "Open…", Some("CmdOrCtrl+O")),
+];
+const SAVE_ITEMS: &[(&str, &str, Option<&str>)] = &[
+    ("save", "Save", Some("CmdOrCtrl+S")),
+    ("save-as", "Save As…", Some("CmdOrCtrl+Shift+S")),
+];
+const EXPORT_ITEMS: &[(&str, &str, Option<&str>)] = &[
+    ("export-svg", "Export SVG…", Some("CmdOrCtrl+E")),
+    ("export-png", "Export PNG…", Some("CmdOrCtrl+Shift+E")),
+    ("export-pdf", "Export PDF…", None),
+];
+const ZOOM_ITEMS: &[(&str, &str, Option<&str>)] = &[
+    ("zoom-in", "Zoom In", Some("CmdOrCtrl+=")),
+    ("zoom-out", "Zoom Out", Some("CmdOrCtrl+-")),
+    ("zoom-reset", "Actual Size", Some("CmdOrCtrl+0")),
+];
+const FORMAT: &str = "format";
+const THEME_PREFIX: &str = "theme:";
+
+const RECENT_PREFIX: &str = "recent:";
+const CLEAR_RECENT: &str = "clear-recent";
+/// Replaces the predefined Quit, which exits without asking about unsaved
+/// changes.
+const QUIT: &str = "quit";
+
+/// The "Open Recent" submenu, kept so it can follow the recent-files store.
+pub struct RecentMenu(Submenu<Wry>);
+
+pub fn build(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let open_recent = Submenu::new(app, "Open Recent", true)?;
+    let file = Submenu::new(app, "File", true)?;
+    append_items(app, &file, FILE_ITEMS)?;
+    file.append(&open_recent)?;
+    file.append(&PredefinedMenuItem::separator(app)?)?;
+    append_items(app, &file, SAVE_ITEMS)?;
+    file.append(&PredefinedMenuItem::separator(app)?)?;
+    append_items(app, &file, EXPORT_ITEMS)?;
+    file.append(&PredefinedMenuItem::separator(app)?)?;
+    file.append(&MenuItem::with_id(
+        app,
+        QUIT,
+        "Quit",
+        true,
+        Some("CmdOrCtrl+Q"),
+    )?)?;
+
+    let edit = Submenu::with_items(
+        app,
+        "Edit",
+        true,
+        &[
+            &PredefinedMenuItem::undo(app, None)?,
+            &PredefinedMenuItem::redo(app, None)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::cut(app, None)?,
+            &PredefinedMenuItem::copy(app, None)?,
+            &PredefinedMenuItem::paste(app, None)?,
+            &PredefinedMenuItem::select_all(app, None)?,
+            &PredefinedMenuItem::separator(app)?,
+            // Shift+Alt+F is bound in the editor itself.
+            &MenuItem::with_id(app, FORMAT, "Format Document", true, None::<&str>)?,
+        ],
+    )?;
+
+    let theme = Submenu::new(app, "Theme", true)?;
+    for (name, label) in THEMES {
+        let id = format!("{THEME_PREFIX}{name}");
+        theme.append(&MenuItem::with_id(app, id, label, true, None::<&str>)?)?;
+    }
+    let view = Submenu::new(app, "View", true)?;
+    append_items(app, &view, ZOOM_ITEMS)?;
+    view.append(&PredefinedMenuItem::separator(app)?)?;
+    view.append(&theme)?;
+
+    let menu = Menu::with_items(app, &[&file, &edit, &view])?;
+
+    app.manage(RecentMenu(open_recent));
+    refresh_recent(app)?;
+    Ok(menu)
+}
+
+fn append_items(
+    app: &AppHandle,
+    submenu: &Submenu<Wry>,
+    items: &[(&str, &str, Option<&str>)],
+) -> tauri::Result<()> {
+    for &(id, label, accelerator) in items {
+        submenu.append(&MenuItem::with_id(app, id, label, true, accelerator)?)?;
+    }
+    Ok(())
+}
+
+/// Rebuilds "Open Recent" from the recent-files store.
+pub fn refresh_recent(app: &AppHandle) -> tauri::Result<()> {
+    let Some(menu) = app.try_state::<RecentMenu>() else {
+        return Ok(());
+    };
+    let submenu = &menu.0;
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+
+    let paths = app.state::<RecentFiles>().list();
+    if paths.is_empty() {
+        let empty = MenuItem::new(app, "No Recent Files", false, None::<&str>)?;
+        return submenu.append(&empty);
+    }
+    for path in &paths {
+        let path = path.display().to_string();
+        let item = MenuItem::with_id(
+            app,
+            format!("{RECENT_PREFIX}{path}"),
+            &path,
+            true,
+            None::<&str>,
+        )?;
+        submenu.append(&item)?;
+    }
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    submenu.append(&MenuItem::with_id(
+        app,
+        CLEAR_RECENT,
+        "Clear Recent",
+        true,
+        None::<&str>,
+    )?)
+}
+
+pub fn handle_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    if let Some(path) = id.strip_prefix(RECENT_PREFIX) {
+        emit_focused(app, OPEN_RECENT_EVENT, path);
+    } else if id == CLEAR_RECENT {
+        app.state::<RecentFiles>().clear();
+        let _ = refresh_recent(app);
+    } else if id == QUIT {
+        documents::close_all(app);
+    } else if is_action(id) {
+        emit_focused(app, MENU_EVENT, id);
+    }
+}
+
+fn is_action(id: &str) -> bool {
+    id == FORMAT
+        || id.starts_with(THEME_PREFIX)
+        || [FILE_ITEMS, SAVE_ITEMS, EXPORT_ITEMS, ZOOM_ITEMS]
+            .iter()
+            .any(|items| items.iter().any(|&(action, _, _)| action == id))
+}
+
+fn emit_focused(app: &AppHandle, event: &str, payload: &str) {
+    if let Some(window) = focused_window(app) {
+        let _ = window.emit_to(window.label(), event, payload);
+    }
+}
+
+/// The window menu actions apply to: the focused one, else `main`.
+pub fn focused_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = app.webview_windows();
+    windows
+        .values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| windows.get("main"))
+        .cloned()
+}
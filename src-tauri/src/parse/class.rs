@@ -0,0 +1,853 @@
+//! `classDiagram` diagrams.
+
+use serde::Serialize;
+
+use super::{Comment, Direction, Ident, Label, Parser, Span, Spanned, Verbatim};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassDiagram {
+    pub keyword: Ident,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Statement {
+    Class(Class),
+    /// `Animal : +int age`, adding one member outside a class body.
+    Member(MemberStatement),
+    Relationship(Relationship),
+    /// `<<interface>> Shape`
+    Annotation(Annotation),
+    Namespace(Namespace),
+    Note(Note),
+    Direction(Spanned<Direction>),
+    /// Styling and interaction statements (`style`, `classDef`,
+    /// `cssClass`, `click`, `link`, `callback`) kept verbatim.
+    Verbatim(Verbatim),
+    Comment(Comment),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Class(class) => class.span,
+            Statement::Member(member) => member.span,
+            Statement::Relationship(relationship) => relationship.span,
+            Statement::Annotation(annotation) => annotation.span,
+            Statement::Namespace(namespace) => namespace.span,
+            Statement::Note(note) => note.span,
+            Statement::Direction(direction) => direction.span,
+            Statement::Verbatim(verbatim) => verbatim.span,
+            Statement::Comment(comment) => comment.span,
+        }
+    }
+}
+
+/// `class Animal~T~["Label"] { ... }`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Class {
+    pub name: Ident,
+    /// The text between `~` markers, e.g. `T` in `List~T~`.
+    pub generic: Option<Ident>,
+    pub label: Option<Label>,
+    /// Style classes attached with `:::name`.
+    pub classes: Vec<Ident>,
+    /// `<<interface>>`-style annotations written inside the body.
+    pub annotations: Vec<Ident>,
+    pub members: Vec<Member>,
+    /// Comment lines inside the body.
+    pub comments: Vec<Comment>,
+    /// The `{ }` body, `None` for a bare declaration.
+    pub body: Option<Span>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Visibility {
+    /// `+`
+    Public,
+    /// `-`
+    Private,
+    /// `#`
+    Protected,
+    /// `~`
+    Package,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Classifier {
+    /// `*`
+    Abstract,
+    /// `$`
+    Static,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum MemberKind {
+    /// `String name` or `name: String`, kept as written.
+    Attribute { text: String },
+    /// `eat(food) bool`
+    Method {
+        name: String,
+        params: String,
+        return_type: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Member {
+    pub visibility: Option<Visibility>,
+    pub kind: MemberKind,
+    pub classifier: Option<Classifier>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemberStatement {
+    pub class: Ident,
+    pub member: Member,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RelationEnd {
+    /// `<|` / `|>`
+    Inheritance,
+    /// `*`
+    Composition,
+    /// `o`
+    Aggregation,
+    /// `<` / `>`
+    Association,
+    /// `()`
+    Lollipop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RelationLine {
+    /// `--`
+    Solid,
+    /// `..`
+    Dashed,
+}
+
+/// Heads allowed on the left of the line, longest first.
+const LEFT_ENDS: &[(&str, RelationEnd)] = &[
+    ("<|", RelationEnd::Inheritance),
+    ("()", RelationEnd::Lollipop),
+    ("*", RelationEnd::Composition),
+    ("o", RelationEnd::Aggregation),
+    ("<", RelationEnd::Association),
+];
+
+/// Heads allowed on the right of the line, longest first.
+const RIGHT_ENDS: &[(&str, RelationEnd)] = &[
+    ("|>", RelationEnd::Inheritance),
+    ("()", RelationEnd::Lollipop),
+    ("*", RelationEnd::Composition),
+    ("o", RelationEnd::Aggregation),
+    (">", RelationEnd::Association),
+];
+
+/// The arrow between two classes, e.g. `<|--` or `..>`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Relation {
+    pub left: Option<RelationEnd>,
+    pub line: RelationLine,
+    pub right: Option<RelationEnd>,
+    pub span: Span,
+}
+
+impl Relation {
+    /// The relation token as written canonically, e.g. `<|--`.
+    pub fn token(&self) -> String {
+        let end = |ends: &[(&'static str, RelationEnd)], end: Option<RelationEnd>| {
+            end.and_then(|end| ends.iter().find(|(_, e)| *e == end))
+                .map_or("", |(token, _)| token)
+        };
+        let line = match self.line {
+            RelationLine::Solid => "--",
+            RelationLine::Dashed => "..",
+        };
+        format!(
+            "{}{line}{}",
+            end(LEFT_ENDS, self.left),
+            end(RIGHT_ENDS, self.right)
+        )
+    }
+}
+
+/// `Customer "1" --> "*" Ticket : opens`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relationship {
+    pub from: Ident,
+    pub from_cardinality: Option<Label>,
+    pub relation: Relation,
+    pub to_cardinality: Option<Label>,
+    pub to: Ident,
+    pub label: Option<Label>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Annotation {
+    pub name: Ident,
+    pub class: Ident,
+    pub span: Span,
+}
+
+/// `namespace Shapes { ... }`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Namespace {
+    pub name: Ident,
+    pub statements: Vec<Statement>,
+    /// The closing `}`, missing when the body is unterminated.
+    pub close: Option<Span>,
+    pub span: Span,
+}
+
+/// `note for Duck "can fly"` / `note "text"`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Note {
+    pub class: Option<Ident>,
+    pub text: Label,
+    pub span: Span,
+}
+
+const VERBATIM_KEYWORDS: &[&str] = &["style", "classDef", "cssClass", "click", "link", "callback"];
+
+pub(super) fn parse(p: &mut Parser, keyword: Ident) -> ClassDiagram {
+    p.expect_statement_end();
+    let (statements, _) = statements(p, false);
+    ClassDiagram {
+        keyword,
+        statements,
+    }
+}
+
+/// Parses statements until EOF or, inside a namespace, until the closing
+/// `}`, which is consumed and returned.
+fn statements(p: &mut Parser, in_namespace: bool) -> (Vec<Statement>, Option<Span>) {
+    let mut statements = Vec::new();
+    loop {
+        p.cur.skip_empty();
+        if p.cur.is_eof() {
+            return (statements, None);
+        }
+        if let Some(comment) = p.comment() {
+            statements.push(Statement::Comment(comment));
+            continue;
+        }
+        if p.cur.peek() == Some('}') {
+            let close = Span::new(p.cur.pos(), p.cur.pos() + 1);
+            p.cur.bump();
+            if in_namespace {
+                p.expect_statement_end();
+                return (statements, Some(close));
+            }
+            p.error(close, "unmatched `}`");
+            p.expect_statement_end();
+            continue;
+        }
+        if let Some(statement) = statement(p) {
+            statements.push(statement);
+        }
+    }
+}
+
+fn statement(p: &mut Parser) -> Option<Statement> {
+    let start = p.cur.pos();
+    if let Some(keyword) = p.cur.eat_keyword("class") {
+        return class(p, keyword).map(Statement::Class);
+    }
+    if let Some(keyword) = p.cur.eat_keyword("namespace") {
+        return namespace(p, keyword).map(Statement::Namespace);
+    }
+
+    let statement = if let Some(verbatim) = p.verbatim(VERBATIM_KEYWORDS) {
+        Statement::Verbatim(verbatim)
+    } else if p.cur.eat_keyword("direction").is_some() {
+        p.cur.skip_blank();
+        Statement::Direction(p.direction()?)
+    } else if p.cur.eat_keyword("note").is_some() {
+        Statement::Note(note(p, start)?)
+    } else if p.cur.starts_with("<<") {
+        let name = annotation(p)?;
+        p.cur.skip_blank();
+        let Some(class) = class_name(p) else {
+            p.recover("expected a class name after the annotation");
+            return None;
+        };
+        Statement::Annotation(Annotation {
+            name,
+            class,
+            span: Span::new(start, p.cur.pos()),
+        })
+    } else {
+        let Some(name) = class_name(p) else {
+            p.recover("expected a class, relationship or statement keyword");
+            return None;
+        };
+        p.cur.skip_blank();
+        if p.cur.eat_char(':') {
+            p.cur.skip_blank();
+            let (text, span) = p.cur.rest_of_statement();
+            if text.is_empty() {
+                p.error(span, "expected a member after `:`");
+                return None;
+            }
+            Statement::Member(MemberStatement {
+                class: name,
+                member: member(text, span),
+                span: Span::new(start, p.cur.pos()),
+            })
+        } else {
+            Statement::Relationship(relationship(p, name)?)
+        }
+    };
+    p.expect_statement_end();
+    Some(statement)
+}
+
+/// Reads a class name with an optional `~generic~` suffix, which stays part
+/// of the name.
+fn class_name(p: &mut Parser) -> Option<Ident> {
+    let mut name = p.cur.ident()?;
+    if p.cur.peek() == Some('~') {
+        let at = p.cur.pos();
+        p.cur.bump();
+        match p.cur.take_until("~") {
+            Some(_) => {
+                p.cur.bump();
+                name.span = name.span.to(Span::new(at, p.cur.pos()));
+                name.value = p.cur.slice(name.span).to_string();
+            }
+            None => p.cur.set_pos(at),
+        }
+    }
+    Some(name)
+}
+
+/// Reads `<<name>>` and returns the name.
+fn annotation(p: &mut Parser) -> Option<Ident> {
+    let start = p.cur.pos();
+    p.cur.eat("<<");
+    let Some((text, span)) = p.cur.take_until(">>") else {
+        p.error(
+            Span::new(start, start + 2),
+            "`<<` is never closed with `>>`",
+        );
+        p.cur.skip_line();
+        return None;
+    };
+    p.cur.eat(">>");
+    let name = text.trim();
+    let at = span.start + (text.len() - text.trim_start().len());
+    Some(Spanned::new(
+        name.to_string(),
+        Span::new(at, at + name.len()),
+    ))
+}
+
+fn class(p: &mut Parser, keyword: Span) -> Option<Class> {
+    p.cur.skip_blank();
+    let Some(name) = p.cur.ident() else {
+        p.recover("expected a class name");
+        return None;
+    };
+
+    let mut generic = None;
+    if p.cur.eat_char('~') {
+        let Some((text, span)) = p.cur.take_until("~") else {
+            p.recover("generic type is never closed with `~`");
+            return None;
+        };
+        p.cur.bump();
+        generic = Some(Spanned::new(text.to_string(), span));
+    }
+
+    let mut label = None;
+    if p.cur.eat_char('[') {
+        let Some(text) = p.label_until("]") else {
+            p.recover("class label is never closed with `]`");
+            return None;
+        };
+        p.cur.eat_char(']');
+        label = Some(text);
+    }
+
+    let mut classes = Vec::new();
+    while p.cur.eat(":::") {
+        let Some(class) = p.cur.ident() else {
+            p.recover("expected a style class after `:::`");
+            return None;
+        };
+        classes.push(class);
+    }
+
+    p.cur.skip_blank();
+    let mut annotations = Vec::new();
+    let mut members = Vec::new();
+    let mut comments = Vec::new();
+    let mut body = None;
+    if p.cur.peek() == Some('{') {
+        let open = p.cur.pos();
+        p.cur.bump();
+        let close = class_body(p, &mut annotations, &mut members, &mut comments);
+        match close {
+            Some(close) => body = Some(Span::new(open, close.end)),
+            None => {
+                let fix = p.append_line_fix("}");
+                p.error_with_fix(Span::new(open, open + 1), "unclosed `{`", fix);
+                body = Some(Span::new(open, p.cur.pos()));
+            }
+        }
+    }
+    p.expect_statement_end();
+
+    let end = body.map_or(p.cur.pos(), |b| b.end);
+    Some(Class {
+        name,
+        generic,
+        label,
+        classes,
+        annotations,
+        members,
+        comments,
+        body,
+        span: Span::new(keyword.start, end),
+    })
+}
+
+/// Parses member lines up to the closing `}` and returns its span.
+fn class_body(
+    p: &mut Parser,
+    annotations: &mut Vec<Ident>,
+    members: &mut Vec<Member>,
+    comments: &mut Vec<Comment>,
+) -> Option<Span> {
+    loop {
+        p.cur.skip_blank();
+        if p.cur.peek() == Some('}') {
+            let close = Span::new(p.cur.pos(), p.cur.pos() + 1);
+            p.cur.bump();
+            return Some(close);
+        }
+        if p.cur.is_eof() {
+            return None;
+        }
+        if matches!(p.cur.peek(), Some('\n' | '\r')) {
+            p.cur.skip_line();
+            continue;
+        }
+        if let Some(comment) = p.comment() {
+            comments.push(comment);
+            continue;
+        }
+        if p.cur.starts_with("<<") {
+            if let Some(name) = annotation(p) {
+                annotations.push(name);
+                p.expect_statement_end();
+            }
+            continue;
+        }
+        let line = p.cur.rest().split('\n').next().unwrap_or_default();
+        let len = line.find('}').unwrap_or(line.len());
+        let start = p.cur.pos();
+        p.cur.set_pos(start + len);
+        let raw = p.cur.slice(Span::new(start, start + len));
+        let text = raw.trim_end();
+        if !text.is_empty() {
+            members.push(member(text, Span::new(start, start + text.len())));
+        }
+        if p.cur.peek() != Some('}') {
+            p.cur.skip_line();
+        }
+    }
+}
+
+/// Interprets one member line. Members are free-form, so this never fails:
+/// anything with parentheses is a method and the rest are attributes.
+fn member(text: &str, span: Span) -> Member {
+    let visibility = match text.chars().next() {
+        Some('+') => Some(Visibility::Public),
+        Some('-') => Some(Visibility::Private),
+        Some('#') => Some(Visibility::Protected),
+        Some('~') => Some(Visibility::Package),
+        _ => None,
+    };
+    let text = if visibility.is_some() {
+        &text[1..]
+    } else {
+        text
+    };
+
+    let classifier_of = |c: Option<char>| match c {
+        Some('*') => Some(Classifier::Abstract),
+        Some('$') => Some(Classifier::Static),
+        _ => None,
+    };
+
+    let (kind, classifier) = match (text.find('('), text.rfind(')')) {
+        (Some(open), Some(close)) if open < close => {
+            let after = &text[close + 1..];
+            let classifier = classifier_of(after.chars().next());
+            let after = if classifier.is_some() {
+                &after[1..]
+            } else {
+                after
+            };
+            let return_type = after.trim();
+            let kind = MemberKind::Method {
+                name: text[..open].trim().to_string(),
+                params: text[open + 1..close].trim().to_string(),
+                return_type: (!return_type.is_empty()).then(|| return_type.to_string()),
+            };
+            (kind, classifier)
+        }
+        _ => {
+            let classifier = classifier_of(text.chars().last());
+            let text = if classifier.is_some() {
+                &text[..text.len() - 1]
+            } else {
+                text
+            };
+            let kind = MemberKind::Attribute {
+                text: text.trim().to_string(),
+            };
+            (kind, classifier)
+        }
+    };
+
+    Member {
+        visibility,
+        kind,
+        classifier,
+        span,
+    }
+}
+
+fn relationship(p: &mut Parser, from: Ident) -> Option<Relationship> {
+    let from_cardinality = cardinality(p);
+    p.cur.skip_blank();
+    let Some(relation) = relation(p) else {
+        p.recover("expected a relationship such as `<|--`, `*--` or `..>`");
+        return None;
+    };
+    p.cur.skip_blank();
+    let to_cardinality = cardinality(p);
+    p.cur.skip_blank();
+    let Some(to) = class_name(p) else {
+        p.recover("expected a class name after the relationship");
+        return None;
+    };
+
+    p.cur.skip_blank();
+    let label = if p.cur.eat_char(':') {
+        p.cur.skip_blank();
+        let (text, span) = p.cur.rest_of_statement();
+        Some(Label {
+            text: text.to_string(),
+            quoted: false,
+            span,
+        })
+    } else {
+        None
+    };
+
+    Some(Relationship {
+        span: from.span.to(Span::new(p.cur.pos(), p.cur.pos())),
+        from,
+        from_cardinality,
+        relation,
+        to_cardinality,
+        to,
+        label,
+    })
+}
+
+fn cardinality(p: &mut Parser) -> Option<Label> {
+    p.cur.skip_blank();
+    let (text, span) = p.cur.quoted()?;
+    Some(Label {
+        text: text.to_string(),
+        quoted: true,
+        span,
+    })
+}
+
+fn relation(p: &mut Parser) -> Option<Relation> {
+    let start = p.cur.pos();
+    let line = |p: &mut Parser| {
+        if p.cur.eat("--") {
+            Some(RelationLine::Solid)
+        } else if p.cur.eat("..") {
+            Some(RelationLine::Dashed)
+        } else {
+            None
+        }
+    };
+
+    // A left head is only one if a line follows, so `o` can still start a
+    // class name elsewhere.
+    let mut left = None;
+    let mut kind = None;
+    for &(token, end) in LEFT_ENDS {
+        if p.cur.eat(token) {
+            if let Some(line) = line(p) {
+                left = Some(end);
+                kind = Some(line);
+                break;
+            }
+            p.cur.set_pos(start);
+        }
+    }
+    let line = match kind {
+        Some(line) => line,
+        None => line(p)?,
+    };
+
+    let right = RIGHT_ENDS.iter().find_map(|&(token, end)| {
+        let at = p.cur.pos();
+        if !p.cur.eat(token) {
+            return None;
+        }
+        // `--oName` is not an aggregation head.
+        if token == "o" && p.cur.peek().is_some_and(super::cursor::is_ident_char) {
+            p.cur.set_pos(at);
+            return None;
+        }
+        Some(end)
+    });
+
+    Some(Relation {
+        left,
+        line,
+        right,
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+fn namespace(p: &mut Parser, keyword: Span) -> Option<Namespace> {
+    p.cur.skip_blank();
+    let Some(name) = p.cur.ident() else {
+        p.recover("expected a namespace name");
+        return None;
+    };
+    p.cur.skip_blank();
+    if !p.cur.eat_char('{') {
+        p.recover("expected `{` after the namespace name");
+        return None;
+    }
+    let open = Span::new(p.cur.pos() - 1, p.cur.pos());
+    p.expect_statement_end();
+    let (statements, close) = statements(p, true);
+    if close.is_none() {
+        let fix = p.append_line_fix("}");
+        p.error_with_fix(open, "unclosed `{`", fix);
+    }
+    Some(Namespace {
+        name,
+        statements,
+        close,
+        span: keyword.to(close.unwrap_or(Span::new(keyword.start, p.cur.pos()))),
+    })
+}
+
+fn note(p: &mut Parser, start: usize) -> Option<Note> {
+    p.cur.skip_blank();
+    let class = if p.cur.eat_keyword("for").is_some() {
+        p.cur.skip_blank();
+        let Some(class) = class_name(p) else {
+            p.recover("expected a class name after `note for`");
+            return None;
+        };
+        p.cur.skip_blank();
+        Some(class)
+    } else {
+        None
+    };
+    let Some((text, span)) = p.cur.quoted() else {
+        p.recover("expected the note text as a `\"...\"` string");
+        return None;
+    };
+    Some(Note {
+        class,
+        text: Label {
+            text: text.to_string(),
+            quoted: true,
+            span,
+        },
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse, Diagram};
+
+    fn statements(source: &str) -> Vec<Statement> {
+        let parsed = parse(source);
+        assert!(parsed.errors.is_empty(), "{source}: {:?}", parsed.errors);
+        match parsed.document.map(|document| document.diagram) {
+            Some(Diagram::Class(diagram)) => diagram.statements,
+            other => panic!("not a class diagram: {other:?}"),
+        }
+    }
+
+    fn relations(source: &str) -> Vec<String> {
+        statements(source)
+            .iter()
+            .map(|statement| match statement {
+                Statement::Relationship(relationship) => relationship.relation.token(),
+                other => panic!("not a relationship: {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn class_bodies() {
+        let statements = statements(
+            "classDiagram\n  class Animal~T~[\"An animal\"]:::pet {\n    <<interface>>\n    +String name\n    -int age$\n    +eat(food)* bool\n    %% comment\n  }\n",
+        );
+        let Statement::Class(class) = &statements[0] else {
+            panic!("not a class");
+        };
+        assert_eq!(class.name.value, "Animal");
+        assert_eq!(class.generic.as_ref().unwrap().value, "T");
+        assert_eq!(class.label.as_ref().unwrap().text, "An animal");
+        assert_eq!(class.classes[0].value, "pet");
+        assert_eq!(class.annotations[0].value, "interface");
+        assert_eq!(class.comments.len(), 1);
+
+        let members: Vec<_> = class
+            .members
+            .iter()
+            .map(|m| (m.visibility, &m.kind, m.classifier))
+            .collect();
+        assert_eq!(
+            members,
+            [
+                (
+                    Some(Visibility::Public),
+                    &MemberKind::Attribute {
+                        text: "String name".into()
+                    },
+                    None
+                ),
+                (
+                    Some(Visibility::Private),
+                    &MemberKind::Attribute {
+                        text: "int age".into()
+                    },
+                    Some(Classifier::Static)
+                ),
+                (
+                    Some(Visibility::Public),
+                    &MemberKind::Method {
+                        name: "eat".into(),
+                        params: "food".into(),
+                        return_type: Some("bool".into()),
+                    },
+                    Some(Classifier::Abstract)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn relationships() {
+        assert_eq!(
+            relations(
+                "classDiagram\n  A <|-- B\n  C *-- D\n  E o-- F\n  G --> H\n  I ..> J\n  K ..|> L\n  M -- N\n  O ()-- P\n  Q --o R\n"
+            ),
+            ["<|--", "*--", "o--", "-->", "..>", "..|>", "--", "()--", "--o"]
+        );
+
+        let statements =
+            statements("classDiagram\n  Customer \"1\" --> \"*\" Ticket : opens\n  A --oval\n");
+        let Statement::Relationship(opens) = &statements[0] else {
+            panic!("not a relationship");
+        };
+        assert_eq!(opens.from_cardinality.as_ref().unwrap().text, "1");
+        assert_eq!(opens.to_cardinality.as_ref().unwrap().text, "*");
+        assert_eq!(opens.label.as_ref().unwrap().text, "opens");
+        let Statement::Relationship(oval) = &statements[1] else {
+            panic!("not a relationship");
+        };
+        assert_eq!(
+            (oval.relation.right, oval.to.value.as_str()),
+            (None, "oval")
+        );
+    }
+
+    #[test]
+    fn other_statements() {
+        let statements = statements(
+            "classDiagram\n  direction RL\n  <<interface>> Shape\n  Animal : +int age\n  List~int~ --> Item\n  note for Duck \"can fly\"\n  note \"general\"\n  style Animal fill:#f9f\n  namespace Shapes {\n    class Square\n  }\n",
+        );
+        assert!(matches!(statements[0], Statement::Direction(_)));
+        let Statement::Annotation(annotation) = &statements[1] else {
+            panic!("not an annotation");
+        };
+        assert_eq!(
+            (
+                annotation.name.value.as_str(),
+                annotation.class.value.as_str()
+            ),
+            ("interface", "Shape")
+        );
+        let Statement::Member(member) = &statements[2] else {
+            panic!("not a member");
+        };
+        assert_eq!(member.class.value, "Animal");
+        let Statement::Relationship(list) = &statements[3] else {
+            panic!("not a relationship");
+        };
+        assert_eq!(list.from.value, "List~int~");
+        let Statement::Note(note) = &statements[4] else {
+            panic!("not a note");
+        };
+        assert_eq!(note.class.as_ref().unwrap().value, "Duck");
+        assert!(matches!(&statements[5], Statement::Note(note) if note.class.is_none()));
+        assert!(matches!(statements[6], Statement::Verbatim(_)));
+        let Statement::Namespace(namespace) = &statements[7] else {
+            panic!("not a namespace");
+        };
+        assert!(namespace.close.is_some());
+        assert!(matches!(namespace.statements[0], Statement::Class(_)));
+    }
+
+    #[test]
+    fn unclosed_blocks() {
+        for source in [
+            "classDiagram\n  class A {\n    +x\n",
+            "classDiagram\n  namespace N {\n    class A\n",
+        ] {
+            let parsed = parse(source);
+            assert_eq!(parsed.errors.len(), 1, "{source}");
+            assert_eq!(parsed.errors[0].message, "unclosed `{`");
+            assert_eq!(parsed.errors[0].fix.as_ref().unwrap().replacement, "}\n");
+        }
+        assert_eq!(
+            parse("classDiagram\n  }\n").errors[0].message,
+            "unmatched `}`"
+        );
+        assert_eq!(
+            parse("classDiagram\n  A ==> B\n").errors[0].message,
+            "expected a relationship such as `<|--`, `*--` or `..>`"
+        );
+    }
+}
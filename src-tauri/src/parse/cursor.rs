@@ -0,0 +1,306 @@
+use super::{Ident, Span, Spanned};
+
+/// Byte cursor over a diagram source. Statements never span lines, so
+/// every helper stops at `\n` and most also at the `;` separator.
+pub(crate) struct Cursor<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.src
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    pub fn slice(&self, span: Span) -> &'a str {
+        &self.src[span.start..span.end]
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.pos >= self.src.len()
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    pub fn peek_second(&self) -> Option<char> {
+        self.rest().chars().nth(1)
+    }
+
+    pub fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    pub fn starts_with(&self, s: &str) -> bool {
+        self.rest().starts_with(s)
+    }
+
+    pub fn eat(&mut self, s: &str) -> bool {
+        if self.starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn eat_char(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes `keyword` only when it is a whole word.
+    pub fn eat_keyword(&mut self, keyword: &str) -> Option<Span> {
+        if !self.starts_with(keyword) {
+            return None;
+        }
+        let after = self.rest()[keyword.len()..].chars().next();
+        if after.is_some_and(is_ident_char) {
+            return None;
+        }
+        let start = self.pos;
+        self.pos += keyword.len();
+        Some(Span::new(start, self.pos))
+    }
+
+    /// Skips spaces and tabs, never newlines.
+    pub fn skip_blank(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// True at end of input, end of line, a `;` separator or a `%%`
+    /// comment trailing the statement.
+    pub fn at_statement_end(&self) -> bool {
+        matches!(self.peek(), None | Some('\n' | '\r' | ';')) || self.starts_with("%%")
+    }
+
+    /// Moves past the next newline.
+    pub fn skip_line(&mut self) {
+        match self.rest().find('\n') {
+            Some(i) => self.pos += i + 1,
+            None => self.pos = self.src.len(),
+        }
+    }
+
+    /// Skips blank lines and `;`, leaving the cursor on the next line with
+    /// content (or at a `%%` comment).
+    pub fn skip_empty(&mut self) {
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                Some('\n' | '\r' | ';') => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Reads an identifier made of letters, digits, `_`, and `-` or `.`
+    /// when they are followed by another identifier character (so `A-->B`
+    /// stops at `A`).
+    pub fn ident(&mut self) -> Option<Ident> {
+        let start = self.pos;
+        let mut chars = self.rest().char_indices().peekable();
+        let mut end = 0;
+        while let Some((i, c)) = chars.next() {
+            let next = chars.peek().map(|&(_, c)| c);
+            let ok = is_ident_char(c)
+                || (matches!(c, '-' | '.') && i > 0 && next.is_some_and(is_ident_char));
+            if !ok {
+                break;
+            }
+            end = i + c.len_utf8();
+        }
+        if end == 0 {
+            return None;
+        }
+        self.pos += end;
+        Some(Spanned::new(
+            self.src[start..self.pos].to_string(),
+            Span::new(start, self.pos),
+        ))
+    }
+
+    /// Reads a run of non-blank characters on the current line.
+    pub fn word(&mut self) -> Option<Ident> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && c != ';') {
+            self.bump();
+        }
+        (self.pos > start).then(|| {
+            Spanned::new(
+                self.src[start..self.pos].to_string(),
+                Span::new(start, self.pos),
+            )
+        })
+    }
+
+    /// Reads a `"..."` string, returning its contents and the span of the
+    /// whole literal including quotes.
+    pub fn quoted(&mut self) -> Option<(&'a str, Span)> {
+        let start = self.pos;
+        if !self.eat_char('"') {
+            return None;
+        }
+        let body = self.pos;
+        let end = self.rest().find(['"', '\n']);
+        let Some(end) = end.filter(|&end| !self.rest()[end..].starts_with('\n')) else {
+            self.pos = start;
+            return None;
+        };
+        self.pos += end + 1;
+        Some((&self.src[body..body + end], Span::new(start, self.pos)))
+    }
+
+    /// Consumes text up to (not including) `pat` on the current line.
+    pub fn take_until(&mut self, pat: &str) -> Option<(&'a str, Span)> {
+        let line = self.rest().split('\n').next().unwrap_or_default();
+        let end = line.find(pat)?;
+        let start = self.pos;
+        self.pos += end;
+        Some((&self.src[start..self.pos], Span::new(start, self.pos)))
+    }
+
+    /// Consumes the rest of the statement (up to `;` or newline) and returns
+    /// it trimmed, with its span.
+    pub fn rest_of_statement(&mut self) -> (&'a str, Span) {
+        let line = self.rest().split(['\n', ';']).next().unwrap_or_default();
+        self.rest_of(line.len())
+    }
+
+    /// [`Cursor::rest_of_statement`] without a trailing `%%` comment. A `%%`
+    /// inside double quotes is text.
+    pub fn rest_before_comment(&mut self) -> (&'a str, Span) {
+        let rest = self.rest();
+        let mut quoted = false;
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| {
+                quoted ^= c == '"';
+                matches!(c, '\n' | ';') || (!quoted && rest[i..].starts_with("%%"))
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        self.rest_of(len)
+    }
+
+    /// Consumes the rest of the line, ignoring `;`, and returns it trimmed.
+    pub fn rest_of_line(&mut self) -> (&'a str, Span) {
+        let line = self.rest().split('\n').next().unwrap_or_default();
+        self.rest_of(line.len())
+    }
+
+    fn rest_of(&mut self, len: usize) -> (&'a str, Span) {
+        let raw = &self.src[self.pos..self.pos + len];
+        let trimmed = raw.trim();
+        let start = self.pos + (raw.len() - raw.trim_start().len());
+        self.pos += len;
+        (trimmed, Span::new(start, start + trimmed.len()))
+    }
+
+    /// Span of the current token for error reporting: the next run of
+    /// non-blank characters, or a zero-width span at end of line.
+    pub fn error_span(&self) -> Span {
+        let len = self
+            .rest()
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest().len());
+        Span::new(self.pos, self.pos + len)
+    }
+}
+
+pub(crate) fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        let idents = |source: &str| {
+            let mut cur = Cursor::new(source);
+            let ident = cur.ident().map(|ident| ident.value);
+            (ident, cur.rest().to_string())
+        };
+        assert_eq!(idents("A-->B"), (Some("A".into()), "-->B".into()));
+        assert_eq!(
+            idents("Web-Server->>DB"),
+            (Some("Web-Server".into()), "->>DB".into())
+        );
+        assert_eq!(idents("a.b.c;"), (Some("a.b.c".into()), ";".into()));
+        assert_eq!(idents("Zürich "), (Some("Zürich".into()), " ".into()));
+        assert_eq!(idents("-x"), (None, "-x".into()));
+    }
+
+    #[test]
+    fn keywords_are_whole_words() {
+        let mut cur = Cursor::new("endpoint");
+        assert_eq!(cur.eat_keyword("end"), None);
+        assert_eq!(cur.pos(), 0);
+        let mut cur = Cursor::new("end;");
+        assert_eq!(cur.eat_keyword("end"), Some(Span::new(0, 3)));
+    }
+
+    #[test]
+    fn strings_stay_on_their_line() {
+        let mut cur = Cursor::new("\"a b\" rest");
+        assert_eq!(cur.quoted(), Some(("a b", Span::new(0, 5))));
+        for source in ["\"open\nnext\"", "\"open"] {
+            let mut cur = Cursor::new(source);
+            assert_eq!(cur.quoted(), None, "{source}");
+            assert_eq!(cur.pos(), 0, "{source}");
+        }
+    }
+
+    #[test]
+    fn statements_and_lines() {
+        let mut cur = Cursor::new("  a b ; c\n\n ;\n  d");
+        assert_eq!(cur.rest_of_statement(), ("a b", Span::new(2, 5)));
+        assert!(cur.at_statement_end());
+        cur.eat_char(';');
+        assert_eq!(cur.rest_of_line(), ("c", Span::new(8, 9)));
+        cur.skip_empty();
+        assert_eq!(cur.rest(), "d");
+        assert_eq!(cur.take_until("x"), None);
+    }
+
+    #[test]
+    fn trailing_comments() {
+        let mut cur = Cursor::new(r#"a "b %% c" d %% e; f"#);
+        assert_eq!(
+            cur.rest_before_comment(),
+            (r#"a "b %% c" d"#, Span::new(0, 12))
+        );
+        assert!(cur.at_statement_end());
+        assert_eq!(cur.rest_of_line(), ("%% e; f", Span::new(13, 20)));
+        let mut cur = Cursor::new("a;b");
+        assert_eq!(cur.rest_before_comment(), ("a", Span::new(0, 1)));
+    }
+}
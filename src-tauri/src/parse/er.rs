@@ -0,0 +1,542 @@
+//! `erDiagram` diagrams.
+
+use serde::Serialize;
+
+use super::{Comment, Direction, Ident, Label, Parser, Span, Spanned};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErDiagram {
+    pub keyword: Ident,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Statement {
+    Entity(Entity),
+    Relationship(Relationship),
+    Direction(Spanned<Direction>),
+    Comment(Comment),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Entity(entity) => entity.span,
+            Statement::Relationship(relationship) => relationship.span,
+            Statement::Direction(direction) => direction.span,
+            Statement::Comment(comment) => comment.span,
+        }
+    }
+}
+
+/// `CUSTOMER["Customer account"] { ... }`, or an entity named on its own
+/// line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entity {
+    pub name: Ident,
+    pub alias: Option<Label>,
+    pub attributes: Vec<Attribute>,
+    /// Comment lines inside the body.
+    pub comments: Vec<Comment>,
+    /// The `{ }` body, `None` for a bare declaration.
+    pub body: Option<Span>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Key {
+    /// `PK`
+    Primary,
+    /// `FK`
+    Foreign,
+    /// `UK`
+    Unique,
+}
+
+/// `int customer_id FK "references CUSTOMER"`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attribute {
+    #[serde(rename = "type")]
+    pub ty: Ident,
+    pub name: Ident,
+    pub keys: Vec<Spanned<Key>>,
+    pub comment: Option<Label>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Cardinality {
+    ZeroOrOne,
+    ExactlyOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+/// Cardinality markers on the left of the line.
+const LEFT_CARDINALITIES: &[(&str, Cardinality)] = &[
+    ("|o", Cardinality::ZeroOrOne),
+    ("||", Cardinality::ExactlyOne),
+    ("}o", Cardinality::ZeroOrMore),
+    ("}|", Cardinality::OneOrMore),
+];
+
+/// Cardinality markers on the right of the line, mirrored.
+const RIGHT_CARDINALITIES: &[(&str, Cardinality)] = &[
+    ("o|", Cardinality::ZeroOrOne),
+    ("||", Cardinality::ExactlyOne),
+    ("o{", Cardinality::ZeroOrMore),
+    ("|{", Cardinality::OneOrMore),
+];
+
+/// `CUSTOMER ||--o{ ORDER : places`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relationship {
+    pub from: Ident,
+    pub from_cardinality: Spanned<Cardinality>,
+    /// `--` (identifying) rather than `..` (non-identifying).
+    pub identifying: bool,
+    pub to_cardinality: Spanned<Cardinality>,
+    pub to: Ident,
+    pub label: Label,
+    pub span: Span,
+}
+
+impl Relationship {
+    /// The relationship token as written canonically, e.g. `||--o{`.
+    pub fn token(&self) -> String {
+        let find = |table: &[(&'static str, Cardinality)], c: Cardinality| {
+            table.iter().find(|(_, k)| *k == c).unwrap().0
+        };
+        let line = if self.identifying { "--" } else { ".." };
+        format!(
+            "{}{line}{}",
+            find(LEFT_CARDINALITIES, self.from_cardinality.value),
+            find(RIGHT_CARDINALITIES, self.to_cardinality.value)
+        )
+    }
+}
+
+pub(super) fn parse(p: &mut Parser, keyword: Ident) -> ErDiagram {
+    p.expect_statement_end();
+    let mut statements = Vec::new();
+    loop {
+        p.cur.skip_empty();
+        if p.cur.is_eof() {
+            break;
+        }
+        if let Some(comment) = p.comment() {
+            statements.push(Statement::Comment(comment));
+            continue;
+        }
+        if let Some(statement) = statement(p) {
+            statements.push(statement);
+        }
+    }
+    ErDiagram {
+        keyword,
+        statements,
+    }
+}
+
+fn statement(p: &mut Parser) -> Option<Statement> {
+    let start = p.cur.pos();
+    if p.cur.eat_keyword("direction").is_some() {
+        p.cur.skip_blank();
+        let direction = p.direction()?;
+        p.expect_statement_end();
+        return Some(Statement::Direction(direction));
+    }
+
+    let Some(name) = entity_name(p) else {
+        p.recover("expected an entity or relationship");
+        return None;
+    };
+
+    let mut alias = None;
+    p.cur.skip_blank();
+    if p.cur.eat_char('[') {
+        let Some(text) = p.label_until("]") else {
+            p.recover("entity alias is never closed with `]`");
+            return None;
+        };
+        p.cur.eat_char(']');
+        alias = Some(text);
+    }
+
+    p.cur.skip_blank();
+    if p.cur.peek() != Some('{') && !p.cur.at_statement_end() {
+        if alias.is_some() {
+            p.recover("relationships name entities without their alias");
+            return None;
+        }
+        let relationship = relationship(p, name)?;
+        p.expect_statement_end();
+        return Some(Statement::Relationship(relationship));
+    }
+
+    let mut attributes = Vec::new();
+    let mut comments = Vec::new();
+    let mut body = None;
+    if p.cur.peek() == Some('{') {
+        let open = p.cur.pos();
+        p.cur.bump();
+        match entity_body(p, &mut attributes, &mut comments) {
+            Some(close) => body = Some(Span::new(open, close.end)),
+            None => {
+                let fix = p.append_line_fix("}");
+                p.error_with_fix(Span::new(open, open + 1), "unclosed `{`", fix);
+                body = Some(Span::new(open, p.cur.pos()));
+            }
+        }
+    }
+    let end = body.map_or(p.cur.pos(), |b| b.end);
+    p.expect_statement_end();
+    Some(Statement::Entity(Entity {
+        name,
+        alias,
+        attributes,
+        comments,
+        body,
+        span: Span::new(start, end),
+    }))
+}
+
+/// Entity names are identifiers or `"quoted names"`.
+fn entity_name(p: &mut Parser) -> Option<Ident> {
+    if let Some((text, span)) = p.cur.quoted() {
+        return Some(Spanned::new(text.to_string(), span));
+    }
+    p.cur.ident()
+}
+
+/// Parses attribute lines up to the closing `}` and returns its span.
+fn entity_body(
+    p: &mut Parser,
+    attributes: &mut Vec<Attribute>,
+    comments: &mut Vec<Comment>,
+) -> Option<Span> {
+    loop {
+        p.cur.skip_blank();
+        if p.cur.peek() == Some('}') {
+            let close = Span::new(p.cur.pos(), p.cur.pos() + 1);
+            p.cur.bump();
+            return Some(close);
+        }
+        if p.cur.is_eof() {
+            return None;
+        }
+        if matches!(p.cur.peek(), Some('\n' | '\r')) {
+            p.cur.skip_line();
+            continue;
+        }
+        if let Some(comment) = p.comment() {
+            comments.push(comment);
+            continue;
+        }
+        if let Some(attribute) = attribute(p) {
+            attributes.push(attribute);
+        }
+    }
+}
+
+/// `type name [PK|FK|UK, ...] ["comment"]`, ending at a newline or `}`.
+fn attribute(p: &mut Parser) -> Option<Attribute> {
+    let start = p.cur.pos();
+    let Some(ty) = attribute_word(p) else {
+        p.recover("expected an attribute type");
+        return None;
+    };
+    p.cur.skip_blank();
+    let Some(name) = attribute_word(p) else {
+        p.recover("expected an attribute name after its type");
+        return None;
+    };
+
+    let mut keys = Vec::new();
+    let mut comment = None;
+    loop {
+        p.cur.skip_blank();
+        if matches!(p.cur.peek(), None | Some('\n' | '\r' | '}')) {
+            break;
+        }
+        if let Some((text, span)) = p.cur.quoted() {
+            comment = Some(Label {
+                text: text.to_string(),
+                quoted: true,
+                span,
+            });
+            continue;
+        }
+        if p.cur.eat_char(',') {
+            continue;
+        }
+        let Some(word) = attribute_word(p) else {
+            p.recover("unexpected token in attribute");
+            return None;
+        };
+        let key = match word.value.as_str() {
+            "PK" => Key::Primary,
+            "FK" => Key::Foreign,
+            "UK" => Key::Unique,
+            other => {
+                let message = format!("unknown key `{other}`, expected PK, FK or UK");
+                let upper = other.to_ascii_uppercase();
+                if matches!(upper.as_str(), "PK" | "FK" | "UK") {
+                    let fix = p.replace_fix(word.span, &upper);
+                    p.error_with_fix(word.span, message, fix);
+                } else {
+                    p.error(word.span, message);
+                }
+                p.cur.skip_line();
+                return None;
+            }
+        };
+        keys.push(Spanned::new(key, word.span));
+    }
+
+    Some(Attribute {
+        ty,
+        name,
+        keys,
+        comment,
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+/// Reads a run of characters that can appear in an attribute type or name,
+/// such as `varchar(255)` or `string[]`.
+fn attribute_word(p: &mut Parser) -> Option<Ident> {
+    let start = p.cur.pos();
+    while p
+        .cur
+        .peek()
+        .is_some_and(|c| !c.is_whitespace() && !matches!(c, ',' | '"' | '}'))
+    {
+        p.cur.bump();
+    }
+    let span = Span::new(start, p.cur.pos());
+    (span.end > start).then(|| Spanned::new(p.cur.slice(span).to_string(), span))
+}
+
+fn relationship(p: &mut Parser, from: Ident) -> Option<Relationship> {
+    let token_start = p.cur.pos();
+    let marker = |p: &mut Parser, table: &[(&str, Cardinality)]| {
+        let at = p.cur.pos();
+        let &(token, cardinality) = table.iter().find(|(t, _)| p.cur.starts_with(t))?;
+        p.cur.eat(token);
+        Some(Spanned::new(cardinality, Span::new(at, p.cur.pos())))
+    };
+
+    let from_cardinality = marker(p, LEFT_CARDINALITIES);
+    let identifying = if p.cur.eat("--") {
+        Some(true)
+    } else if p.cur.eat("..") {
+        Some(false)
+    } else {
+        None
+    };
+    let to_cardinality = marker(p, RIGHT_CARDINALITIES);
+    let (Some(from_cardinality), Some(identifying), Some(to_cardinality)) =
+        (from_cardinality, identifying, to_cardinality)
+    else {
+        p.cur.set_pos(token_start);
+        p.recover("expected a relationship such as `||--o{` or `}|..|{`");
+        return None;
+    };
+
+    p.cur.skip_blank();
+    let Some(to) = entity_name(p) else {
+        p.recover("expected an entity after the relationship");
+        return None;
+    };
+
+    p.cur.skip_blank();
+    if !p.cur.eat_char(':') {
+        p.recover("expected `:` followed by the relationship label");
+        return None;
+    }
+    p.cur.skip_blank();
+    let label = match p.cur.quoted() {
+        Some((text, span)) => Label {
+            text: text.to_string(),
+            quoted: true,
+            span,
+        },
+        None => {
+            let (text, span) = p.cur.rest_of_statement();
+            Label {
+                text: text.to_string(),
+                quoted: false,
+                span,
+            }
+        }
+    };
+    if label.text.is_empty() && !label.quoted {
+        p.error(label.span, "expected a relationship label after `:`");
+    }
+
+    Some(Relationship {
+        span: from.span.to(label.span),
+        from,
+        from_cardinality,
+        identifying,
+        to_cardinality,
+        to,
+        label,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse, Diagram};
+
+    fn statements(source: &str) -> Vec<Statement> {
+        let parsed = parse(source);
+        assert!(parsed.errors.is_empty(), "{source}: {:?}", parsed.errors);
+        match parsed.document.map(|document| document.diagram) {
+            Some(Diagram::Er(diagram)) => diagram.statements,
+            other => panic!("not an ER diagram: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn relationships() {
+        let statements = statements(
+            "erDiagram\n  CUSTOMER ||--o{ ORDER : places\n  ORDER |o..|{ \"LINE ITEM\" : \"contains\"\n  A }o--o| B : x\n  C }|..|| D : y\n",
+        );
+        let relationships: Vec<_> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Relationship(r) => {
+                    (r.token(), r.to.value.as_str(), r.label.text.as_str())
+                }
+                other => panic!("not a relationship: {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            relationships,
+            [
+                ("||--o{".to_string(), "ORDER", "places"),
+                ("|o..|{".to_string(), "LINE ITEM", "contains"),
+                ("}o--o|".to_string(), "B", "x"),
+                ("}|..||".to_string(), "D", "y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn entities() {
+        let statements = statements(
+            "erDiagram\n  CUSTOMER[\"Customer account\"] {\n    string name PK\n    int order_id FK, UK \"references ORDER\"\n    varchar(255) email\n    %% comment\n  }\n  PRODUCT\n",
+        );
+        let Statement::Entity(customer) = &statements[0] else {
+            panic!("not an entity");
+        };
+        assert_eq!(customer.alias.as_ref().unwrap().text, "Customer account");
+        assert_eq!(customer.comments.len(), 1);
+        let attributes: Vec<_> = customer
+            .attributes
+            .iter()
+            .map(|a| {
+                (
+                    a.ty.value.as_str(),
+                    a.name.value.as_str(),
+                    a.keys.iter().map(|k| k.value).collect::<Vec<_>>(),
+                    a.comment.as_ref().map(|c| c.text.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                ("string", "name", vec![Key::Primary], None),
+                (
+                    "int",
+                    "order_id",
+                    vec![Key::Foreign, Key::Unique],
+                    Some("references ORDER")
+                ),
+                ("varchar(255)", "email", vec![], None),
+            ]
+        );
+        let Statement::Entity(product) = &statements[1] else {
+            panic!("not an entity");
+        };
+        assert!(product.body.is_none());
+    }
+
+    #[test]
+    fn aliases() {
+        let statements = statements(concat!(
+            "erDiagram\n",
+            "  CUSTOMER[\"Customer\"]\n",
+            "  ORDER [\"Order [draft]\"] %% spaced\n",
+            "  LINE-ITEM[Item]{\n    int qty\n  }\n",
+            "  CUSTOMER ||--o{ ORDER : places\n",
+        ));
+        let entities: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Entity(entity) => Some((
+                    entity.name.value.as_str(),
+                    entity.alias.as_ref().map(|alias| alias.text.as_str()),
+                    entity.attributes.len(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            entities,
+            [
+                ("CUSTOMER", Some("Customer"), 0),
+                ("ORDER", Some("Order [draft]"), 0),
+                ("LINE-ITEM", Some("Item"), 1),
+            ]
+        );
+        assert!(matches!(
+            statements.last(),
+            Some(Statement::Relationship(_))
+        ));
+
+        let parsed = parse("erDiagram\n  CUSTOMER[\"Customer\"] ||--o{ ORDER : places\n");
+        assert_eq!(
+            parsed.errors[0].message,
+            "relationships name entities without their alias"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let parsed = parse("erDiagram\n  A {\n    int id pk\n  }\n");
+        assert_eq!(
+            parsed.errors[0].message,
+            "unknown key `pk`, expected PK, FK or UK"
+        );
+        assert_eq!(parsed.errors[0].fix.as_ref().unwrap().replacement, "PK");
+
+        let parsed = parse("erDiagram\n  A {\n    int id\n");
+        assert_eq!(parsed.errors[0].message, "unclosed `{`");
+
+        for (source, message) in [
+            (
+                "erDiagram\n  A --> B : x\n",
+                "expected a relationship such as `||--o{` or `}|..|{`",
+            ),
+            (
+                "erDiagram\n  A ||--o{ B\n",
+                "expected `:` followed by the relationship label",
+            ),
+            (
+                "erDiagram\n  A ||--o{ B :\n",
+                "expected a relationship label after `:`",
+            ),
+        ] {
+            assert_eq!(parse(source).errors[0].message, message, "{source}");
+        }
+    }
+}
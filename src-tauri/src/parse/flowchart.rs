@@ -0,0 +1,899 @@
+//! `graph` / `flowchart` diagrams.
+
+use serde::Serialize;
+
+use super::{Comment, Direction, Ident, Label, Parser, Span, Spanned};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Flowchart {
+    /// `graph` or `flowchart`.
+    pub keyword: Ident,
+    pub direction: Option<Spanned<Direction>>,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Statement {
+    /// Nodes joined by links, e.g. `A --> B & C -.-> D`. A lone node
+    /// declaration is a chain with no links.
+    Chain(Chain),
+    Subgraph(Subgraph),
+    Direction(Spanned<Direction>),
+    ClassDef(ClassDef),
+    Class(ClassAssign),
+    Style(Style),
+    LinkStyle(LinkStyle),
+    Click(Click),
+    Comment(Comment),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Chain(chain) => chain.span,
+            Statement::Subgraph(subgraph) => subgraph.span,
+            Statement::Direction(direction) => direction.span,
+            Statement::ClassDef(class_def) => class_def.span,
+            Statement::Class(class) => class.span,
+            Statement::Style(style) => style.span,
+            Statement::LinkStyle(link_style) => link_style.span,
+            Statement::Click(click) => click.span,
+            Statement::Comment(comment) => comment.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Chain {
+    /// Groups of nodes joined with `&`; there is always one more group
+    /// than there are links.
+    pub groups: Vec<Vec<Node>>,
+    pub links: Vec<Link>,
+    pub span: Span,
+}
+
+impl Chain {
+    /// Every `(from, link, to)` edge the chain expands to.
+    pub fn edges(&self) -> impl Iterator<Item = (&Node, &Link, &Node)> {
+        self.links.iter().enumerate().flat_map(move |(i, link)| {
+            self.groups[i]
+                .iter()
+                .flat_map(move |from| self.groups[i + 1].iter().map(move |to| (from, link, to)))
+        })
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.groups.iter().flatten()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Node {
+    pub id: Ident,
+    /// Shape and label are either both present or both absent.
+    pub shape: Option<Shape>,
+    pub label: Option<Label>,
+    /// Classes attached with `:::name`.
+    pub classes: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Shape {
+    Rect,
+    Round,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    DoubleCircle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+    Parallelogram,
+    ParallelogramAlt,
+    Trapezoid,
+    TrapezoidAlt,
+}
+
+/// Opening and closing delimiters, ordered so longer openers are tried first.
+const SHAPES: &[(&str, &str, Shape)] = &[
+    ("(((", ")))", Shape::DoubleCircle),
+    ("((", "))", Shape::Circle),
+    ("([", "])", Shape::Stadium),
+    ("(", ")", Shape::Round),
+    ("[[", "]]", Shape::Subroutine),
+    ("[(", ")]", Shape::Cylinder),
+    ("[/", "/]", Shape::Parallelogram),
+    ("[/", "\\]", Shape::Trapezoid),
+    ("[\\", "\\]", Shape::ParallelogramAlt),
+    ("[\\", "/]", Shape::TrapezoidAlt),
+    ("[", "]", Shape::Rect),
+    ("{{", "}}", Shape::Hexagon),
+    ("{", "}", Shape::Rhombus),
+    (">", "]", Shape::Asymmetric),
+];
+
+impl Shape {
+    pub fn delimiters(self) -> (&'static str, &'static str) {
+        SHAPES
+            .iter()
+            .find(|(_, _, shape)| *shape == self)
+            .map(|&(open, close, _)| (open, close))
+            .unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stroke {
+    /// `---`
+    Normal,
+    /// `===`
+    Thick,
+    /// `-.-`
+    Dotted,
+    /// `~~~`
+    Invisible,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArrowHead {
+    /// `>` (or `<` at the start)
+    Arrow,
+    /// `o`
+    Circle,
+    /// `x`
+    Cross,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Link {
+    pub stroke: Stroke,
+    pub start: Option<ArrowHead>,
+    pub end: Option<ArrowHead>,
+    /// Minimum rank distance; `-->` is 1, `--->` is 2.
+    pub length: usize,
+    /// Text from `-->|label|` or `-- label -->`.
+    pub label: Option<Label>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Subgraph {
+    pub id: Option<Ident>,
+    pub title: Option<Label>,
+    pub statements: Vec<Statement>,
+    /// The closing `end`, missing when the subgraph is unterminated.
+    pub end: Option<Span>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StyleProp {
+    pub key: String,
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassDef {
+    pub names: Vec<Ident>,
+    pub styles: Vec<StyleProp>,
+    pub span: Span,
+}
+
+/// `class A,B className`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassAssign {
+    pub nodes: Vec<Ident>,
+    pub class: Ident,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Style {
+    pub node: Ident,
+    pub styles: Vec<StyleProp>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkTargets {
+    Default,
+    Indices(Vec<Spanned<usize>>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkStyle {
+    pub targets: LinkTargets,
+    pub styles: Vec<StyleProp>,
+    pub span: Span,
+}
+
+/// `click A callback "tooltip"`; everything after the node is kept verbatim.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Click {
+    pub node: Ident,
+    pub action: Label,
+    pub span: Span,
+}
+
+pub(super) fn parse(p: &mut Parser, keyword: Ident) -> Flowchart {
+    p.cur.skip_blank();
+    let direction = if p.cur.at_statement_end() {
+        None
+    } else {
+        p.direction()
+    };
+    if direction.is_some() {
+        p.expect_statement_end();
+    }
+
+    let (statements, _) = statements(p, false);
+    Flowchart {
+        keyword,
+        direction,
+        statements,
+    }
+}
+
+/// Parses statements until EOF or, inside a subgraph, the matching `end`.
+fn statements(p: &mut Parser, in_subgraph: bool) -> (Vec<Statement>, Option<Span>) {
+    let mut statements = Vec::new();
+    loop {
+        p.cur.skip_empty();
+        if p.cur.is_eof() {
+            return (statements, None);
+        }
+        if let Some(comment) = p.comment() {
+            statements.push(Statement::Comment(comment));
+            continue;
+        }
+        if let Some(end) = p.cur.eat_keyword("end") {
+            p.expect_statement_end();
+            if in_subgraph {
+                return (statements, Some(end));
+            }
+            p.error(end, "`end` without a matching `subgraph`");
+            continue;
+        }
+        if let Some(statement) = statement(p) {
+            statements.push(statement);
+        }
+    }
+}
+
+fn statement(p: &mut Parser) -> Option<Statement> {
+    let start = p.cur.pos();
+    if let Some(keyword) = p.cur.eat_keyword("subgraph") {
+        return subgraph(p, keyword).map(Statement::Subgraph);
+    }
+    let statement = if p.cur.eat_keyword("direction").is_some() {
+        p.cur.skip_blank();
+        Statement::Direction(p.direction()?)
+    } else if p.cur.eat_keyword("classDef").is_some() {
+        let names = ident_list(p, "class name")?;
+        let styles = style_props(p)?;
+        let span = Span::new(start, p.cur.pos());
+        Statement::ClassDef(ClassDef {
+            names,
+            styles,
+            span,
+        })
+    } else if p.cur.eat_keyword("class").is_some() {
+        let nodes = ident_list(p, "node id")?;
+        p.cur.skip_blank();
+        let Some(class) = p.cur.ident() else {
+            p.recover("expected a class name");
+            return None;
+        };
+        let span = Span::new(start, p.cur.pos());
+        Statement::Class(ClassAssign { nodes, class, span })
+    } else if p.cur.eat_keyword("style").is_some() {
+        p.cur.skip_blank();
+        let Some(node) = p.cur.ident() else {
+            p.recover("expected a node id");
+            return None;
+        };
+        let styles = style_props(p)?;
+        let span = Span::new(start, p.cur.pos());
+        Statement::Style(Style { node, styles, span })
+    } else if p.cur.eat_keyword("linkStyle").is_some() {
+        let targets = link_targets(p)?;
+        let styles = style_props(p)?;
+        let span = Span::new(start, p.cur.pos());
+        Statement::LinkStyle(LinkStyle {
+            targets,
+            styles,
+            span,
+        })
+    } else if p.cur.eat_keyword("click").is_some() {
+        p.cur.skip_blank();
+        let Some(node) = p.cur.ident() else {
+            p.recover("expected a node id");
+            return None;
+        };
+        let (text, span) = p.cur.rest_before_comment();
+        let action = Label {
+            text: text.to_string(),
+            quoted: false,
+            span,
+        };
+        let span = Span::new(start, p.cur.pos());
+        Statement::Click(Click { node, action, span })
+    } else {
+        Statement::Chain(chain(p)?)
+    };
+    p.expect_statement_end();
+    Some(statement)
+}
+
+fn subgraph(p: &mut Parser, keyword: Span) -> Option<Subgraph> {
+    p.cur.skip_blank();
+    let (id, title) = if let Some((text, span)) = p.cur.quoted() {
+        let title = Label {
+            text: text.to_string(),
+            quoted: true,
+            span,
+        };
+        (None, Some(title))
+    } else {
+        let start = p.cur.pos();
+        let id = p.cur.ident();
+        p.cur.skip_blank();
+        if id.is_some() && p.cur.eat_char('[') {
+            let Some(title) = p.label_until("]") else {
+                p.recover("unclosed `[` in subgraph title");
+                return None;
+            };
+            p.cur.eat_char(']');
+            (id, Some(title))
+        } else if p.cur.at_statement_end() {
+            (id, None)
+        } else {
+            // `subgraph Some title` uses the whole text as the title.
+            p.cur.set_pos(start);
+            let (text, span) = p.cur.rest_before_comment();
+            let title = Label {
+                text: text.to_string(),
+                quoted: false,
+                span,
+            };
+            (None, Some(title))
+        }
+    };
+    p.expect_statement_end();
+
+    let (statements, end) = statements(p, true);
+    if end.is_none() {
+        let fix = p.append_line_fix("end");
+        p.error_with_fix(keyword, "`subgraph` is never closed with `end`", fix);
+    }
+    let span = keyword.to(end.unwrap_or(Span::new(keyword.start, p.cur.pos())));
+    Some(Subgraph {
+        id,
+        title,
+        statements,
+        end,
+        span,
+    })
+}
+
+fn chain(p: &mut Parser) -> Option<Chain> {
+    let start = p.cur.pos();
+    let mut groups = vec![node_group(p)?];
+    let mut links = Vec::new();
+    loop {
+        p.cur.skip_blank();
+        if p.cur.at_statement_end() {
+            break;
+        }
+        let Ok(link) = link(p) else {
+            return None;
+        };
+        let Some(link) = link else {
+            let token = p.cur.slice(p.cur.error_span()).to_string();
+            p.recover(format!("expected a link such as `-->`, found `{token}`"));
+            return None;
+        };
+        links.push(link);
+        p.cur.skip_blank();
+        groups.push(node_group(p)?);
+    }
+    Some(Chain {
+        groups,
+        links,
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+fn node_group(p: &mut Parser) -> Option<Vec<Node>> {
+    let mut nodes = vec![node(p)?];
+    loop {
+        let before = p.cur.pos();
+        p.cur.skip_blank();
+        if !p.cur.eat_char('&') {
+            p.cur.set_pos(before);
+            return Some(nodes);
+        }
+        p.cur.skip_blank();
+        nodes.push(node(p)?);
+    }
+}
+
+fn node(p: &mut Parser) -> Option<Node> {
+    let Some(id) = p.cur.ident() else {
+        p.recover("expected a node id");
+        return None;
+    };
+    let start = id.span.start;
+
+    let (shape, label) = match shape(p) {
+        Ok(Some((shape, label))) => (Some(shape), Some(label)),
+        Ok(None) => (None, None),
+        Err(()) => return None,
+    };
+
+    let mut classes = Vec::new();
+    while p.cur.eat(":::") {
+        let Some(class) = p.cur.ident() else {
+            p.recover("expected a class name after `:::`");
+            return None;
+        };
+        classes.push(class);
+    }
+
+    Some(Node {
+        id,
+        shape,
+        label,
+        classes,
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+/// Parses a shaped label directly after a node id. `Err` means an error was
+/// reported and the line skipped.
+fn shape(p: &mut Parser) -> Result<Option<(Shape, Label)>, ()> {
+    let line = p.cur.rest().split('\n').next().unwrap_or_default();
+    let mut openers = SHAPES
+        .iter()
+        .map(|&(open, _, _)| open)
+        .filter(|open| line.starts_with(open))
+        .peekable();
+    let Some(&first) = openers.peek() else {
+        return Ok(None);
+    };
+
+    // Try the longest opener first, falling back to shorter ones so that
+    // `A[/path]` is a rectangle. Openers like `[/` have several closers;
+    // the nearest one wins.
+    let mut openers: Vec<&str> = openers.collect();
+    openers.dedup();
+    for open in openers {
+        let body = &line[open.len()..];
+        let from = match body.trim_start().strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(i) => body.len() - quoted.len() + i + 1,
+                None => continue,
+            },
+            None => 0,
+        };
+        let found = SHAPES
+            .iter()
+            .filter(|(o, _, _)| *o == open)
+            .filter_map(|&(_, close, shape)| body[from..].find(close).map(|at| (at, close, shape)))
+            .min_by_key(|&(at, _, _)| at);
+        let Some((_, close, shape)) = found else {
+            continue;
+        };
+
+        p.cur.eat(open);
+        let Some(label) = p.label_until(close) else {
+            p.recover(format!("expected `{close}`"));
+            return Err(());
+        };
+        p.cur.skip_blank();
+        p.cur.eat(close);
+        return Ok(Some((shape, label)));
+    }
+
+    let start = p.cur.pos();
+    p.error(
+        Span::new(start, start + first.len()),
+        format!("unclosed `{first}`"),
+    );
+    p.cur.skip_line();
+    Err(())
+}
+
+/// Parses a link at the cursor. `Ok(None)` means there is no link here and
+/// nothing was consumed; `Err` means an error was reported.
+fn link(p: &mut Parser) -> Result<Option<Link>, ()> {
+    let start = p.cur.pos();
+
+    if p.cur.starts_with("~~~") {
+        let length = run(p, '~') - 2;
+        return pipe_label(
+            p,
+            Link {
+                stroke: Stroke::Invisible,
+                start: None,
+                end: None,
+                length,
+                label: None,
+                span: Span::new(start, p.cur.pos()),
+            },
+        )
+        .map(Some);
+    }
+
+    let head_start = match (p.cur.peek(), p.cur.peek_second()) {
+        (Some('<'), Some('-' | '=')) => Some(ArrowHead::Arrow),
+        (Some('o'), Some('-' | '=')) => Some(ArrowHead::Circle),
+        (Some('x'), Some('-' | '=')) => Some(ArrowHead::Cross),
+        _ => None,
+    };
+    if head_start.is_some() {
+        p.cur.bump();
+    }
+
+    let (stroke, complete, length) = match p.cur.peek() {
+        Some('-') if p.cur.peek_second() == Some('.') => {
+            p.cur.bump();
+            let dots = run(p, '.');
+            if p.cur.eat_char('-') {
+                (Stroke::Dotted, true, dots)
+            } else {
+                (Stroke::Dotted, false, 1)
+            }
+        }
+        Some(c @ ('-' | '=')) => {
+            let n = run(p, c);
+            let stroke = if c == '-' {
+                Stroke::Normal
+            } else {
+                Stroke::Thick
+            };
+            if n < 2 {
+                p.cur.set_pos(start);
+                return Ok(None);
+            }
+            (stroke, n >= 3 || at_head_end(p), n)
+        }
+        _ => {
+            p.cur.set_pos(start);
+            return Ok(None);
+        }
+    };
+
+    let mut label = None;
+    let (end, length) = if complete {
+        let end = head_end(p);
+        let length = match stroke {
+            Stroke::Dotted => length,
+            _ if end.is_some() => length - 1,
+            _ => length - 2,
+        };
+        (end, length)
+    } else {
+        // `-- text -->`, `== text ==>`, `-. text .->`
+        let close = match stroke {
+            Stroke::Normal => "--",
+            Stroke::Thick => "==",
+            _ => ".-",
+        };
+        label = p.label_until(close);
+        if label.is_none() {
+            p.error(Span::new(start, p.cur.pos()), "unterminated link text");
+            p.cur.skip_line();
+            return Err(());
+        }
+        let (end, length) = match stroke {
+            Stroke::Dotted => {
+                let dots = run(p, '.');
+                p.cur.eat_char('-');
+                (head_end(p), dots)
+            }
+            _ => {
+                let n = run(p, if stroke == Stroke::Thick { '=' } else { '-' });
+                let end = head_end(p);
+                (end, if end.is_some() { n - 1 } else { n.max(3) - 2 })
+            }
+        };
+        (end, length)
+    };
+
+    let link = Link {
+        stroke,
+        start: head_start,
+        end,
+        length,
+        label,
+        span: Span::new(start, p.cur.pos()),
+    };
+    if link.label.is_some() {
+        return Ok(Some(link));
+    }
+    pipe_label(p, link).map(Some)
+}
+
+/// Attaches an optional `|label|` following a link.
+fn pipe_label(p: &mut Parser, mut link: Link) -> Result<Link, ()> {
+    let before = p.cur.pos();
+    p.cur.skip_blank();
+    if !p.cur.eat_char('|') {
+        p.cur.set_pos(before);
+        return Ok(link);
+    }
+    let Some(label) = p.label_until("|") else {
+        p.error(Span::new(before, p.cur.pos()), "unclosed `|` in link label");
+        p.cur.skip_line();
+        return Err(());
+    };
+    p.cur.skip_blank();
+    p.cur.eat_char('|');
+    link.label = Some(label);
+    link.span = link.span.to(Span::new(before, p.cur.pos()));
+    Ok(link)
+}
+
+fn run(p: &mut Parser, c: char) -> usize {
+    let mut n = 0;
+    while p.cur.eat_char(c) {
+        n += 1;
+    }
+    n
+}
+
+/// Whether an arrow head follows the strokes. Like Mermaid, `o` and `x`
+/// directly after them always end the link, so `E--oF` links to `F`.
+fn at_head_end(p: &Parser) -> bool {
+    matches!(p.cur.peek(), Some('>' | 'o' | 'x'))
+}
+
+fn head_end(p: &mut Parser) -> Option<ArrowHead> {
+    if !at_head_end(p) {
+        return None;
+    }
+    match p.cur.bump() {
+        Some('>') => Some(ArrowHead::Arrow),
+        Some('o') => Some(ArrowHead::Circle),
+        _ => Some(ArrowHead::Cross),
+    }
+}
+
+fn ident_list(p: &mut Parser, what: &str) -> Option<Vec<Ident>> {
+    let mut idents = Vec::new();
+    loop {
+        p.cur.skip_blank();
+        let Some(ident) = p.cur.ident() else {
+            p.recover(format!("expected a {what}"));
+            return None;
+        };
+        idents.push(ident);
+        p.cur.skip_blank();
+        if !p.cur.eat_char(',') {
+            return Some(idents);
+        }
+    }
+}
+
+fn link_targets(p: &mut Parser) -> Option<LinkTargets> {
+    p.cur.skip_blank();
+    if p.cur.eat_keyword("default").is_some() {
+        return Some(LinkTargets::Default);
+    }
+    let mut indices = Vec::new();
+    loop {
+        p.cur.skip_blank();
+        let start = p.cur.pos();
+        while p.cur.peek().is_some_and(|c| c.is_ascii_digit()) {
+            p.cur.bump();
+        }
+        let span = Span::new(start, p.cur.pos());
+        let Ok(index) = p.cur.slice(span).parse() else {
+            p.recover("expected a link index or `default`");
+            return None;
+        };
+        indices.push(Spanned::new(index, span));
+        if !p.cur.eat_char(',') {
+            return Some(LinkTargets::Indices(indices));
+        }
+    }
+}
+
+/// Parses `key:value,key:value` up to the end of the statement. Commas
+/// inside parentheses, as in `rgb(1,2,3)`, do not split properties.
+fn style_props(p: &mut Parser) -> Option<Vec<StyleProp>> {
+    p.cur.skip_blank();
+    let (text, span) = p.cur.rest_before_comment();
+    let mut props = Vec::new();
+    let mut depth = 0usize;
+    let mut from = 0;
+    for (i, c) in text.char_indices().chain([(text.len(), ',')]) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                let raw = &text[from..i];
+                let at = span.start + from + (raw.len() - raw.trim_start().len());
+                let prop_span = Span::new(at, at + raw.trim().len());
+                match raw.split_once(':') {
+                    Some((key, value)) if !key.trim().is_empty() => props.push(StyleProp {
+                        key: key.trim().to_string(),
+                        value: value.trim().to_string(),
+                        span: prop_span,
+                    }),
+                    _ => {
+                        p.error(prop_span, "expected `property:value`");
+                        return None;
+                    }
+                }
+                from = i + 1;
+            }
+            _ => {}
+        }
+    }
+    Some(props)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse, Diagram};
+
+    fn flowchart(source: &str) -> Flowchart {
+        let parsed = parse(source);
+        assert!(parsed.errors.is_empty(), "{source}: {:?}", parsed.errors);
+        match parsed.document.map(|document| document.diagram) {
+            Some(Diagram::Flowchart(chart)) => chart,
+            other => panic!("not a flowchart: {other:?}"),
+        }
+    }
+
+    fn edges(source: &str) -> Vec<(String, Option<ArrowHead>, String)> {
+        flowchart(source)
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Chain(chain) => Some(chain),
+                _ => None,
+            })
+            .flat_map(|chain| {
+                chain
+                    .edges()
+                    .map(|(from, link, to)| (from.id.value.clone(), link.end, to.id.value.clone()))
+            })
+            .collect()
+    }
+
+    fn edge(from: &str, end: Option<ArrowHead>, to: &str) -> (String, Option<ArrowHead>, String) {
+        (from.to_string(), end, to.to_string())
+    }
+
+    fn comments(statements: &[Statement]) -> Vec<&str> {
+        statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Comment(comment) => Some(comment.text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn trailing_comments() {
+        let chart = flowchart(concat!(
+            "flowchart TD %% top down\n",
+            "  A --> B %% note\n",
+            "  C[\"50%% done\"] --- D;%% after a separator\n",
+            "  classDef x fill:#f00 %% red\n",
+            "  click A \"https://example.com/?q=%%\" %% link\n",
+            "  subgraph S [Sub] %% group\n",
+            "    E %% inside\n",
+            "  end %% closed\n",
+        ));
+        assert_eq!(
+            comments(&chart.statements),
+            [
+                "%% top down",
+                "%% note",
+                "%% after a separator",
+                "%% red",
+                "%% link",
+                "%% closed"
+            ]
+        );
+        for statement in &chart.statements {
+            match statement {
+                Statement::ClassDef(class_def) => assert_eq!(class_def.styles[0].value, "#f00"),
+                Statement::Click(click) => {
+                    assert_eq!(click.action.text, "\"https://example.com/?q=%%\"")
+                }
+                Statement::Subgraph(subgraph) => {
+                    assert_eq!(subgraph.title.as_ref().unwrap().text, "Sub");
+                    assert_eq!(comments(&subgraph.statements), ["%% group", "%% inside"]);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(
+            edges("graph LR\n  A-->B%%x\n"),
+            [edge("A", Some(ArrowHead::Arrow), "B")]
+        );
+    }
+
+    #[test]
+    fn arrow_heads_without_spaces() {
+        assert_eq!(
+            edges("flowchart LR\n  A-->B\n  E--oF\n  G--xH\n  I==oJ\n  K-.-xL\n  M---oN\n"),
+            [
+                edge("A", Some(ArrowHead::Arrow), "B"),
+                edge("E", Some(ArrowHead::Circle), "F"),
+                edge("G", Some(ArrowHead::Cross), "H"),
+                edge("I", Some(ArrowHead::Circle), "J"),
+                edge("K", Some(ArrowHead::Cross), "L"),
+                edge("M", Some(ArrowHead::Circle), "N"),
+            ]
+        );
+    }
+
+    #[test]
+    fn link_text() {
+        let chart = flowchart("graph TD\n  A -- yes --> B\n  A -->|no| C\n  A -- option --- D\n");
+        let labels: Vec<_> = chart
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Chain(chain) => chain.links[0].label.as_ref(),
+                _ => None,
+            })
+            .map(|label| label.text.as_str())
+            .collect();
+        assert_eq!(labels, ["yes", "no", "option"]);
+    }
+
+    #[test]
+    fn chains_and_groups() {
+        assert_eq!(
+            edges("flowchart TD\n  A & B --> C --> D\n"),
+            [
+                edge("A", Some(ArrowHead::Arrow), "C"),
+                edge("B", Some(ArrowHead::Arrow), "C"),
+                edge("C", Some(ArrowHead::Arrow), "D"),
+            ]
+        );
+    }
+
+    #[test]
+    fn shapes() {
+        let chart =
+            flowchart("flowchart TD\n  A[rect] --> B((circle)) --> C{rhombus} --> D[(db)]\n");
+        let Statement::Chain(chain) = &chart.statements[0] else {
+            panic!("not a chain");
+        };
+        let shapes: Vec<_> = chain.nodes().map(|node| node.shape).collect();
+        assert_eq!(
+            shapes,
+            [
+                Some(Shape::Rect),
+                Some(Shape::Circle),
+                Some(Shape::Rhombus),
+                Some(Shape::Cylinder),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_link_text() {
+        let parsed = parse("flowchart TD\n  A -- text B\n  C --> D\n");
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].message, "unterminated link text");
+    }
+}
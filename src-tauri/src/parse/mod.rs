@@ -0,0 +1,568 @@
+//! Native Mermaid parser producing a typed AST with byte spans.
+//!
+//! Rendering still happens in the webview; this module exists so tooling
+//! (checks, linting, formatting, refactoring) can understand diagrams in
+//! Rust. Parsing recovers at line granularity, so a single bad statement
+//! yields one error and the rest of the document is still available.
+
+pub mod class;
+mod cursor;
+pub mod er;
+pub mod flowchart;
+pub mod sequence;
+pub mod state;
+
+use serde::Serialize;
+
+pub use class::ClassDiagram;
+use cursor::Cursor;
+pub use er::ErDiagram;
+pub use flowchart::Flowchart;
+pub use sequence::SequenceDiagram;
+pub use state::StateDiagram;
+
+/// Half-open byte range into the parsed source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Self {
+        Self { value, span }
+    }
+}
+
+pub type Ident = Spanned<String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Direction {
+    TB,
+    TD,
+    BT,
+    RL,
+    LR,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::TB => "TB",
+            Direction::TD => "TD",
+            Direction::BT => "BT",
+            Direction::RL => "RL",
+            Direction::LR => "LR",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        Some(match s {
+            "TB" => Direction::TB,
+            "TD" => Direction::TD,
+            "BT" => Direction::BT,
+            "RL" => Direction::RL,
+            "LR" => Direction::LR,
+            _ => return None,
+        })
+    }
+}
+
+/// Free text such as a node label or message. `span` covers the quotes
+/// when the text was written as a `"..."` string.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Label {
+    pub text: String,
+    pub quoted: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommentKind {
+    /// `%% text`
+    Line,
+    /// `%%{init: {...}}%%`
+    Directive,
+}
+
+/// A comment, on a line of its own or trailing a statement, kept in the AST
+/// so formatters can reproduce it. `text` runs from the `%%` markers to the
+/// end of the line, trimmed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comment {
+    pub kind: CommentKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// A statement recognised by its keyword but kept as raw text, such as
+/// styling lines the AST has no use for yet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Verbatim {
+    pub keyword: Ident,
+    pub text: Label,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+/// A suggested edit that resolves an error: replace `span` with
+/// `replacement`. An empty span is an insertion.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fix {
+    /// Short imperative description, e.g. "Insert `end`".
+    pub title: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    /// The YAML block between `---` fences at the top of the file.
+    pub front_matter: Option<Spanned<String>>,
+    /// Comments and directives before the diagram header.
+    pub preamble: Vec<Comment>,
+    pub diagram: Diagram,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Diagram {
+    Flowchart(Flowchart),
+    Sequence(SequenceDiagram),
+    Class(ClassDiagram),
+    State(StateDiagram),
+    Er(ErDiagram),
+    /// A diagram type the native parser does not understand yet.
+    Unsupported {
+        keyword: Ident,
+    },
+}
+
+impl Diagram {
+    /// The header keyword, e.g. `flowchart`, `graph` or `sequenceDiagram`.
+    pub fn keyword(&self) -> &Ident {
+        match self {
+            Diagram::Flowchart(chart) => &chart.keyword,
+            Diagram::Sequence(diagram) => &diagram.keyword,
+            Diagram::Class(diagram) => &diagram.keyword,
+            Diagram::State(diagram) => &diagram.keyword,
+            Diagram::Er(diagram) => &diagram.keyword,
+            Diagram::Unsupported { keyword } => keyword,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub document: Option<Document>,
+    pub errors: Vec<ParseError>,
+}
+
+/// Header keywords, used to suggest a correction for an unknown one.
+const DIAGRAM_TYPES: &[&str] = &[
+    "flowchart",
+    "graph",
+    "sequenceDiagram",
+    "classDiagram",
+    "stateDiagram-v2",
+    "stateDiagram",
+    "erDiagram",
+    "gantt",
+    "pie",
+    "mindmap",
+    "journey",
+    "gitGraph",
+    "requirementDiagram",
+    "C4Context",
+];
+
+pub fn parse(source: &str) -> Parsed {
+    let mut p = Parser::new(source);
+    let document = p.document();
+    p.errors.sort_by_key(|e| e.span.start);
+    Parsed {
+        document,
+        errors: p.errors,
+    }
+}
+
+pub(crate) struct Parser<'a> {
+    cur: Cursor<'a>,
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            cur: Cursor::new(source),
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, span: Span, message: impl Into<String>) {
+        self.errors.push(ParseError {
+            span,
+            message: message.into(),
+            fix: None,
+        });
+    }
+
+    fn error_with_fix(&mut self, span: Span, message: impl Into<String>, fix: Fix) {
+        self.errors.push(ParseError {
+            span,
+            message: message.into(),
+            fix: Some(fix),
+        });
+    }
+
+    /// A fix that appends `line` as the last line of the source, used for
+    /// blocks left open at the end of the file.
+    fn append_line_fix(&self, line: &str) -> Fix {
+        let source = self.cur.source();
+        let end = source.len();
+        let replacement = if source.is_empty() || source.ends_with('\n') {
+            format!("{line}\n")
+        } else {
+            format!("\n{line}")
+        };
+        Fix {
+            title: format!("Insert `{line}`"),
+            span: Span::new(end, end),
+            replacement,
+        }
+    }
+
+    /// A fix replacing `span` with `replacement`.
+    fn replace_fix(&self, span: Span, replacement: &str) -> Fix {
+        Fix {
+            title: format!("Replace with `{replacement}`"),
+            span,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    /// Reports an error at the current token and skips the rest of the line.
+    fn recover(&mut self, message: impl Into<String>) {
+        let span = self.cur.error_span();
+        self.error(span, message);
+        self.cur.skip_line();
+    }
+
+    /// Requires the statement to end here, otherwise reports the leftover
+    /// token and skips the rest of the line.
+    fn expect_statement_end(&mut self) {
+        self.cur.skip_blank();
+        if self.cur.at_statement_end() {
+            self.cur.eat_char(';');
+        } else {
+            let token = self.cur.slice(self.cur.error_span()).to_string();
+            self.recover(format!("unexpected `{token}`"));
+        }
+    }
+
+    /// Parses a `%%` comment or `%%{...}%%` directive at the cursor.
+    fn comment(&mut self) -> Option<Comment> {
+        if !self.cur.starts_with("%%") {
+            return None;
+        }
+        let (text, span) = self.cur.rest_of_line();
+        let kind = if text.starts_with("%%{") {
+            CommentKind::Directive
+        } else {
+            CommentKind::Line
+        };
+        Some(Comment {
+            kind,
+            text: text.to_string(),
+            span,
+        })
+    }
+
+    /// Parses a statement that starts with one of `keywords` as
+    /// [`Verbatim`] text.
+    fn verbatim(&mut self, keywords: &[&str]) -> Option<Verbatim> {
+        let start = self.cur.pos();
+        let keyword = keywords.iter().find_map(|&kw| {
+            let span = self.cur.eat_keyword(kw)?;
+            Some(Spanned::new(kw.to_string(), span))
+        })?;
+        self.cur.skip_blank();
+        let (text, span) = self.cur.rest_of_statement();
+        Some(Verbatim {
+            keyword,
+            text: Label {
+                text: text.to_string(),
+                quoted: false,
+                span,
+            },
+            span: Span::new(start, self.cur.pos()),
+        })
+    }
+
+    /// Reads the text of a label, either a `"..."` string or raw text up to
+    /// `close` on the current line.
+    fn label_until(&mut self, close: &str) -> Option<Label> {
+        self.cur.skip_blank();
+        if let Some((text, span)) = self.cur.quoted() {
+            self.cur.skip_blank();
+            if self.cur.starts_with(close) {
+                return Some(Label {
+                    text: text.to_string(),
+                    quoted: true,
+                    span,
+                });
+            }
+            self.cur.set_pos(span.start);
+        }
+        let (raw, span) = self.cur.take_until(close)?;
+        let text = raw.trim();
+        let start = span.start + (raw.len() - raw.trim_start().len());
+        Some(Label {
+            text: text.to_string(),
+            quoted: false,
+            span: Span::new(start, start + text.len()),
+        })
+    }
+
+    /// Parses `TB`, `TD`, `BT`, `RL` or `LR` after a `direction` keyword or
+    /// diagram header.
+    fn direction(&mut self) -> Option<Spanned<Direction>> {
+        let Some(word) = self.cur.word() else {
+            self.recover("expected a direction (TB, TD, BT, RL or LR)");
+            return None;
+        };
+        match Direction::from_str(&word.value) {
+            Some(direction) => Some(Spanned::new(direction, word.span)),
+            None => {
+                let message = format!(
+                    "unknown direction `{}`, expected TB, TD, BT, RL or LR",
+                    word.value
+                );
+                match Direction::from_str(&word.value.to_ascii_uppercase()) {
+                    Some(direction) => {
+                        let fix = self.replace_fix(word.span, direction.as_str());
+                        self.error_with_fix(word.span, message, fix);
+                    }
+                    None => self.error(word.span, message),
+                }
+                self.cur.skip_line();
+                None
+            }
+        }
+    }
+
+    fn document(&mut self) -> Option<Document> {
+        let front_matter = self.front_matter();
+
+        let mut preamble = Vec::new();
+        loop {
+            self.cur.skip_empty();
+            match self.comment() {
+                Some(comment) => preamble.push(comment),
+                None => break,
+            }
+        }
+
+        let Some(keyword) = self.cur.ident() else {
+            if self.cur.is_eof() {
+                self.error(Span::new(0, self.cur.pos()), "empty diagram");
+            } else {
+                self.recover("expected a diagram type such as `flowchart` or `sequenceDiagram`");
+            }
+            return None;
+        };
+
+        let diagram = match keyword.value.as_str() {
+            "graph" | "flowchart" => Diagram::Flowchart(flowchart::parse(self, keyword)),
+            "sequenceDiagram" => Diagram::Sequence(sequence::parse(self, keyword)),
+            "classDiagram" => Diagram::Class(class::parse(self, keyword)),
+            "stateDiagram" | "stateDiagram-v2" => Diagram::State(state::parse(self, keyword)),
+            "erDiagram" => Diagram::Er(er::parse(self, keyword)),
+            "gantt" | "pie" | "mindmap" | "journey" | "gitGraph" | "requirementDiagram"
+            | "C4Context" => Diagram::Unsupported { keyword },
+            other => {
+                let message = format!("unknown diagram type `{other}`");
+                match closest(other, DIAGRAM_TYPES) {
+                    Some(suggestion) => {
+                        let fix = self.replace_fix(keyword.span, suggestion);
+                        self.error_with_fix(keyword.span, message, fix);
+                    }
+                    None => self.error(keyword.span, message),
+                }
+                return None;
+            }
+        };
+
+        Some(Document {
+            front_matter,
+            preamble,
+            diagram,
+        })
+    }
+
+    fn front_matter(&mut self) -> Option<Spanned<String>> {
+        if !(self.cur.starts_with("---\n") || self.cur.starts_with("---\r\n")) {
+            return None;
+        }
+        let start = self.cur.pos();
+        self.cur.skip_line();
+        let body = self.cur.pos();
+        loop {
+            if self.cur.is_eof() {
+                self.error(
+                    Span::new(start, start + 3),
+                    "front matter is never closed with `---`",
+                );
+                return None;
+            }
+            let line_start = self.cur.pos();
+            let (line, _) = self.cur.rest_of_line();
+            if line == "---" {
+                let text = self.cur.slice(Span::new(body, line_start)).to_string();
+                let span = Span::new(start, self.cur.pos());
+                self.cur.skip_line();
+                return Some(Spanned::new(text, span));
+            }
+            self.cur.skip_line();
+        }
+    }
+}
+
+/// The candidate closest to `word`: an exact match ignoring case, or one
+/// within two single-character edits.
+fn closest<'k>(word: &str, candidates: &[&'k str]) -> Option<&'k str> {
+    if let Some(&exact) = candidates.iter().find(|c| c.eq_ignore_ascii_case(word)) {
+        return Some(exact);
+    }
+    candidates
+        .iter()
+        .map(|&c| (edit_distance(&word.to_lowercase(), &c.to_lowercase()), c))
+        .filter(|&(distance, _)| distance <= 2)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_and_preamble() {
+        let source =
+            "---\ntitle: Demo\n---\n%% note\n%%{init: {\"theme\": \"dark\"}}%%\nflowchart LR\n";
+        let parsed = parse(source);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let document = parsed.document.unwrap();
+        assert_eq!(document.front_matter.unwrap().value, "title: Demo\n");
+        let kinds: Vec<_> = document.preamble.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [CommentKind::Line, CommentKind::Directive]);
+        assert_eq!(document.diagram.keyword().value, "flowchart");
+    }
+
+    #[test]
+    fn unclosed_front_matter() {
+        let parsed = parse("---\ntitle: Demo\nflowchart LR\n");
+        assert_eq!(
+            parsed.errors[0].message,
+            "front matter is never closed with `---`"
+        );
+        assert_eq!(parsed.errors[0].span, Span::new(0, 3));
+    }
+
+    #[test]
+    fn unknown_diagram_types() {
+        let parsed = parse("flowhcart LR\n");
+        assert!(parsed.document.is_none());
+        assert_eq!(parsed.errors[0].message, "unknown diagram type `flowhcart`");
+        assert_eq!(
+            parsed.errors[0].fix.as_ref().unwrap().replacement,
+            "flowchart"
+        );
+
+        let parsed = parse("SEQUENCEDIAGRAM\n");
+        assert_eq!(
+            parsed.errors[0].fix.as_ref().unwrap().replacement,
+            "sequenceDiagram"
+        );
+
+        let parsed = parse("timeline\n");
+        assert!(parsed.errors[0].fix.is_none());
+    }
+
+    #[test]
+    fn empty_and_unsupported() {
+        assert_eq!(parse("").errors[0].message, "empty diagram");
+        assert_eq!(
+            parse("%% only a comment\n").errors[0].message,
+            "empty diagram"
+        );
+        let parsed = parse("pie\n  \"a\" : 1\n");
+        assert!(parsed.errors.is_empty());
+        assert!(matches!(
+            parsed.document.unwrap().diagram,
+            Diagram::Unsupported { .. }
+        ));
+    }
+
+    #[test]
+    fn directions() {
+        let parsed = parse("flowchart lr\n");
+        assert_eq!(
+            parsed.errors[0].message,
+            "unknown direction `lr`, expected TB, TD, BT, RL or LR"
+        );
+        assert_eq!(parsed.errors[0].fix.as_ref().unwrap().replacement, "LR");
+        assert!(parse("flowchart XY\n").errors[0].fix.is_none());
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("graph", "graph"), 0);
+        assert_eq!(edit_distance("grpah", "graph"), 2);
+        assert_eq!(edit_distance("", "pie"), 3);
+        assert_eq!(closest("Graph", DIAGRAM_TYPES), Some("graph"));
+        assert_eq!(closest("erDiagrm", DIAGRAM_TYPES), Some("erDiagram"));
+        assert_eq!(closest("banana", DIAGRAM_TYPES), None);
+    }
+}
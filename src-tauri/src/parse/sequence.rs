@@ -0,0 +1,929 @@
+//! `sequenceDiagram` diagrams.
+
+use serde::Serialize;
+
+use super::{Comment, Ident, Label, Parser, Span, Spanned};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SequenceDiagram {
+    pub keyword: Ident,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Statement {
+    Participant(Participant),
+    Message(Message),
+    Activation(Activation),
+    Note(Note),
+    Link(Link),
+    Block(Block),
+    Autonumber(Autonumber),
+    Title(Label),
+    Destroy(Destroy),
+    Accessibility(Accessibility),
+    Comment(Comment),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Participant(participant) => participant.span,
+            Statement::Message(message) => message.span,
+            Statement::Activation(activation) => activation.span,
+            Statement::Note(note) => note.span,
+            Statement::Link(link) => link.span,
+            Statement::Block(block) => block.span,
+            Statement::Autonumber(autonumber) => autonumber.span,
+            Statement::Title(title) => title.span,
+            Statement::Destroy(destroy) => destroy.span,
+            Statement::Accessibility(accessibility) => accessibility.span,
+            Statement::Comment(comment) => comment.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ParticipantKind {
+    Participant,
+    Actor,
+}
+
+/// `participant A as Alice` / `actor C`, or `create participant B` for one
+/// that appears with the next message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Participant {
+    pub created: bool,
+    pub kind: ParticipantKind,
+    pub id: Ident,
+    pub alias: Option<Label>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineStyle {
+    Solid,
+    Dotted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArrowHead {
+    /// `->`
+    Open,
+    /// `->>`
+    Filled,
+    /// `-x`
+    Cross,
+    /// `-)`
+    Async,
+    /// `<<->>`
+    Bidirectional,
+}
+
+/// Arrow tokens, longest first.
+const ARROWS: &[(&str, LineStyle, ArrowHead)] = &[
+    ("<<-->>", LineStyle::Dotted, ArrowHead::Bidirectional),
+    ("<<->>", LineStyle::Solid, ArrowHead::Bidirectional),
+    ("-->>", LineStyle::Dotted, ArrowHead::Filled),
+    ("->>", LineStyle::Solid, ArrowHead::Filled),
+    ("--x", LineStyle::Dotted, ArrowHead::Cross),
+    ("-x", LineStyle::Solid, ArrowHead::Cross),
+    ("--)", LineStyle::Dotted, ArrowHead::Async),
+    ("-)", LineStyle::Solid, ArrowHead::Async),
+    ("-->", LineStyle::Dotted, ArrowHead::Open),
+    ("->", LineStyle::Solid, ArrowHead::Open),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Arrow {
+    pub line: LineStyle,
+    pub head: ArrowHead,
+    pub span: Span,
+}
+
+impl Arrow {
+    pub fn as_str(&self) -> &'static str {
+        ARROWS
+            .iter()
+            .find(|&&(_, line, head)| line == self.line && head == self.head)
+            .map(|&(token, _, _)| token)
+            .unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActivationChange {
+    /// `+` after the arrow
+    Activate,
+    /// `-` after the arrow
+    Deactivate,
+}
+
+/// `Alice->>+Bob: Hello`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Message {
+    pub from: Ident,
+    pub arrow: Arrow,
+    pub activation: Option<Spanned<ActivationChange>>,
+    pub to: Ident,
+    pub text: Label,
+    pub span: Span,
+}
+
+/// `activate A` / `deactivate A`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Activation {
+    pub change: ActivationChange,
+    pub participant: Ident,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotePlacement {
+    LeftOf,
+    RightOf,
+    Over,
+}
+
+/// `link A: Dashboard @ https://…` or `links A: {"Wiki": "https://…"}`,
+/// entries for a participant's menu. The text is kept as written.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Link {
+    pub keyword: Ident,
+    pub participant: Ident,
+    pub text: Label,
+    pub span: Span,
+}
+
+/// `destroy B`, removing a participant after the next message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Destroy {
+    pub participant: Ident,
+    pub span: Span,
+}
+
+/// `accTitle: text`, `accDescr: text` or a multi-line `accDescr { ... }`,
+/// whose lines are joined with `\n`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Accessibility {
+    /// `accTitle` or `accDescr`.
+    pub keyword: Ident,
+    pub text: Label,
+    pub span: Span,
+}
+
+/// `Note right of A: text` / `Note over A,B: text`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Note {
+    pub placement: NotePlacement,
+    pub participants: Vec<Ident>,
+    pub text: Label,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockKind {
+    Loop,
+    Alt,
+    Opt,
+    Par,
+    Critical,
+    Break,
+    Rect,
+    Box,
+}
+
+const BLOCKS: &[(&str, BlockKind)] = &[
+    ("loop", BlockKind::Loop),
+    ("alt", BlockKind::Alt),
+    ("opt", BlockKind::Opt),
+    ("par", BlockKind::Par),
+    ("critical", BlockKind::Critical),
+    ("break", BlockKind::Break),
+    ("rect", BlockKind::Rect),
+    ("box", BlockKind::Box),
+];
+
+/// Keywords that end one section of a block and start the next.
+const CONTINUATIONS: &[&str] = &["else", "and", "option"];
+
+impl BlockKind {
+    pub fn keyword(self) -> &'static str {
+        BLOCKS.iter().find(|(_, k)| *k == self).unwrap().0
+    }
+
+    /// The keyword that starts another section, as `else` does for `alt`.
+    pub fn continuation(self) -> Option<&'static str> {
+        match self {
+            BlockKind::Alt => Some("else"),
+            BlockKind::Par => Some("and"),
+            BlockKind::Critical => Some("option"),
+            _ => None,
+        }
+    }
+}
+
+/// A `loop`/`alt`/`opt`/... block. The first section holds the opening
+/// keyword; each `else`/`and`/`option` starts another one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub sections: Vec<Section>,
+    /// The closing `end`, missing when the block is unterminated.
+    pub end: Option<Span>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Section {
+    pub keyword: Ident,
+    pub label: Option<Label>,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+/// `autonumber`, `autonumber 10 5` or `autonumber off`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Autonumber {
+    pub start: Option<Spanned<u32>>,
+    pub step: Option<Spanned<u32>>,
+    pub off: bool,
+    pub span: Span,
+}
+
+pub(super) fn parse(p: &mut Parser, keyword: Ident) -> SequenceDiagram {
+    p.expect_statement_end();
+    let (statements, _) = statements(p, false);
+    SequenceDiagram {
+        keyword,
+        statements,
+    }
+}
+
+/// Parses statements until EOF or, inside a block, until `end` or a
+/// section keyword, which is consumed and returned as the terminator.
+fn statements(p: &mut Parser, in_block: bool) -> (Vec<Statement>, Option<Ident>) {
+    let mut statements = Vec::new();
+    loop {
+        p.cur.skip_empty();
+        if p.cur.is_eof() {
+            return (statements, None);
+        }
+        if let Some(comment) = p.comment() {
+            statements.push(Statement::Comment(comment));
+            continue;
+        }
+        if let Some(terminator) = terminator(p) {
+            if in_block {
+                return (statements, Some(terminator));
+            }
+            let message = match terminator.value.as_str() {
+                "end" => "`end` without an open block".to_string(),
+                kw => format!("`{kw}` outside of a block"),
+            };
+            p.error(terminator.span, message);
+            p.cur.rest_of_statement();
+            p.expect_statement_end();
+            continue;
+        }
+        if let Some(statement) = statement(p) {
+            statements.push(statement);
+        }
+    }
+}
+
+fn terminator(p: &mut Parser) -> Option<Ident> {
+    ["end"].iter().chain(CONTINUATIONS).find_map(|&kw| {
+        p.cur
+            .eat_keyword(kw)
+            .map(|span| Spanned::new(kw.to_string(), span))
+    })
+}
+
+fn statement(p: &mut Parser) -> Option<Statement> {
+    let start = p.cur.pos();
+
+    let opener = BLOCKS.iter().find_map(|&(kw, kind)| {
+        let span = p.cur.eat_keyword(kw)?;
+        Some((kind, Spanned::new(kw.to_string(), span)))
+    });
+    if let Some((kind, keyword)) = opener {
+        return Some(Statement::Block(block(p, kind, keyword)));
+    }
+
+    // `create` only counts before `participant` or `actor`, so a
+    // participant called `create` can still send messages.
+    let mut created = p.cur.eat_keyword("create").is_some();
+    if created {
+        p.cur.skip_blank();
+    }
+    let kind = participant_kind(p);
+    if created && kind.is_none() {
+        p.cur.set_pos(start);
+        created = false;
+    }
+
+    let statement = if let Some(kind) = kind {
+        p.cur.skip_blank();
+        let (text, span) = p.cur.rest_of_statement();
+        if text.is_empty() {
+            p.error(span, "expected a participant name");
+            return None;
+        }
+        let (id, alias) = match text.find(" as ") {
+            Some(i) => {
+                let raw = &text[i + 4..];
+                let alias = raw.trim();
+                let at = span.start + i + 4 + (raw.len() - raw.trim_start().len());
+                let alias = Label {
+                    text: alias.to_string(),
+                    quoted: false,
+                    span: Span::new(at, at + alias.len()),
+                };
+                (text[..i].trim_end(), Some(alias))
+            }
+            None => (text, None),
+        };
+        Statement::Participant(Participant {
+            created,
+            kind,
+            id: Spanned::new(id.to_string(), Span::new(span.start, span.start + id.len())),
+            alias,
+            span: Span::new(start, p.cur.pos()),
+        })
+    } else if let Some(change) = activation_keyword(p) {
+        p.cur.skip_blank();
+        let (text, span) = p.cur.rest_of_statement();
+        if text.is_empty() {
+            p.error(span, "expected a participant name");
+            return None;
+        }
+        Statement::Activation(Activation {
+            change,
+            participant: Spanned::new(text.to_string(), span),
+            span: Span::new(start, p.cur.pos()),
+        })
+    } else if p.cur.eat_keyword("destroy").is_some() {
+        p.cur.skip_blank();
+        let (text, span) = p.cur.rest_of_statement();
+        if text.is_empty() {
+            p.error(span, "expected a participant name");
+            return None;
+        }
+        Statement::Destroy(Destroy {
+            participant: Spanned::new(text.to_string(), span),
+            span: Span::new(start, p.cur.pos()),
+        })
+    } else if let Some(keyword) = accessibility_keyword(p) {
+        Statement::Accessibility(accessibility(p, keyword, start)?)
+    } else if p.cur.eat_keyword("Note").is_some() || p.cur.eat_keyword("note").is_some() {
+        Statement::Note(note(p, start)?)
+    } else if let Some(keyword) = link_keyword(p) {
+        Statement::Link(link(p, keyword, start)?)
+    } else if p.cur.eat_keyword("autonumber").is_some() {
+        Statement::Autonumber(autonumber(p, start)?)
+    } else if p.cur.eat_keyword("title").is_some() {
+        p.cur.skip_blank();
+        p.cur.eat_char(':');
+        let (text, span) = p.cur.rest_of_statement();
+        Statement::Title(Label {
+            text: text.to_string(),
+            quoted: false,
+            span,
+        })
+    } else {
+        Statement::Message(message(p)?)
+    };
+    p.expect_statement_end();
+    Some(statement)
+}
+
+fn participant_kind(p: &mut Parser) -> Option<ParticipantKind> {
+    if p.cur.eat_keyword("participant").is_some() {
+        Some(ParticipantKind::Participant)
+    } else if p.cur.eat_keyword("actor").is_some() {
+        Some(ParticipantKind::Actor)
+    } else {
+        None
+    }
+}
+
+fn accessibility_keyword(p: &mut Parser) -> Option<Ident> {
+    ["accTitle", "accDescr"].iter().find_map(|&kw| {
+        p.cur
+            .eat_keyword(kw)
+            .map(|span| Spanned::new(kw.to_string(), span))
+    })
+}
+
+fn accessibility(p: &mut Parser, keyword: Ident, start: usize) -> Option<Accessibility> {
+    p.cur.skip_blank();
+    if p.cur.eat_char(':') {
+        let (text, span) = p.cur.rest_of_statement();
+        return Some(Accessibility {
+            keyword,
+            text: Label {
+                text: text.to_string(),
+                quoted: false,
+                span,
+            },
+            span: Span::new(start, p.cur.pos()),
+        });
+    }
+    if keyword.value != "accDescr" || !p.cur.eat_char('{') {
+        p.recover(format!("expected `:` after `{}`", keyword.value));
+        return None;
+    }
+
+    // Multi-line form: everything up to the closing `}` is the text.
+    let mut lines = Vec::new();
+    let mut text_span: Option<Span> = None;
+    let end = loop {
+        if p.cur.is_eof() {
+            let message = "`accDescr {` is never closed with `}`";
+            let fix = p.append_line_fix("}");
+            p.error_with_fix(keyword.span, message, fix);
+            break p.cur.pos();
+        }
+        let (line, span) = p.cur.rest_of_line();
+        let (line, closed) = match line.strip_suffix('}') {
+            Some(line) => (line.trim_end(), true),
+            None => (line, false),
+        };
+        if !line.is_empty() {
+            let span = Span::new(span.start, span.start + line.len());
+            lines.push(line);
+            text_span = Some(text_span.map_or(span, |s| s.to(span)));
+        }
+        if closed {
+            break p.cur.pos();
+        }
+        p.cur.skip_line();
+    };
+    Some(Accessibility {
+        keyword,
+        text: Label {
+            text: lines.join("\n"),
+            quoted: false,
+            span: text_span.unwrap_or(Span::new(end, end)),
+        },
+        span: Span::new(start, end),
+    })
+}
+
+fn activation_keyword(p: &mut Parser) -> Option<ActivationChange> {
+    if p.cur.eat_keyword("activate").is_some() {
+        Some(ActivationChange::Activate)
+    } else if p.cur.eat_keyword("deactivate").is_some() {
+        Some(ActivationChange::Deactivate)
+    } else {
+        None
+    }
+}
+
+fn block(p: &mut Parser, kind: BlockKind, keyword: Ident) -> Block {
+    let opener = keyword.span;
+    let mut section = section_header(p, keyword);
+    let mut sections = Vec::new();
+    let end = loop {
+        let (statements, terminator) = statements(p, true);
+        let section_end = terminator.as_ref().map_or(p.cur.pos(), |t| t.span.start);
+        section.statements = statements;
+        section.span = Span::new(section.span.start, section_end);
+        sections.push(section);
+
+        let Some(terminator) = terminator else {
+            let message = format!("`{}` is never closed with `end`", kind.keyword());
+            let fix = p.append_line_fix("end");
+            p.error_with_fix(opener, message, fix);
+            break None;
+        };
+        if terminator.value == "end" {
+            p.expect_statement_end();
+            break Some(terminator.span);
+        }
+        if kind.continuation() != Some(terminator.value.as_str()) {
+            let owner = BLOCKS
+                .iter()
+                .find(|(_, k)| k.continuation() == Some(terminator.value.as_str()))
+                .map_or("", |(kw, _)| kw);
+            let message = format!("`{}` is only allowed in `{owner}` blocks", terminator.value);
+            p.error(terminator.span, message);
+        }
+        section = section_header(p, terminator);
+    };
+
+    let span = opener.to(end.unwrap_or(Span::new(opener.start, p.cur.pos())));
+    Block {
+        kind,
+        sections,
+        end,
+        span,
+    }
+}
+
+fn section_header(p: &mut Parser, keyword: Ident) -> Section {
+    p.cur.skip_blank();
+    let (text, span) = p.cur.rest_of_statement();
+    p.expect_statement_end();
+    let label = (!text.is_empty()).then(|| Label {
+        text: text.to_string(),
+        quoted: false,
+        span,
+    });
+    Section {
+        span: keyword.span,
+        keyword,
+        label,
+        statements: Vec::new(),
+    }
+}
+
+fn note(p: &mut Parser, start: usize) -> Option<Note> {
+    p.cur.skip_blank();
+    let placement = if p.cur.eat_keyword("over").is_some() {
+        NotePlacement::Over
+    } else {
+        let side = if p.cur.eat_keyword("left").is_some() {
+            NotePlacement::LeftOf
+        } else if p.cur.eat_keyword("right").is_some() {
+            NotePlacement::RightOf
+        } else {
+            p.recover("expected `left of`, `right of` or `over`");
+            return None;
+        };
+        p.cur.skip_blank();
+        if p.cur.eat_keyword("of").is_none() {
+            p.recover("expected `of`");
+            return None;
+        }
+        side
+    };
+
+    p.cur.skip_blank();
+    let Some((names, names_span)) = p.cur.take_until(":") else {
+        p.recover("expected `:` followed by the note text");
+        return None;
+    };
+    let mut participants = Vec::new();
+    let mut offset = names_span.start;
+    for name in names.split(',') {
+        let trimmed = name.trim();
+        let at = offset + (name.len() - name.trim_start().len());
+        if trimmed.is_empty() {
+            p.error(Span::new(at, at), "expected a participant name");
+            p.cur.skip_line();
+            return None;
+        }
+        participants.push(Spanned::new(
+            trimmed.to_string(),
+            Span::new(at, at + trimmed.len()),
+        ));
+        offset += name.len() + 1;
+    }
+    if placement != NotePlacement::Over && participants.len() > 1 {
+        p.error(names_span, "only `Note over` can span several participants");
+    }
+
+    p.cur.eat_char(':');
+    let (text, span) = p.cur.rest_of_statement();
+    Some(Note {
+        placement,
+        participants,
+        text: Label {
+            text: text.to_string(),
+            quoted: false,
+            span,
+        },
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+fn link_keyword(p: &mut Parser) -> Option<Ident> {
+    ["links", "link"].iter().find_map(|&kw| {
+        p.cur
+            .eat_keyword(kw)
+            .map(|span| Spanned::new(kw.to_string(), span))
+    })
+}
+
+fn link(p: &mut Parser, keyword: Ident, start: usize) -> Option<Link> {
+    p.cur.skip_blank();
+    let Some((name, name_span)) = p.cur.take_until(":") else {
+        p.recover(format!(
+            "expected a participant, then `:` and the {}",
+            keyword.value
+        ));
+        return None;
+    };
+    let name = name.trim_end();
+    if name.is_empty() {
+        p.recover("expected a participant name");
+        return None;
+    }
+    let participant = Spanned::new(
+        name.to_string(),
+        Span::new(name_span.start, name_span.start + name.len()),
+    );
+    p.cur.eat_char(':');
+    let (text, span) = p.cur.rest_of_statement();
+    Some(Link {
+        keyword,
+        participant,
+        text: Label {
+            text: text.to_string(),
+            quoted: false,
+            span,
+        },
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+fn autonumber(p: &mut Parser, start: usize) -> Option<Autonumber> {
+    p.cur.skip_blank();
+    if p.cur.eat_keyword("off").is_some() {
+        return Some(Autonumber {
+            start: None,
+            step: None,
+            off: true,
+            span: Span::new(start, p.cur.pos()),
+        });
+    }
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        p.cur.skip_blank();
+        if p.cur.at_statement_end() {
+            break;
+        }
+        let Some(word) = p.cur.word() else {
+            break;
+        };
+        match word.value.parse() {
+            Ok(n) => numbers.push(Spanned::new(n, word.span)),
+            Err(_) => {
+                p.error(word.span, "expected a number or `off` after `autonumber`");
+                p.cur.skip_line();
+                return None;
+            }
+        }
+    }
+    let mut numbers = numbers.into_iter();
+    Some(Autonumber {
+        start: numbers.next(),
+        step: numbers.next(),
+        off: false,
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+fn message(p: &mut Parser) -> Option<Message> {
+    let start = p.cur.pos();
+    let line = p.cur.rest().split(['\n', ';']).next().unwrap_or_default();
+
+    // Participant names may contain spaces and `-`, but never `:`. The
+    // arrow is the longest token before the text, so `Web-Server->>DB`
+    // splits at `->>`.
+    let head = line.split(':').next().unwrap_or_default();
+    let found = head
+        .char_indices()
+        .filter_map(|(i, _)| {
+            let token = ARROWS.iter().find(|(t, _, _)| head[i..].starts_with(t))?;
+            Some((i, token.0.len()))
+        })
+        .min_by_key(|&(i, len)| (std::cmp::Reverse(len), i));
+    let Some((arrow_at, _)) = found else {
+        p.recover("expected a message such as `A->>B: text` or a statement keyword");
+        return None;
+    };
+    let raw = &line[..arrow_at];
+    let from_text = raw.trim();
+    if from_text.is_empty() || from_text.contains([':', '>']) {
+        p.recover("expected a participant name before the arrow");
+        return None;
+    }
+    let from_at = start + (raw.len() - raw.trim_start().len());
+    let from = Spanned::new(
+        from_text.to_string(),
+        Span::new(from_at, from_at + from_text.len()),
+    );
+
+    p.cur.set_pos(start + arrow_at);
+    let arrow_start = p.cur.pos();
+    let Some(&(token, line_style, head)) = ARROWS.iter().find(|(t, _, _)| p.cur.starts_with(t))
+    else {
+        p.recover("expected an arrow such as `->>` or `-->>`");
+        return None;
+    };
+    p.cur.eat(token);
+    let arrow = Arrow {
+        line: line_style,
+        head,
+        span: Span::new(arrow_start, p.cur.pos()),
+    };
+
+    let activation_at = p.cur.pos();
+    let activation = match p.cur.peek() {
+        Some('+') => Some(ActivationChange::Activate),
+        Some('-') => Some(ActivationChange::Deactivate),
+        _ => None,
+    }
+    .map(|change| {
+        p.cur.bump();
+        Spanned::new(change, Span::new(activation_at, activation_at + 1))
+    });
+
+    let Some((to_text, to_span)) = p.cur.take_until(":") else {
+        p.recover("expected `:` followed by the message text");
+        return None;
+    };
+    let to_name = to_text.trim();
+    if to_name.is_empty() || to_name.contains(';') {
+        p.error(to_span, "expected a participant name after the arrow");
+        p.cur.skip_line();
+        return None;
+    }
+    let to_at = to_span.start + (to_text.len() - to_text.trim_start().len());
+    let to = Spanned::new(to_name.to_string(), Span::new(to_at, to_at + to_name.len()));
+
+    p.cur.eat_char(':');
+    let (text, text_span) = p.cur.rest_of_statement();
+    Some(Message {
+        from,
+        arrow,
+        activation,
+        to,
+        text: Label {
+            text: text.to_string(),
+            quoted: false,
+            span: text_span,
+        },
+        span: Span::new(start, p.cur.pos()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse, Diagram};
+
+    fn statements(source: &str) -> Vec<Statement> {
+        let parsed = parse(source);
+        assert!(parsed.errors.is_empty(), "{source}: {:?}", parsed.errors);
+        match parsed.document.map(|document| document.diagram) {
+            Some(Diagram::Sequence(diagram)) => diagram.statements,
+            other => panic!("not a sequence diagram: {other:?}"),
+        }
+    }
+
+    fn message(statement: &Statement) -> (&str, &'static str, &str) {
+        let Statement::Message(message) = statement else {
+            panic!("not a message: {statement:?}");
+        };
+        (
+            &message.from.value,
+            message.arrow.as_str(),
+            &message.to.value,
+        )
+    }
+
+    #[test]
+    fn arrows() {
+        let statements = statements(
+            "sequenceDiagram\n  A->>B: a\n  A-->>B: b\n  A-xB: c\n  A--)B: d\n  A<<->>B: e\n  A->B: f\n",
+        );
+        let arrows: Vec<_> = statements.iter().map(|s| message(s).1).collect();
+        assert_eq!(arrows, ["->>", "-->>", "-x", "--)", "<<->>", "->"]);
+    }
+
+    #[test]
+    fn names_with_dashes() {
+        let statements = statements(
+            "sequenceDiagram\n  Web-Server->>DB: query\n  DB-->>Web-Server: rows\n  Web-xray->>DB: x\n",
+        );
+        assert_eq!(message(&statements[0]), ("Web-Server", "->>", "DB"));
+        assert_eq!(message(&statements[1]), ("DB", "-->>", "Web-Server"));
+        assert_eq!(message(&statements[2]), ("Web-xray", "->>", "DB"));
+    }
+
+    #[test]
+    fn activation_and_text() {
+        let statements = statements("sequenceDiagram\n  A->>+B: ask: why?\n  B-->>-A: because\n");
+        let Statement::Message(ask) = &statements[0] else {
+            panic!("not a message");
+        };
+        assert_eq!(
+            ask.activation.as_ref().map(|a| a.value),
+            Some(ActivationChange::Activate)
+        );
+        assert_eq!(ask.to.value, "B");
+        assert_eq!(ask.text.text, "ask: why?");
+    }
+
+    #[test]
+    fn create_and_destroy() {
+        let statements = statements(
+            "sequenceDiagram\n  A->>B: hi\n  create participant C as Carl\n  A->>C: hello\n  destroy C\n  C->>A: bye\n  create->>A: a participant called create\n",
+        );
+        let Statement::Participant(carl) = &statements[1] else {
+            panic!("not a participant");
+        };
+        assert!(carl.created);
+        assert_eq!(carl.id.value, "C");
+        let Statement::Destroy(destroy) = &statements[3] else {
+            panic!("not destroy");
+        };
+        assert_eq!(destroy.participant.value, "C");
+        assert_eq!(message(&statements[5]), ("create", "->>", "A"));
+    }
+
+    #[test]
+    fn links() {
+        let statements = statements(concat!(
+            "sequenceDiagram\n",
+            "  participant A as Alice\n",
+            "  link A: Dashboard @ https://dashboard.contoso.com/alice\n",
+            "  links A: {\"Wiki\": \"https://wiki.contoso.com/alice\"}\n",
+            "  A->>B: hi\n",
+        ));
+        let links: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Link(link) => Some((
+                    link.keyword.value.as_str(),
+                    link.participant.value.as_str(),
+                    link.text.text.as_str(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            links,
+            [
+                (
+                    "link",
+                    "A",
+                    "Dashboard @ https://dashboard.contoso.com/alice"
+                ),
+                (
+                    "links",
+                    "A",
+                    "{\"Wiki\": \"https://wiki.contoso.com/alice\"}"
+                ),
+            ]
+        );
+        assert_eq!(message(&statements[3]), ("A", "->>", "B"));
+
+        let parsed = parse("sequenceDiagram\n  link A Dashboard\n  links : {}\n");
+        let messages: Vec<_> = parsed.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected a participant, then `:` and the link",
+                "expected a participant name"
+            ]
+        );
+    }
+
+    #[test]
+    fn accessibility() {
+        let statements = statements(
+            "sequenceDiagram\n  accTitle: Login flow\n  accDescr: How a user signs in\n  accDescr {\n    First line\n    Second line\n  }\n  A->>B: hi\n",
+        );
+        let texts: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Accessibility(a) => {
+                    Some((a.keyword.value.as_str(), a.text.text.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                ("accTitle", "Login flow"),
+                ("accDescr", "How a user signs in"),
+                ("accDescr", "First line\nSecond line"),
+            ]
+        );
+        assert_eq!(message(&statements[3]), ("A", "->>", "B"));
+    }
+
+    #[test]
+    fn unclosed_block() {
+        let parsed = parse("sequenceDiagram\n  loop every minute\n    A->>B: ping\n");
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(
+            parsed.errors[0].message,
+            "`loop` is never closed with `end`"
+        );
+        assert!(parsed.errors[0].fix.is_some());
+    }
+}
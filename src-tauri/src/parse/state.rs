@@ -0,0 +1,515 @@
+//! `stateDiagram` / `stateDiagram-v2` diagrams.
+
+use serde::Serialize;
+
+use super::{Comment, Direction, Ident, Label, Parser, Span, Spanned, Verbatim};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateDiagram {
+    /// `stateDiagram` or `stateDiagram-v2`.
+    pub keyword: Ident,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Statement {
+    /// A `state ...` declaration, or a state named on its own line.
+    State(State),
+    Transition(Transition),
+    /// `Idle : waiting for input`
+    Description(Description),
+    Note(Note),
+    /// `--` separating concurrent regions of a composite state.
+    Concurrency(Span),
+    Direction(Spanned<Direction>),
+    /// `classDef`, `class` and `style` lines kept verbatim.
+    Verbatim(Verbatim),
+    Comment(Comment),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::State(state) => state.span,
+            Statement::Transition(transition) => transition.span,
+            Statement::Description(description) => description.span,
+            Statement::Note(note) => note.span,
+            Statement::Concurrency(span) => *span,
+            Statement::Direction(direction) => direction.span,
+            Statement::Verbatim(verbatim) => verbatim.span,
+            Statement::Comment(comment) => comment.span,
+        }
+    }
+}
+
+/// One end of a transition.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StateRef {
+    /// `[*]`: the start state on the left of `-->`, the end state on the
+    /// right.
+    Terminal(Span),
+    State(Ident),
+}
+
+impl StateRef {
+    pub fn span(&self) -> Span {
+        match self {
+            StateRef::Terminal(span) => *span,
+            StateRef::State(id) => id.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StateKind {
+    /// `<<fork>>`
+    Fork,
+    /// `<<join>>`
+    Join,
+    /// `<<choice>>`
+    Choice,
+}
+
+/// `state "Waiting for input" as Idle`, `state Idle : description`,
+/// `state Split <<fork>>` or a composite `state Active { ... }`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct State {
+    pub id: Ident,
+    pub label: Option<Label>,
+    pub description: Option<Label>,
+    pub kind: Option<Spanned<StateKind>>,
+    /// Style classes attached with `:::name`.
+    pub classes: Vec<Ident>,
+    /// Nested states of a composite state.
+    pub statements: Vec<Statement>,
+    /// The `{ }` body, `None` for a simple state.
+    pub body: Option<Span>,
+    pub span: Span,
+}
+
+/// `Idle --> Active : start`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Transition {
+    pub from: StateRef,
+    pub to: StateRef,
+    pub label: Option<Label>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Description {
+    pub state: Ident,
+    pub text: Label,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NoteSide {
+    LeftOf,
+    RightOf,
+}
+
+/// `note right of Idle : text`, or a multi-line note closed by `end note`.
+/// Lines of a multi-line note are joined with `\n`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Note {
+    pub side: NoteSide,
+    pub state: Ident,
+    pub text: Label,
+    pub span: Span,
+}
+
+const VERBATIM_KEYWORDS: &[&str] = &["classDef", "class", "style"];
+
+pub(super) fn parse(p: &mut Parser, keyword: Ident) -> StateDiagram {
+    p.expect_statement_end();
+    let (statements, _) = statements(p, false);
+    StateDiagram {
+        keyword,
+        statements,
+    }
+}
+
+/// Parses statements until EOF or, inside a composite state, until the
+/// closing `}`, which is consumed and returned.
+fn statements(p: &mut Parser, in_state: bool) -> (Vec<Statement>, Option<Span>) {
+    let mut statements = Vec::new();
+    loop {
+        p.cur.skip_empty();
+        if p.cur.is_eof() {
+            return (statements, None);
+        }
+        if let Some(comment) = p.comment() {
+            statements.push(Statement::Comment(comment));
+            continue;
+        }
+        if p.cur.peek() == Some('}') {
+            let close = Span::new(p.cur.pos(), p.cur.pos() + 1);
+            p.cur.bump();
+            if in_state {
+                p.expect_statement_end();
+                return (statements, Some(close));
+            }
+            p.error(close, "unmatched `}`");
+            p.expect_statement_end();
+            continue;
+        }
+        if let Some(statement) = statement(p) {
+            statements.push(statement);
+        }
+    }
+}
+
+fn statement(p: &mut Parser) -> Option<Statement> {
+    let start = p.cur.pos();
+    if let Some(keyword) = p.cur.eat_keyword("state") {
+        return state(p, keyword).map(Statement::State);
+    }
+    if p.cur.eat_keyword("note").is_some() {
+        return note(p, start).map(Statement::Note);
+    }
+
+    let statement = if let Some(verbatim) = p.verbatim(VERBATIM_KEYWORDS) {
+        Statement::Verbatim(verbatim)
+    } else if p.cur.eat_keyword("direction").is_some() {
+        p.cur.skip_blank();
+        Statement::Direction(p.direction()?)
+    } else if p.cur.starts_with("--") && !p.cur.starts_with("-->") {
+        p.cur.eat("--");
+        Statement::Concurrency(Span::new(start, p.cur.pos()))
+    } else {
+        let Some(from) = state_ref(p) else {
+            p.recover("expected a state, transition or statement keyword");
+            return None;
+        };
+        p.cur.skip_blank();
+        if p.cur.eat("-->") {
+            p.cur.skip_blank();
+            let Some(to) = state_ref(p) else {
+                p.recover("expected a state after `-->`");
+                return None;
+            };
+            let label = description(p);
+            Statement::Transition(Transition {
+                from,
+                to,
+                label,
+                span: Span::new(start, p.cur.pos()),
+            })
+        } else {
+            let StateRef::State(id) = from else {
+                p.error(from.span(), "expected `-->` after `[*]`");
+                p.cur.skip_line();
+                return None;
+            };
+            let mut classes = Vec::new();
+            while p.cur.eat(":::") {
+                let Some(class) = p.cur.ident() else {
+                    p.recover("expected a style class after `:::`");
+                    return None;
+                };
+                classes.push(class);
+            }
+            // `Idle : text` describes a state; with style classes attached
+            // it is a declaration like any other.
+            match description(p) {
+                Some(text) if classes.is_empty() => Statement::Description(Description {
+                    state: id,
+                    text,
+                    span: Span::new(start, p.cur.pos()),
+                }),
+                description => Statement::State(State {
+                    id,
+                    label: None,
+                    description,
+                    kind: None,
+                    classes,
+                    statements: Vec::new(),
+                    body: None,
+                    span: Span::new(start, p.cur.pos()),
+                }),
+            }
+        }
+    };
+    p.expect_statement_end();
+    Some(statement)
+}
+
+fn state_ref(p: &mut Parser) -> Option<StateRef> {
+    let start = p.cur.pos();
+    if p.cur.eat("[*]") {
+        return Some(StateRef::Terminal(Span::new(start, p.cur.pos())));
+    }
+    p.cur.ident().map(StateRef::State)
+}
+
+/// Reads an optional `: text` suffix.
+fn description(p: &mut Parser) -> Option<Label> {
+    p.cur.skip_blank();
+    if !p.cur.eat_char(':') {
+        return None;
+    }
+    p.cur.skip_blank();
+    let (text, span) = p.cur.rest_of_statement();
+    Some(Label {
+        text: text.to_string(),
+        quoted: false,
+        span,
+    })
+}
+
+fn state(p: &mut Parser, keyword: Span) -> Option<State> {
+    p.cur.skip_blank();
+    let mut label = None;
+    if let Some((text, span)) = p.cur.quoted() {
+        label = Some(Label {
+            text: text.to_string(),
+            quoted: true,
+            span,
+        });
+        p.cur.skip_blank();
+        if p.cur.eat_keyword("as").is_none() {
+            p.recover("expected `as` followed by the state id");
+            return None;
+        }
+        p.cur.skip_blank();
+    }
+    let Some(id) = p.cur.ident() else {
+        p.recover("expected a state id");
+        return None;
+    };
+
+    p.cur.skip_blank();
+    let mut kind = None;
+    if p.cur.starts_with("<<") {
+        let at = p.cur.pos();
+        let parsed = match p.cur.rest() {
+            rest if rest.starts_with("<<fork>>") => Some(StateKind::Fork),
+            rest if rest.starts_with("<<join>>") => Some(StateKind::Join),
+            rest if rest.starts_with("<<choice>>") => Some(StateKind::Choice),
+            _ => None,
+        };
+        let Some(parsed) = parsed else {
+            p.recover("expected `<<fork>>`, `<<join>>` or `<<choice>>`");
+            return None;
+        };
+        p.cur.take_until(">>");
+        p.cur.eat(">>");
+        kind = Some(Spanned::new(parsed, Span::new(at, p.cur.pos())));
+    }
+
+    let description = description(p);
+    p.cur.skip_blank();
+    let mut statements = Vec::new();
+    let mut body = None;
+    if description.is_none() && p.cur.peek() == Some('{') {
+        let open = Span::new(p.cur.pos(), p.cur.pos() + 1);
+        p.cur.bump();
+        p.expect_statement_end();
+        let (nested, close) = self::statements(p, true);
+        statements = nested;
+        match close {
+            Some(close) => body = Some(open.to(close)),
+            None => {
+                let fix = p.append_line_fix("}");
+                p.error_with_fix(open, "unclosed `{`", fix);
+                body = Some(Span::new(open.start, p.cur.pos()));
+            }
+        }
+    } else {
+        p.expect_statement_end();
+    }
+
+    let end = body.map_or(p.cur.pos(), |b| b.end);
+    Some(State {
+        id,
+        label,
+        description,
+        kind,
+        classes: Vec::new(),
+        statements,
+        body,
+        span: Span::new(keyword.start, end),
+    })
+}
+
+fn note(p: &mut Parser, start: usize) -> Option<Note> {
+    p.cur.skip_blank();
+    let side = if p.cur.eat_keyword("left").is_some() {
+        NoteSide::LeftOf
+    } else if p.cur.eat_keyword("right").is_some() {
+        NoteSide::RightOf
+    } else {
+        p.recover("expected `left of` or `right of`");
+        return None;
+    };
+    p.cur.skip_blank();
+    if p.cur.eat_keyword("of").is_none() {
+        p.recover("expected `of`");
+        return None;
+    }
+    p.cur.skip_blank();
+    let Some(state) = p.cur.ident() else {
+        p.recover("expected a state id");
+        return None;
+    };
+
+    if let Some(text) = description(p) {
+        p.expect_statement_end();
+        return Some(Note {
+            side,
+            state,
+            text,
+            span: Span::new(start, p.cur.pos()),
+        });
+    }
+
+    // Multi-line form: every line up to `end note` is note text.
+    p.expect_statement_end();
+    let mut lines = Vec::new();
+    let mut text_span: Option<Span> = None;
+    let end = loop {
+        if p.cur.is_eof() {
+            p.error(
+                Span::new(start, start + 4),
+                "`note` is never closed with `end note`",
+            );
+            break p.cur.pos();
+        }
+        let (line, span) = p.cur.rest_of_line();
+        p.cur.skip_line();
+        if line == "end note" {
+            break span.end;
+        }
+        if !line.is_empty() {
+            lines.push(line);
+            text_span = Some(text_span.map_or(span, |s| s.to(span)));
+        }
+    };
+    Some(Note {
+        side,
+        state,
+        text: Label {
+            text: lines.join("\n"),
+            quoted: false,
+            span: text_span.unwrap_or(Span::new(end, end)),
+        },
+        span: Span::new(start, end),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse, Diagram};
+
+    fn statements(source: &str) -> Vec<Statement> {
+        let parsed = parse(source);
+        assert!(parsed.errors.is_empty(), "{source}: {:?}", parsed.errors);
+        match parsed.document.map(|document| document.diagram) {
+            Some(Diagram::State(diagram)) => diagram.statements,
+            other => panic!("not a state diagram: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn transitions() {
+        let statements = statements(
+            "stateDiagram-v2\n  [*] --> Idle\n  Idle --> Active : start\n  Active --> [*]\n",
+        );
+        let transitions: Vec<_> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Transition(t) => (
+                    matches!(t.from, StateRef::Terminal(_)),
+                    matches!(t.to, StateRef::Terminal(_)),
+                    t.label.as_ref().map(|l| l.text.as_str()),
+                ),
+                other => panic!("not a transition: {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            transitions,
+            [
+                (true, false, None),
+                (false, false, Some("start")),
+                (false, true, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn states() {
+        let statements = statements(
+            "stateDiagram\n  state \"Waiting for input\" as Idle\n  state Split <<fork>>\n  Idle : waiting\n  Busy:::alert\n  state Active {\n    [*] --> Running\n    --\n    [*] --> Logging\n  }\n",
+        );
+        let Statement::State(idle) = &statements[0] else {
+            panic!("not a state");
+        };
+        assert_eq!(idle.id.value, "Idle");
+        assert_eq!(idle.label.as_ref().unwrap().text, "Waiting for input");
+        let Statement::State(split) = &statements[1] else {
+            panic!("not a state");
+        };
+        assert_eq!(split.kind.as_ref().unwrap().value, StateKind::Fork);
+        assert!(matches!(&statements[2], Statement::Description(d) if d.text.text == "waiting"));
+        let Statement::State(busy) = &statements[3] else {
+            panic!("not a state");
+        };
+        assert_eq!(busy.classes[0].value, "alert");
+        let Statement::State(active) = &statements[4] else {
+            panic!("not a state");
+        };
+        assert!(active.body.is_some());
+        assert_eq!(active.statements.len(), 3);
+        assert!(matches!(active.statements[1], Statement::Concurrency(_)));
+    }
+
+    #[test]
+    fn notes() {
+        let statements = statements(
+            "stateDiagram-v2\n  note right of Idle : short\n  note left of Idle\n    first\n    second\n  end note\n",
+        );
+        let notes: Vec<_> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Note(note) => (note.side, note.text.text.as_str()),
+                other => panic!("not a note: {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (NoteSide::RightOf, "short"),
+                (NoteSide::LeftOf, "first\nsecond")
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for (source, message) in [
+            ("stateDiagram-v2\n  state A {\n    B\n", "unclosed `{`"),
+            ("stateDiagram-v2\n  }\n", "unmatched `}`"),
+            ("stateDiagram-v2\n  [*]\n", "expected `-->` after `[*]`"),
+            (
+                "stateDiagram-v2\n  state S <<split>>\n",
+                "expected `<<fork>>`, `<<join>>` or `<<choice>>`",
+            ),
+            (
+                "stateDiagram-v2\n  note left of A\n    text\n",
+                "`note` is never closed with `end note`",
+            ),
+            (
+                "stateDiagram-v2\n  note above A : x\n",
+                "expected `left of` or `right of`",
+            ),
+        ] {
+            assert_eq!(parse(source).errors[0].message, message, "{source}");
+        }
+    }
+}
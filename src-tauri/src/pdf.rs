@@ -0,0 +1,347 @@
+//! SVG to vector PDF, one diagram per page, for printing and `bmermaid
+//! render -f pdf`.
+
+use std::str::FromStr;
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref};
+use serde::Deserialize;
+
+use crate::svg;
+
+/// PDF points per SVG user unit, which is a CSS pixel.
+const POINTS_PER_PX: f32 = 72.0 / 96.0;
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+const DIAGRAM: Name = Name(b"D1");
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageSize {
+    A4,
+    Letter,
+    /// Each page is as large as its diagram plus the margins.
+    Diagram,
+    /// Width and height in points.
+    Custom {
+        width: f32,
+        height: f32,
+    },
+}
+
+/// How a diagram is scaled onto its page.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Fit {
+    /// Natural size, which may overflow the page.
+    None,
+    /// Scaled down to fit inside the margins, never up.
+    Shrink,
+    /// Scaled up or down to fill the space inside the margins.
+    Page,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Align {
+    Center,
+    TopLeft,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    /// Turns the page so it is wider than tall.
+    pub landscape: bool,
+    /// Space kept free on every side, in points.
+    pub margin: f32,
+    pub fit: Fit,
+    pub align: Align,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            page_size: PageSize::A4,
+            landscape: false,
+            margin: 10.0 * POINTS_PER_MM,
+            fit: Fit::Shrink,
+            align: Align::Center,
+        }
+    }
+}
+
+impl PdfOptions {
+    /// Rejects a page the margins leave no room on.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.margin.is_finite() && self.margin >= 0.0) {
+            return Err(format!("invalid margin {}", self.margin));
+        }
+        if !matches!(self.page_size, PageSize::Diagram) {
+            let (width, height) = self.page((0.0, 0.0));
+            let usable = |side: f32| side.is_finite() && side > 2.0 * self.margin;
+            if !(usable(width) && usable(height)) {
+                return Err("the margins leave no room on the page".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Page width and height in points for a diagram of `size` points.
+    fn page(&self, size: (f32, f32)) -> (f32, f32) {
+        let (width, height) = match self.page_size {
+            PageSize::A4 => (210.0 * POINTS_PER_MM, 297.0 * POINTS_PER_MM),
+            PageSize::Letter => (612.0, 792.0),
+            PageSize::Custom { width, height } => (width, height),
+            PageSize::Diagram => {
+                return (size.0 + 2.0 * self.margin, size.1 + 2.0 * self.margin);
+            }
+        };
+        if self.landscape {
+            (width.max(height), width.min(height))
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Where a diagram of `size` points goes on a `page`: x, y from the
+    /// bottom left, width and height.
+    fn place(&self, size: (f32, f32), page: (f32, f32)) -> [f32; 4] {
+        let room = (page.0 - 2.0 * self.margin, page.1 - 2.0 * self.margin);
+        let fit = (room.0 / size.0).min(room.1 / size.1);
+        let scale = match self.fit {
+            Fit::None => 1.0,
+            Fit::Shrink => fit.min(1.0),
+            Fit::Page => fit,
+        };
+        let (width, height) = (size.0 * scale, size.1 * scale);
+        match self.align {
+            Align::Center => [
+                self.margin + (room.0 - width) / 2.0,
+                self.margin + (room.1 - height) / 2.0,
+                width,
+                height,
+            ],
+            Align::TopLeft => [self.margin, page.1 - self.margin - height, width, height],
+        }
+    }
+}
+
+/// Converts each SVG into a page of one PDF, keeping text and shapes as
+/// vectors.
+pub fn pdf<S: AsRef<str>>(svgs: &[S], options: &PdfOptions) -> Result<Vec<u8>, String> {
+    options.validate()?;
+    if svgs.is_empty() {
+        return Err("no diagrams to export".to_string());
+    }
+
+    let mut next = Ref::new(1);
+    let catalog = next.bump();
+    let page_tree = next.bump();
+    let mut pdf = Pdf::new();
+    let mut pages = Vec::new();
+    for svg in svgs {
+        let tree = svg::tree(svg.as_ref())?;
+        let page = next.bump();
+        let content = next.bump();
+        let diagram = next.bump();
+        next = svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, diagram);
+
+        let size = (
+            tree.size.width() * POINTS_PER_PX,
+            tree.size.height() * POINTS_PER_PX,
+        );
+        let (width, height) = options.page(size);
+        let [x, y, w, h] = options.place(size, (width, height));
+
+        let mut stream = Content::new();
+        stream
+            .save_state()
+            .transform([w, 0.0, 0.0, h, x, y])
+            .x_object(DIAGRAM)
+            .restore_state();
+        pdf.stream(content, &stream.finish());
+
+        let mut writer = pdf.page(page);
+        writer.media_box(Rect::new(0.0, 0.0, width, height));
+        writer.parent(page_tree);
+        writer.contents(content);
+        writer.resources().x_objects().pair(DIAGRAM, diagram);
+        writer.finish();
+        pages.push(page);
+    }
+
+    pdf.catalog(catalog).pages(page_tree);
+    pdf.pages(page_tree).count(pages.len() as i32).kids(pages);
+    Ok(pdf.finish())
+}
+
+impl FromStr for PageSize {
+    type Err = String;
+
+    /// `a4`, `letter`, `diagram`, or `<width>x<height>` with a unit as in
+    /// [`parse_length`], e.g. `148x210mm`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "a4" => Ok(PageSize::A4),
+            "letter" => Ok(PageSize::Letter),
+            "diagram" => Ok(PageSize::Diagram),
+            custom => {
+                let invalid = || format!("invalid page size `{text}`");
+                let (width, height) = custom.split_once('x').ok_or_else(invalid)?;
+                // A unit written once applies to both sides.
+                let unit = height.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                let width = if width.ends_with(|c: char| c.is_ascii_digit()) {
+                    format!("{width}{unit}")
+                } else {
+                    width.to_string()
+                };
+                Ok(PageSize::Custom {
+                    width: parse_length(&width).map_err(|_| invalid())?,
+                    height: parse_length(height).map_err(|_| invalid())?,
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for Fit {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "none" => Ok(Fit::None),
+            "shrink" => Ok(Fit::Shrink),
+            "page" => Ok(Fit::Page),
+            _ => Err(format!("invalid fit `{text}`")),
+        }
+    }
+}
+
+impl FromStr for Align {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "center" => Ok(Align::Center),
+            "top-left" => Ok(Align::TopLeft),
+            _ => Err(format!("invalid alignment `{text}`")),
+        }
+    }
+}
+
+/// A length in points from `10mm`, `1cm`, `0.5in`, `12pt` or `16px`; plain
+/// numbers are millimetres.
+pub fn parse_length(text: &str) -> Result<f32, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f32 = number
+        .parse()
+        .map_err(|_| format!("invalid length `{text}`"))?;
+    let points = match unit.trim() {
+        "" | "mm" => number * POINTS_PER_MM,
+        "cm" => number * 10.0 * POINTS_PER_MM,
+        "in" => number * 72.0,
+        "pt" => number,
+        "px" => number * POINTS_PER_PX,
+        _ => return Err(format!("invalid length `{text}`")),
+    };
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn lengths() {
+        for (text, points) in [
+            ("10mm", 28.35),
+            ("10", 28.35),
+            ("1cm", 28.35),
+            ("0.5in", 36.0),
+            (" 12pt ", 12.0),
+            ("16px", 12.0),
+        ] {
+            let parsed = parse_length(text).unwrap();
+            assert!(close(parsed, points), "{text}: {parsed}");
+        }
+        for text in ["", "mm", "10 furlongs", "-5mm", "1.2.3cm"] {
+            assert_eq!(
+                parse_length(text),
+                Err(format!("invalid length `{}`", text.trim()))
+            );
+        }
+    }
+
+    #[test]
+    fn page_sizes() {
+        assert_eq!("A4".parse(), Ok(PageSize::A4));
+        assert_eq!("letter".parse(), Ok(PageSize::Letter));
+        assert_eq!("Diagram".parse(), Ok(PageSize::Diagram));
+
+        for text in ["148x210mm", "148mmx210mm", "14.8x21cm"] {
+            let Ok(PageSize::Custom { width, height }) = text.parse() else {
+                panic!("{text} is not a custom size");
+            };
+            assert!(close(width, 419.53) && close(height, 595.28), "{text}");
+        }
+        let Ok(PageSize::Custom { width, height }) = "8.5inx11in".parse() else {
+            panic!("not a custom size");
+        };
+        assert_eq!((width, height), (612.0, 792.0));
+
+        for text in ["a5", "148", "148x", "x210", "148x210furlongs"] {
+            assert_eq!(
+                text.parse::<PageSize>(),
+                Err(format!("invalid page size `{text}`"))
+            );
+        }
+    }
+
+    #[test]
+    fn validation() {
+        assert!(PdfOptions::default().validate().is_ok());
+        let options = |page_size, margin| PdfOptions {
+            page_size,
+            margin,
+            ..PdfOptions::default()
+        };
+        assert!(options(PageSize::A4, -1.0).validate().is_err());
+        assert!(options(PageSize::Letter, 306.0).validate().is_err());
+        assert!(options(PageSize::Diagram, 1000.0).validate().is_ok());
+    }
+
+    #[test]
+    fn placement() {
+        let options = PdfOptions {
+            page_size: PageSize::Letter,
+            landscape: true,
+            margin: 0.0,
+            fit: Fit::Page,
+            align: Align::TopLeft,
+        };
+        let page = options.page((0.0, 0.0));
+        assert_eq!(page, (792.0, 612.0));
+        assert_eq!(
+            options.place((100.0, 50.0), page),
+            [0.0, 216.0, 792.0, 396.0]
+        );
+    }
+
+    #[test]
+    fn one_page_per_diagram() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="10" height="10"/></svg>"#;
+        let pdf = pdf(&[svg, svg], &PdfOptions::default()).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Count 2"), "{text}");
+    }
+}
@@ -0,0 +1,856 @@
+//! Post-processing of exported SVGs, so several can share an HTML page and
+//! they look the same on every machine.
+//!
+//! Each step edits the SVG text in place and leaves everything else as the
+//! renderer wrote it, in this order:
+//!
+//! - `styles` copies the rules of the `<style>` elements onto the elements
+//!   they match, or drops them. At-rules and rules that only match in a
+//!   browser, like `:hover`, are lost either way;
+//! - `embed-fonts` embeds the editor's sans-serif font as a subset of the
+//!   characters the diagram uses, and sets it on all text that does not
+//!   name a font of its own. The `@font-face` rules need a `<style>`
+//!   element, so it cannot be combined with stripped styles;
+//! - `prefix-ids` and `id-prefix` prefix every `id` and the references to
+//!   it;
+//! - `minify` drops comments and the whitespace between tags and in style
+//!   sheets.
+
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+use std::str::FromStr;
+
+use base64::Engine;
+use resvg::usvg::fontdb;
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+
+use crate::{subset, svg};
+
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+/// Elements whose whitespace, their descendants' included, is content:
+/// `foreignObject` holds the HTML of labels, where it separates words.
+const TEXT_ELEMENTS: &[&str] = &[
+    "text",
+    "tspan",
+    "textPath",
+    "title",
+    "desc",
+    "foreignObject",
+];
+
+/// What happens to `<style>` elements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Styles {
+    #[default]
+    Keep,
+    /// Moved into the `style` attributes of the elements they match.
+    Inline,
+    Strip,
+}
+
+/// The `[export]` section of `.bmermaid.toml` and the matching `render`
+/// flags. Applies to SVG output only.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct PostProcessOptions {
+    pub minify: bool,
+    /// Prefix ids with a namespace derived from the diagram, so different
+    /// diagrams never share one.
+    pub prefix_ids: bool,
+    /// Prefix ids with this namespace instead.
+    pub id_prefix: Option<String>,
+    pub styles: Styles,
+    pub embed_fonts: bool,
+}
+
+impl PostProcessOptions {
+    /// Rejects an id prefix that would not make valid ids, and embedded
+    /// fonts without the style sheet they live in.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(prefix) = self.id_prefix.as_deref().filter(|prefix| !is_name(prefix)) {
+            return Err(format!("invalid id prefix `{prefix}`"));
+        }
+        if self.embed_fonts && self.styles == Styles::Strip {
+            return Err(
+                "`embed-fonts` needs a `<style>` element, which `styles = \"strip\"` removes"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The namespace ids in `svg` get.
+    fn prefix(&self, svg: &str) -> Option<String> {
+        self.id_prefix.clone().or_else(|| {
+            self.prefix_ids
+                .then(|| format!("m{:08x}-", crc32fast::hash(svg.as_bytes())))
+        })
+    }
+}
+
+impl FromStr for Styles {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "keep" => Ok(Styles::Keep),
+            "inline" => Ok(Styles::Inline),
+            "strip" => Ok(Styles::Strip),
+            _ => Err(format!("invalid styles `{text}`")),
+        }
+    }
+}
+
+/// Applies the steps `options` enables to `svg`.
+pub fn postprocess(svg: &str, options: &PostProcessOptions) -> Result<String, String> {
+    options.validate()?;
+    let prefix = options.prefix(svg);
+    let mut svg = match options.styles {
+        Styles::Keep => svg.to_string(),
+        Styles::Inline => inline_styles(svg)?,
+        Styles::Strip => strip_styles(svg)?,
+    };
+    if options.embed_fonts {
+        svg = embed_fonts(&svg, options.styles == Styles::Inline)?;
+    }
+    if let Some(prefix) = prefix {
+        svg = prefix_ids(&svg, &prefix)?;
+    }
+    if options.minify {
+        svg = minify(&svg)?;
+    }
+    Ok(svg)
+}
+
+fn strip_styles(svg: &str) -> Result<String, String> {
+    let document = parse(svg)?;
+    let edits = document
+        .descendants()
+        .filter(is_style)
+        .map(|node| (node.range(), String::new()))
+        .collect();
+    Ok(apply(svg, edits))
+}
+
+fn inline_styles(svg: &str) -> Result<String, String> {
+    let document = parse(svg)?;
+    let styles: Vec<_> = document.descendants().filter(is_style).collect();
+    let css: Vec<String> = styles.iter().map(|node| text_content(*node)).collect();
+    let mut rules = Vec::new();
+    for css in &css {
+        rules.extend(css_rules(css));
+    }
+    // Later and more specific rules win, so they go last.
+    rules.sort_by_key(|(selector, _)| selector.specificity());
+
+    let mut edits: Vec<_> = styles
+        .iter()
+        .map(|node| (node.range(), String::new()))
+        .collect();
+    for node in document.descendants().filter(Node::is_element) {
+        if node.ancestors().any(|node| is_style(&node)) {
+            continue;
+        }
+        let declarations: Vec<&str> = rules
+            .iter()
+            .filter(|(selector, _)| selector.matches(&Element(node)))
+            .flat_map(|(_, declarations)| declarations.iter().copied())
+            .collect();
+        if declarations.is_empty() {
+            continue;
+        }
+        // The element's own style attribute still wins.
+        let mut style = declarations.join(";");
+        match node
+            .attributes()
+            .find(|a| a.name() == "style" && a.namespace().is_none())
+        {
+            Some(attribute) => {
+                style.push(';');
+                style.push_str(attribute.value());
+                edits.push((value_range(svg, &attribute), escape_attribute(&style)));
+            }
+            None => {
+                let at = node.range().start + 1 + tag_name_len(svg, node);
+                edits.push((at..at, format!(" style=\"{}\"", escape_attribute(&style))));
+            }
+        }
+    }
+    Ok(apply(svg, edits))
+}
+
+/// The rules of `css` as one selector each with its declarations. At-rules
+/// and selectors simplecss cannot match are skipped; its own parser would
+/// drop custom properties.
+fn css_rules(css: &str) -> Vec<(simplecss::Selector<'_>, Vec<&str>)> {
+    let mut rules = Vec::new();
+    let mut rest = css;
+    loop {
+        rest = skip_comments(rest);
+        let Some(open) = find_outside(rest, |c| c == '{' || c == ';') else {
+            break;
+        };
+        let prelude = rest[..open].trim();
+        if rest[open..].starts_with(';') {
+            rest = &rest[open + 1..];
+            continue;
+        }
+        let body_start = open + 1;
+        let Some(len) = block_len(&rest[body_start..]) else {
+            break;
+        };
+        let body = &rest[body_start..body_start + len];
+        rest = &rest[body_start + len + 1..];
+        if prelude.starts_with('@') {
+            continue;
+        }
+
+        let mut declarations = Vec::new();
+        let mut remaining = body;
+        while !remaining.is_empty() {
+            let end = find_outside(remaining, |c| c == ';').unwrap_or(remaining.len());
+            let declaration = skip_comments(remaining[..end].trim()).trim();
+            if declaration.contains(':') {
+                declarations.push(declaration);
+            }
+            remaining = remaining.get(end + 1..).unwrap_or_default();
+        }
+        for selector in split_args(prelude) {
+            if let Some(selector) = simplecss::Selector::parse(selector.trim()) {
+                rules.push((selector, declarations.clone()));
+            }
+        }
+    }
+    rules
+}
+
+/// `css` after the comments and whitespace it starts with.
+fn skip_comments(mut css: &str) -> &str {
+    loop {
+        css = css.trim_start();
+        match css.strip_prefix("/*") {
+            Some(comment) => css = comment.find("*/").map_or("", |end| &comment[end + 2..]),
+            None => return css,
+        }
+    }
+}
+
+/// The offset of the first character `stop` accepts outside strings and
+/// parentheses.
+fn find_outside(css: &str, stop: impl Fn(char) -> bool) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0;
+    for (i, c) in css.char_indices() {
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('"' | '\'', None) => quote = Some(c),
+            ('(', None) => depth += 1,
+            (')', None) => depth -= 1,
+            (c, None) if depth == 0 && stop(c) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The length of a block's contents up to its closing brace, nested blocks
+/// included.
+fn block_len(css: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut rest = 0;
+    while let Some(i) = find_outside(&css[rest..], |c| c == '{' || c == '}') {
+        let at = rest + i;
+        match &css[at..at + 1] {
+            "{" => depth += 1,
+            _ if depth == 0 => return Some(at),
+            _ => depth -= 1,
+        }
+        rest = at + 1;
+    }
+    None
+}
+
+/// Splits on the commas outside parentheses.
+fn split_args(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(comma) = find_outside(rest, |c| c == ',') {
+        parts.push(&rest[..comma]);
+        rest = &rest[comma + 1..];
+    }
+    parts.push(rest);
+    parts
+}
+
+/// Adds a style sheet with the font as `@font-face` rules, one per weight
+/// the document uses, and sets it on the `<text>` elements without a
+/// `font-family` of their own: with a rule ahead of the diagram's own
+/// style sheets, or, when styles are `inline`, in their `style`
+/// attributes. The family is named after the subset, so SVGs with
+/// different subsets can share a page.
+fn embed_fonts(svg: &str, inline: bool) -> Result<String, String> {
+    let document = parse(svg)?;
+    let chars: BTreeSet<char> = document
+        .descendants()
+        .filter(|node| node.is_text() && node.ancestors().any(|a| a.has_tag_name("text")))
+        .flat_map(|node| node.text().unwrap_or_default().chars())
+        .collect();
+    let Some(first) = document.root_element().first_child() else {
+        return Ok(svg.to_string());
+    };
+    if chars.is_empty() {
+        return Ok(svg.to_string());
+    }
+
+    let fonts = svg::fonts();
+    let mut faces = Vec::new();
+    for weight in font_weights(svg) {
+        let query = fontdb::Query {
+            families: &[fontdb::Family::SansSerif],
+            weight: fontdb::Weight(weight),
+            ..fontdb::Query::default()
+        };
+        if let Some(id) = fonts.query(&query).filter(|id| !faces.contains(id)) {
+            faces.push(id);
+        }
+    }
+    if faces.is_empty() {
+        return Err("no font installed to embed".to_string());
+    }
+
+    let mut subsets = Vec::new();
+    let mut hasher = crc32fast::Hasher::new();
+    for id in faces {
+        let face = fonts.face(id).ok_or("no font installed to embed")?;
+        let family = face
+            .families
+            .first()
+            .map_or("the sans-serif font", |(name, _)| name.as_str());
+        let data = fonts
+            .with_face_data(id, |data, index| subset::subset(data, index, &chars))
+            .ok_or_else(|| format!("cannot read {family}"))?
+            .map_err(|e| format!("cannot embed {family}: {e}"))?;
+        hasher.update(&data);
+        subsets.push((face.weight.0, data));
+    }
+
+    let family = format!("bmermaid-{:08x}", hasher.finalize());
+    let mut css = String::new();
+    for (weight, data) in subsets {
+        css.push_str(&format!(
+            "@font-face{{font-family:\"{family}\";font-weight:{weight};\
+             src:url(data:font/ttf;base64,{}) format(\"truetype\")}}",
+            base64::engine::general_purpose::STANDARD.encode(data)
+        ));
+    }
+    let font = format!("font-family:\"{family}\",sans-serif");
+    let at = first.range().start;
+    let mut edits = Vec::new();
+    if inline {
+        for node in document
+            .descendants()
+            .filter(|node| node.has_tag_name("text") && !sets_font_family(node))
+        {
+            match node
+                .attributes()
+                .find(|a| a.name() == "style" && a.namespace().is_none())
+            {
+                Some(attribute) => {
+                    let style = format!("{font};{}", attribute.value());
+                    edits.push((value_range(svg, &attribute), escape_attribute(&style)));
+                }
+                None => {
+                    let at = node.range().start + 1 + tag_name_len(svg, node);
+                    edits.push((at..at, format!(" style=\"{}\"", escape_attribute(&font))));
+                }
+            }
+        }
+    } else {
+        css.push_str(&format!("text:not([font-family]){{{font}}}"));
+    }
+    edits.push((at..at, format!("<style>{css}</style>")));
+    Ok(apply(svg, edits))
+}
+
+/// Whether `node` names its font in a `font-family` attribute or its
+/// `style` attribute.
+fn sets_font_family(node: &Node) -> bool {
+    node.has_attribute("font-family")
+        || node.attribute("style").is_some_and(|style| {
+            style.split(';').any(|declaration| {
+                declaration
+                    .split_once(':')
+                    .is_some_and(|(property, _)| property.trim() == "font-family")
+            })
+        })
+}
+
+/// The weights `font-weight` is set to in attributes and style sheets, and
+/// the normal weight.
+fn font_weights(svg: &str) -> BTreeSet<u16> {
+    let mut weights = BTreeSet::from([400]);
+    for (at, property) in svg.match_indices("font-weight") {
+        let value = svg[at + property.len()..]
+            .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, ':' | '=' | '"' | '\''));
+        let end = value
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(value.len());
+        let weight = match &value[..end] {
+            "normal" => 400,
+            "bold" => 700,
+            number => match number.parse() {
+                Ok(weight @ 1..=1000) => weight,
+                _ => continue,
+            },
+        };
+        weights.insert(weight);
+    }
+    weights
+}
+
+fn prefix_ids(svg: &str, prefix: &str) -> Result<String, String> {
+    let document = parse(svg)?;
+    let ids: HashSet<&str> = document
+        .descendants()
+        .filter_map(|node| node.attribute("id"))
+        .collect();
+    if ids.is_empty() {
+        return Ok(svg.to_string());
+    }
+    let rename = |id: &str| ids.contains(id).then(|| format!("{prefix}{id}"));
+
+    let mut edits = Vec::new();
+    for node in document.descendants().filter(Node::is_element) {
+        if is_style(&node) {
+            let css = text_content(node);
+            if let Some(css) = rename_hashes(&css, rename) {
+                edits.push(replace_style(svg, node, &css));
+            }
+            continue;
+        }
+        for attribute in node.attributes() {
+            let value = attribute.value();
+            let renamed = match (attribute.namespace(), attribute.name()) {
+                (None, "id") => rename(value),
+                (None | Some(XLINK_NAMESPACE), "href") => value
+                    .strip_prefix('#')
+                    .and_then(rename)
+                    .map(|id| format!("#{id}")),
+                (None, "aria-labelledby" | "aria-describedby") => {
+                    let renamed: Vec<String> = value
+                        .split_whitespace()
+                        .map(|id| rename(id).unwrap_or_else(|| id.to_string()))
+                        .collect();
+                    Some(renamed.join(" ")).filter(|renamed| renamed != value)
+                }
+                _ => rename_urls(value, rename),
+            };
+            if let Some(renamed) = renamed {
+                edits.push((value_range(svg, &attribute), escape_attribute(&renamed)));
+            }
+        }
+    }
+    Ok(apply(svg, edits))
+}
+
+/// `value` with the ids in its `url(#id)` references renamed, if any were.
+fn rename_urls(value: &str, rename: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut renamed = false;
+    while let Some(start) = rest.find("url(") {
+        let args_start = start + "url(".len();
+        let Some(len) = rest[args_start..].find(')') else {
+            break;
+        };
+        let target = rest[args_start..args_start + len]
+            .trim_matches(|c: char| c.is_whitespace() || c == '"' || c == '\'');
+        out.push_str(&rest[..args_start]);
+        match target.strip_prefix('#').and_then(&rename) {
+            Some(id) => {
+                out.push('#');
+                out.push_str(&id);
+                renamed = true;
+            }
+            None => out.push_str(&rest[args_start..args_start + len]),
+        }
+        rest = &rest[args_start + len..];
+    }
+    out.push_str(rest);
+    renamed.then_some(out)
+}
+
+/// `css` with `#id` selectors and `url(#id)` references renamed, if any
+/// were. Colours are left alone unless an element has the same id.
+fn rename_hashes(css: &str, rename: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    let mut renamed = false;
+    while let Some(start) = rest.find('#') {
+        let name = &rest[start + 1..];
+        let len = name
+            .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(name.len());
+        out.push_str(&rest[..start + 1]);
+        match rename(&name[..len]) {
+            Some(id) => {
+                out.push_str(&id);
+                renamed = true;
+            }
+            None => out.push_str(&name[..len]),
+        }
+        rest = &name[len..];
+    }
+    out.push_str(rest);
+    renamed.then_some(out)
+}
+
+fn minify(svg: &str) -> Result<String, String> {
+    let document = parse(svg)?;
+    let mut edits = Vec::new();
+    for node in document.descendants() {
+        // Style sheets are rewritten as a whole.
+        if node.ancestors().skip(1).any(|node| is_style(&node)) {
+            continue;
+        }
+        let whitespace = node.is_text()
+            && node.text().is_some_and(|text| text.trim().is_empty())
+            && !node
+                .ancestors()
+                .any(|a| TEXT_ELEMENTS.contains(&a.tag_name().name()));
+        if node.is_comment() || whitespace {
+            edits.push((node.range(), String::new()));
+        } else if is_style(&node) {
+            edits.push(replace_style(svg, node, &minify_css(&text_content(node))));
+        }
+    }
+    Ok(apply(svg, edits))
+}
+
+/// `css` without comments and the whitespace around punctuation. Strings
+/// are kept as they are.
+fn minify_css(css: &str) -> String {
+    // Spaces before `:` stay: `g :first-child` is not `g:first-child`.
+    const PUNCTUATION: [char; 5] = ['{', '}', ';', ',', '>'];
+    let mut out = String::with_capacity(css.len());
+    let mut chars = css.chars().peekable();
+    let mut space = false;
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            let mut last = ' ';
+            for next in chars.by_ref() {
+                if last == '*' && next == '/' {
+                    break;
+                }
+                last = next;
+            }
+            space = true;
+            continue;
+        }
+        if PUNCTUATION.contains(&c) {
+            if c == '}' && out.ends_with(';') {
+                out.pop();
+            }
+        } else if space && !out.is_empty() && !out.ends_with(PUNCTUATION) && !out.ends_with(':') {
+            out.push(' ');
+        }
+        space = false;
+        out.push(c);
+        if c == '"' || c == '\'' {
+            for next in chars.by_ref() {
+                out.push(next);
+                if next == c {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+fn parse(svg: &str) -> Result<Document<'_>, String> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    Document::parse_with_options(svg, options).map_err(|e| format!("invalid SVG: {e}"))
+}
+
+fn is_style(node: &Node) -> bool {
+    node.is_element() && node.tag_name().name() == "style"
+}
+
+fn text_content(node: Node) -> String {
+    node.descendants()
+        .filter(Node::is_text)
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+/// An edit giving the style element `node` the style sheet `css`.
+fn replace_style(svg: &str, node: Node, css: &str) -> (Range<usize>, String) {
+    let range = node.range();
+    let start_tag = &svg[range.start..start_tag_end(svg, node)];
+    let start_tag = start_tag
+        .strip_suffix("/>")
+        .map_or(start_tag.to_string(), |tag| format!("{tag}>"));
+    let name = &svg[range.start + 1..range.start + 1 + tag_name_len(svg, node)];
+    (range, format!("{start_tag}{}</{name}>", escape_text(css)))
+}
+
+/// The length of the name in the start tag of the element `node`.
+fn tag_name_len(svg: &str, node: Node) -> usize {
+    let name = &svg[node.range().start + 1..];
+    name.find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(name.len())
+}
+
+/// The offset just past the start tag of the element `node`.
+fn start_tag_end(svg: &str, node: Node) -> usize {
+    let start = node.range().start;
+    let mut quote = None;
+    for (i, c) in svg[start..].char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) => return start + i + 1,
+            _ => {}
+        }
+    }
+    svg.len()
+}
+
+/// The range of `attribute`'s value between its quotes.
+fn value_range(svg: &str, attribute: &roxmltree::Attribute) -> Range<usize> {
+    let at = attribute.position();
+    // The document parsed, so the value is quoted.
+    let quote_at = at + svg[at..].find(['"', '\'']).unwrap();
+    let quote = &svg[quote_at..quote_at + 1];
+    let start = quote_at + 1;
+    start..start + svg[start..].find(quote).unwrap()
+}
+
+/// Applies edits that replace non-overlapping ranges of `text`.
+fn apply(text: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut out = String::with_capacity(text.len());
+    let mut at = 0;
+    for (range, replacement) in edits {
+        out.push_str(&text[at..range.start]);
+        out.push_str(&replacement);
+        at = range.end;
+    }
+    out.push_str(&text[at..]);
+    out
+}
+
+fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;")
+}
+
+/// Whether `name` can start an XML name: a letter or `_`, then letters,
+/// digits, `-`, `_` or `.`.
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// An element for matching style sheet selectors.
+struct Element<'a, 'input>(Node<'a, 'input>);
+
+impl simplecss::Element for Element<'_, '_> {
+    fn parent_element(&self) -> Option<Self> {
+        self.0.parent_element().map(Element)
+    }
+
+    fn prev_sibling_element(&self) -> Option<Self> {
+        self.0.prev_sibling_element().map(Element)
+    }
+
+    fn has_local_name(&self, name: &str) -> bool {
+        self.0.tag_name().name() == name
+    }
+
+    fn attribute_matches(&self, name: &str, operator: simplecss::AttributeOperator) -> bool {
+        self.0
+            .attribute(name)
+            .is_some_and(|value| operator.matches(value))
+    }
+
+    /// Only `:first-child` holds in a static image.
+    fn pseudo_class_matches(&self, class: simplecss::PseudoClass) -> bool {
+        class == simplecss::PseudoClass::FirstChild && self.0.prev_sibling_element().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation() {
+        let options = |styles, embed_fonts| PostProcessOptions {
+            styles,
+            embed_fonts,
+            ..PostProcessOptions::default()
+        };
+        assert!(options(Styles::Keep, true).validate().is_ok());
+        assert!(options(Styles::Inline, true).validate().is_ok());
+        assert!(options(Styles::Strip, false).validate().is_ok());
+        assert_eq!(
+            options(Styles::Strip, true).validate(),
+            Err(
+                "`embed-fonts` needs a `<style>` element, which `styles = \"strip\"` removes"
+                    .to_string()
+            )
+        );
+
+        let prefixed = PostProcessOptions {
+            id_prefix: Some("1-".to_string()),
+            ..PostProcessOptions::default()
+        };
+        assert_eq!(
+            prefixed.validate(),
+            Err("invalid id prefix `1-`".to_string())
+        );
+    }
+
+    #[test]
+    fn prefixes_ids_and_references() {
+        let svg = concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" aria-labelledby="t other">"##,
+            r##"<title id="t">T</title><style>#a{fill:#fff}#fff{stroke:red}</style>"##,
+            r##"<marker id="a"/><g id="fff"/><path marker-end="url(#a)" style="fill:url('#a')"/>"##,
+            r##"<use href="#a"/><use xlink:href="#missing"/></svg>"##,
+        );
+        assert_eq!(
+            prefix_ids(svg, "p-").unwrap(),
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" aria-labelledby="p-t other">"##,
+                r##"<title id="p-t">T</title><style>#p-a{fill:#p-fff}#p-fff{stroke:red}</style>"##,
+                r##"<marker id="p-a"/><g id="p-fff"/><path marker-end="url(#p-a)" style="fill:url(#p-a)"/>"##,
+                r##"<use href="#p-a"/><use xlink:href="#missing"/></svg>"##,
+            )
+        );
+        assert_eq!(
+            prefix_ids("<svg><g/></svg>", "p-").unwrap(),
+            "<svg><g/></svg>"
+        );
+    }
+
+    #[test]
+    fn renames_urls_and_hashes() {
+        let rename = |id: &str| (id == "a").then(|| "x-a".to_string());
+        assert_eq!(
+            rename_urls("url(#a) url( \"#b\" ) url(#a", rename).as_deref(),
+            Some("url(#x-a) url( \"#b\" ) url(#a")
+        );
+        assert_eq!(rename_urls("url(#b)", rename), None);
+        assert_eq!(
+            rename_hashes("#a, #ab > #a-b {fill:#a}", rename).as_deref(),
+            Some("#x-a, #ab > #a-b {fill:#x-a}")
+        );
+        assert_eq!(rename_hashes("#abc{}", rename), None);
+    }
+
+    #[test]
+    fn inlines_styles() {
+        let svg = concat!(
+            r#"<svg><style>/* c */ rect{fill:red;stroke:blue} .a{fill:green} @media print{rect{fill:black}} "#,
+            r#"rect:hover{fill:pink}</style><rect class="a"/><rect style="stroke:none"/><g/></svg>"#,
+        );
+        assert_eq!(
+            inline_styles(svg).unwrap(),
+            concat!(
+                r#"<svg><rect style="fill:red;stroke:blue;fill:green" class="a"/>"#,
+                r#"<rect style="fill:red;stroke:blue;stroke:none"/><g/></svg>"#,
+            )
+        );
+        assert_eq!(
+            strip_styles(svg).unwrap(),
+            r#"<svg><rect class="a"/><rect style="stroke:none"/><g/></svg>"#
+        );
+    }
+
+    #[test]
+    fn minifies() {
+        let svg = concat!(
+            "<svg>\n  <!-- c -->\n  <style>\n    g > rect , text { fill: red; }\n  </style>\n",
+            "  <text> <tspan>a</tspan> <tspan>b</tspan> </text>\n",
+            "  <foreignObject><div><span>one</span> <span>two</span></div></foreignObject>\n</svg>\n",
+        );
+        assert_eq!(
+            minify(svg).unwrap(),
+            concat!(
+                "<svg><style>g>rect,text{fill:red}</style>",
+                "<text> <tspan>a</tspan> <tspan>b</tspan> </text>",
+                "<foreignObject><div><span>one</span> <span>two</span></div></foreignObject></svg>\n",
+            )
+        );
+    }
+
+    /// Needs a sans-serif font installed.
+    #[test]
+    fn embeds_fonts() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><text>Hi</text><text font-family="serif" font-weight="bold">Yo</text><text style="fill:red">!</text></svg>"#;
+
+        let kept = embed_fonts(svg, false).unwrap();
+        let sheet = &kept[kept.find("<style>").unwrap()..kept.find("</style>").unwrap()];
+        assert_eq!(sheet.matches("@font-face").count(), 2);
+        assert!(sheet.contains("font-weight:700"));
+        let family = &sheet[sheet.find("font-family:\"").unwrap() + 13..];
+        let family = &family[..family.find('"').unwrap()];
+        assert!(sheet.ends_with(&format!(
+            "text:not([font-family]){{font-family:\"{family}\",sans-serif}}"
+        )));
+        assert!(!sheet.contains("!important"));
+
+        let data = &sheet[sheet.find("base64,").unwrap() + 7..];
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(&data[..data.find(')').unwrap()])
+            .unwrap();
+        let face = ttf_parser::Face::parse(&data, 0).unwrap();
+        for c in "HiYo!".chars() {
+            assert!(face.glyph_index(c).is_some(), "{c}");
+        }
+        assert!(face.glyph_index('Z').is_none());
+
+        let inline = embed_fonts(svg, true).unwrap();
+        let font = format!("font-family:&quot;{family}&quot;,sans-serif");
+        assert!(!inline.contains("text:not"));
+        assert!(inline.contains(&format!(r#"<text style="{font}">Hi</text>"#)));
+        assert!(inline.contains(r#"<text font-family="serif" font-weight="bold">Yo</text>"#));
+        assert!(inline.contains(&format!(r#"<text style="{font};fill:red">!</text>"#)));
+
+        assert_eq!(
+            embed_fonts("<svg><g/></svg>", false).unwrap(),
+            "<svg><g/></svg>"
+        );
+    }
+
+    #[test]
+    fn own_fonts() {
+        let svg = r#"<svg><text font-family="serif"/><text style="fill:red; font-family : mono"/><text style="fill:red"/><text/></svg>"#;
+        let document = parse(svg).unwrap();
+        let own: Vec<_> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("text"))
+            .map(|node| sets_font_family(&node))
+            .collect();
+        assert_eq!(own, [true, true, false, false]);
+    }
+}
@@ -0,0 +1,198 @@
+//! SVG to PNG, for the editor's PNG export and `bmermaid render -f png`.
+
+use std::str::FromStr;
+
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use serde::Deserialize;
+
+use crate::svg;
+
+/// The CSS pixels per inch SVG user units are measured in.
+pub const CSS_DPI: f32 = 96.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RasterOptions {
+    /// Image pixels per SVG user unit.
+    pub scale: f32,
+    /// Largest image width in pixels; larger diagrams are scaled down to fit.
+    pub width: Option<u32>,
+    /// Largest image height in pixels.
+    pub height: Option<u32>,
+    /// A CSS colour to fill the image with first; transparent when `None`.
+    pub background: Option<String>,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        RasterOptions {
+            scale: 2.0,
+            width: None,
+            height: None,
+            background: None,
+        }
+    }
+}
+
+impl RasterOptions {
+    /// The scale for printing at `dpi` dots per inch.
+    pub fn scale_for_dpi(dpi: f32) -> f32 {
+        dpi / CSS_DPI
+    }
+
+    /// Rejects a scale or background [`png`] cannot use.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(format!("invalid scale {}", self.scale));
+        }
+        self.background().map(|_| ())
+    }
+
+    fn background(&self) -> Result<Option<Color>, String> {
+        self.background.as_deref().map(color).transpose()
+    }
+}
+
+/// Renders `svg` to PNG bytes.
+pub fn png(svg: &str, options: &RasterOptions) -> Result<Vec<u8>, String> {
+    options.validate()?;
+    let background = options.background()?;
+
+    let tree = svg::tree(svg)?;
+    let size = tree.size;
+    let mut scale = options.scale;
+    if let Some(width) = options.width {
+        scale = scale.min(width as f32 / size.width());
+    }
+    if let Some(height) = options.height {
+        scale = scale.min(height as f32 / size.height());
+    }
+    let width = (size.width() * scale).round().max(1.0) as u32;
+    let height = (size.height() * scale).round().max(1.0) as u32;
+
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| format!("cannot allocate a {width}x{height} image"))?;
+    if let Some(background) = background {
+        pixmap.fill(background);
+    }
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+fn color(text: &str) -> Result<Color, String> {
+    let color = svgtypes::Color::from_str(text.trim())
+        .map_err(|_| format!("invalid background colour `{text}`"))?;
+    Ok(Color::from_rgba8(
+        color.red,
+        color.green,
+        color.blue,
+        color.alpha,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 200x100 diagram with a square in its middle.
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100"><rect x="90" y="40" width="20" height="20" fill="red"/></svg>"#;
+
+    fn render(options: &RasterOptions) -> Pixmap {
+        Pixmap::decode_png(&png(SVG, options).unwrap()).unwrap()
+    }
+
+    fn size(options: &RasterOptions) -> (u32, u32) {
+        let pixmap = render(options);
+        (pixmap.width(), pixmap.height())
+    }
+
+    fn scaled(scale: f32) -> RasterOptions {
+        RasterOptions {
+            scale,
+            ..RasterOptions::default()
+        }
+    }
+
+    #[test]
+    fn dpi_sets_the_scale() {
+        assert_eq!(RasterOptions::scale_for_dpi(96.0), 1.0);
+        assert_eq!(
+            size(&scaled(RasterOptions::scale_for_dpi(96.0))),
+            (200, 100)
+        );
+        assert_eq!(
+            size(&scaled(RasterOptions::scale_for_dpi(288.0))),
+            (600, 300)
+        );
+        assert_eq!(size(&RasterOptions::default()), (400, 200));
+    }
+
+    #[test]
+    fn fits_within_the_largest_size() {
+        let fit = |width, height| {
+            size(&RasterOptions {
+                width,
+                height,
+                ..RasterOptions::default()
+            })
+        };
+        assert_eq!(fit(Some(100), None), (100, 50));
+        assert_eq!(fit(None, Some(20)), (40, 20));
+        assert_eq!(fit(Some(100), Some(20)), (40, 20));
+        // Never scaled up.
+        assert_eq!(fit(Some(1000), Some(1000)), (400, 200));
+    }
+
+    #[test]
+    fn fills_the_background() {
+        let pixel = |background: Option<&str>| {
+            let pixmap = render(&RasterOptions {
+                background: background.map(str::to_string),
+                ..scaled(1.0)
+            });
+            let pixel = pixmap.pixel(0, 0).unwrap();
+            let centre = pixmap.pixel(100, 50).unwrap();
+            assert_eq!((centre.red(), centre.alpha()), (255, 255));
+            (pixel.red(), pixel.green(), pixel.blue(), pixel.alpha())
+        };
+        assert_eq!(pixel(None), (0, 0, 0, 0));
+        assert_eq!(pixel(Some("white")), (255, 255, 255, 255));
+        assert_eq!(pixel(Some(" #00f ")), (0, 0, 255, 255));
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let background = |text: &str| {
+            RasterOptions {
+                background: Some(text.to_string()),
+                ..RasterOptions::default()
+            }
+            .validate()
+        };
+        for valid in ["white", "#fff8", "rgb(1, 2, 3)", "transparent"] {
+            assert_eq!(background(valid), Ok(()), "{valid}");
+        }
+        for invalid in ["blurple", "#12", "", "rgb(1, 2)"] {
+            assert_eq!(
+                background(invalid),
+                Err(format!("invalid background colour `{invalid}`"))
+            );
+        }
+        assert!(png(
+            SVG,
+            &RasterOptions {
+                background: Some("blurple".to_string()),
+                ..RasterOptions::default()
+            }
+        )
+        .is_err());
+
+        for scale in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(scaled(scale).validate().is_err(), "{scale}");
+        }
+    }
+}
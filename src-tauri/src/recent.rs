@@ -0,0 +1,149 @@
+//! Recently opened and saved documents, kept in the app data dir.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::save;
+
+/// How many documents the list remembers.
+pub const MAX_RECENT: usize = 10;
+
+pub struct RecentFiles {
+    /// The JSON file the list is stored in.
+    file: PathBuf,
+    entries: Mutex<Vec<PathBuf>>,
+}
+
+impl RecentFiles {
+    pub fn load(file: PathBuf) -> Self {
+        let entries = fs::read_to_string(&file)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        RecentFiles {
+            file,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Moves `path` to the top of the list.
+    pub fn add(&self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| *entry != path);
+        entries.insert(0, path);
+        entries.truncate(MAX_RECENT);
+        self.store(&entries);
+    }
+
+    /// The list, most recent first, without files that no longer exist.
+    pub fn list(&self) -> Vec<PathBuf> {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|entry| entry.is_file());
+        if entries.len() != before {
+            self.store(&entries);
+        }
+        entries.clone()
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clear();
+        self.store(&entries);
+    }
+
+    fn store(&self, entries: &[PathBuf]) {
+        let result = match self.file.parent() {
+            Some(dir) => fs::create_dir_all(dir).map_err(|e| save::SaveError::new(dir, e)),
+            None => Ok(()),
+        }
+        .and_then(|()| save::save(&self.file, &serde_json::to_string(entries).unwrap(), false));
+        if let Err(e) = result {
+            eprintln!("recent files: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory with `count` files in it, `0.mmd` and so on.
+    fn files(test: &str, count: usize) -> (PathBuf, Vec<PathBuf>) {
+        let dir =
+            std::env::temp_dir().join(format!("bmermaid-recent-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+        let files = (0..count)
+            .map(|i| {
+                let file = dir.join(format!("{i}.mmd"));
+                fs::write(&file, "flowchart TD\n").unwrap();
+                file
+            })
+            .collect();
+        (dir, files)
+    }
+
+    #[test]
+    fn most_recent_first_without_duplicates() {
+        let (dir, files) = files("order", 3);
+        let recent = RecentFiles::load(dir.join("data/recent.json"));
+        assert!(recent.list().is_empty());
+        recent.add(&files[0]);
+        recent.add(&files[1]);
+        recent.add(&files[2]);
+        // Spelled differently, but the same file.
+        recent.add(&dir.join(".").join("0.mmd"));
+        assert_eq!(
+            recent.list(),
+            [files[0].clone(), files[2].clone(), files[1].clone()]
+        );
+
+        let reloaded = RecentFiles::load(dir.join("data/recent.json"));
+        assert_eq!(reloaded.list(), recent.list());
+        reloaded.clear();
+        assert!(RecentFiles::load(dir.join("data/recent.json"))
+            .list()
+            .is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_latest_few() {
+        let (dir, files) = files("cap", MAX_RECENT + 2);
+        let recent = RecentFiles::load(dir.join("recent.json"));
+        for file in &files {
+            recent.add(file);
+        }
+        let expected: Vec<_> = files.iter().rev().take(MAX_RECENT).cloned().collect();
+        assert_eq!(recent.list(), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drops_missing_files() {
+        let (dir, files) = files("missing", 2);
+        let recent = RecentFiles::load(dir.join("recent.json"));
+        recent.add(&files[0]);
+        recent.add(&files[1]);
+        fs::remove_file(&files[1]).unwrap();
+        assert_eq!(recent.list(), [files[0].clone()]);
+        let stored = fs::read_to_string(dir.join("recent.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<PathBuf>>(&stored).unwrap(),
+            [files[0].clone()]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_lists_start_empty() {
+        let (dir, _) = files("corrupt", 0);
+        fs::write(dir.join("recent.json"), "not json").unwrap();
+        assert!(RecentFiles::load(dir.join("recent.json")).list().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
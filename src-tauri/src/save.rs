@@ -0,0 +1,295 @@
+//! Crash-safe document saving.
+//!
+//! The new contents go to a temporary file next to the target, which is
+//! flushed to disk and then renamed over it, so a crash leaves either the
+//! old file or the new one but never a truncated mix.
+
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+/// Why a save failed, serialized for the editor as `{ kind, path, ... }`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SaveError {
+    PermissionDenied { path: PathBuf },
+    DiskFull { path: PathBuf },
+    IsADirectory { path: PathBuf },
+    Io { path: PathBuf, message: String },
+}
+
+impl SaveError {
+    pub fn new(path: &Path, error: io::Error) -> Self {
+        let path = path.to_path_buf();
+        match error.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                SaveError::PermissionDenied { path }
+            }
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => {
+                SaveError::DiskFull { path }
+            }
+            io::ErrorKind::IsADirectory => SaveError::IsADirectory { path },
+            _ => SaveError::Io {
+                path,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::PermissionDenied { path } => {
+                write!(f, "{}: permission denied", path.display())
+            }
+            SaveError::DiskFull { path } => write!(f, "{}: disk is full", path.display()),
+            SaveError::IsADirectory { path } => write!(f, "{}: is a directory", path.display()),
+            SaveError::Io { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+/// Atomically replaces `path` with `content`. An existing file keeps its
+/// permissions and line endings and, with `backup`, is first copied to
+/// `<path>.bak`. Symlinks are followed so the link itself survives.
+pub fn save(path: &Path, content: &str, backup: bool) -> Result<(), SaveError> {
+    let (target, existing) = target(path)?;
+    let content = match &existing {
+        Some(_) => match_line_endings(&target, content),
+        None => content.to_string(),
+    };
+    replace(path, &target, existing, content.as_bytes(), backup)
+}
+
+/// [`save`] for binary files such as exported images, written as they are.
+pub fn save_bytes(path: &Path, content: &[u8], backup: bool) -> Result<(), SaveError> {
+    let (target, existing) = target(path)?;
+    replace(path, &target, existing, content, backup)
+}
+
+/// The file `path` resolves to, and its metadata if it exists.
+fn target(path: &Path) -> Result<(PathBuf, Option<Metadata>), SaveError> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let existing = match fs::metadata(&target) {
+        Ok(metadata) if metadata.is_dir() => {
+            return Err(SaveError::IsADirectory {
+                path: path.to_path_buf(),
+            })
+        }
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(SaveError::new(path, e)),
+    };
+    Ok((target, existing))
+}
+
+/// Backs up and replaces `target`, reporting errors against `path`.
+fn replace(
+    path: &Path,
+    target: &Path,
+    existing: Option<Metadata>,
+    content: &[u8],
+    backup: bool,
+) -> Result<(), SaveError> {
+    if backup && existing.is_some() {
+        let mut backup_path = target.as_os_str().to_owned();
+        backup_path.push(".bak");
+        fs::copy(target, &backup_path).map_err(|e| SaveError::new(path, e))?;
+    }
+
+    let (temp, file) = create_temp(target).map_err(|e| SaveError::new(path, e))?;
+    let result = write_synced(file, content)
+        .and_then(|()| match &existing {
+            Some(metadata) => fs::set_permissions(&temp, metadata.permissions()),
+            None => Ok(()),
+        })
+        .and_then(|()| fs::rename(&temp, target));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(SaveError::new(path, e));
+    }
+
+    sync_dir(target);
+    Ok(())
+}
+
+/// Creates `.<name>.<pid>.<n>.tmp` in the target's directory, so the final
+/// rename never crosses file systems. The process id and a counter keep
+/// concurrent saves of the same file, from this process or another, apart;
+/// a name left behind by a crash is skipped rather than overwritten.
+fn create_temp(target: &Path) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    loop {
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp = target.with_file_name(format!(".{name}.{}.{n}.tmp", std::process::id()));
+        match File::create_new(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn write_synced(mut file: File, content: &[u8]) -> io::Result<()> {
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Converts `content` to the line endings the file at `target` uses now.
+/// The editor always hands over `\n`.
+fn match_line_endings(target: &Path, content: &str) -> String {
+    let crlf = fs::read(target)
+        .map(|bytes| bytes.windows(2).any(|w| w == b"\r\n"))
+        .unwrap_or(false);
+    let normalized = content.replace("\r\n", "\n");
+    if crlf {
+        normalized.replace('\n', "\r\n")
+    } else {
+        normalized
+    }
+}
+
+/// Persists the rename itself. Best effort: not every platform can open a
+/// directory for syncing.
+fn sync_dir(target: &Path) {
+    if let Some(dir) = target.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory for one test.
+    fn dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bmermaid-save-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn creates_and_replaces() {
+        let dir = dir("replace");
+        let path = dir.join("a.mmd");
+        save(&path, "flowchart TD\n", false).unwrap();
+        save(&path, "flowchart LR\n", false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "flowchart LR\n");
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["a.mmd"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_saves_do_not_collide() {
+        let dir = dir("concurrent");
+        let path = dir.join("a.mmd");
+        let contents: Vec<String> = (0..8)
+            .map(|i| format!("flowchart TD\n    A{i}\n"))
+            .collect();
+        std::thread::scope(|scope| {
+            for content in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        save(path, content, false).unwrap();
+                    }
+                });
+            }
+        });
+        assert!(contents.contains(&fs::read_to_string(&path).unwrap()));
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["a.mmd"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let dir = dir("crlf");
+        let path = dir.join("a.mmd");
+        fs::write(&path, "graph TD\r\n").unwrap();
+        save(&path, "flowchart TD\n    A\n", false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "flowchart TD\r\n    A\r\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backs_up_existing_files() {
+        let dir = dir("backup");
+        let path = dir.join("a.mmd");
+        save(&path, "one\n", true).unwrap();
+        assert!(!dir.join("a.mmd.bak").exists());
+        save(&path, "two\n", true).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.mmd.bak")).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_bytes_as_they_are() {
+        let dir = dir("bytes");
+        let path = dir.join("a.png");
+        fs::write(&path, "old\r\n").unwrap();
+        save_bytes(&path, b"\x89PNG\n\0", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\x89PNG\n\0");
+        assert_eq!(fs::read(dir.join("a.png.bak")).unwrap(), b"old\r\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_directories() {
+        let dir = dir("directory");
+        let error = save(&dir, "x", false).unwrap_err();
+        assert!(matches!(error, SaveError::IsADirectory { .. }), "{error:?}");
+        assert_eq!(
+            error.to_string(),
+            format!("{}: is a directory", dir.display())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_permissions_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = dir("unix");
+        let target = dir.join("target.mmd");
+        let link = dir.join("link.mmd");
+        fs::write(&target, "old\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        save(&link, "new\n", false).unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+//! Serialized shapes other programs depend on: the arguments a window opens
+//! with, and the records the headless subcommands print with
+//! `--format json`.
+//!
+//! JSON output carries [`SCHEMA_VERSION`]. Adding a field keeps the
+//! version; renaming, removing or retyping one bumps it.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::check::Diagnostic;
+use crate::parse;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// What a window opens with, handed to its webview as `window.__CLI_ARGS__`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CliArgs {
+    pub file: Option<String>,
+    pub theme: Option<String>,
+    /// Contents for an unsaved buffer, read from stdin.
+    pub source: Option<String>,
+    /// Where to put the cursor, 1-based.
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+/// Everything a subcommand prints with `--format json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Output {
+    pub schema_version: u32,
+    /// The subcommand, e.g. `check`.
+    pub command: &'static str,
+    /// One record per file, in command-line order.
+    pub files: Vec<FileRecord>,
+    /// The exit status the run ends with.
+    pub status: i32,
+    pub duration_ms: f64,
+}
+
+impl Output {
+    pub fn new(
+        command: &'static str,
+        files: Vec<FileRecord>,
+        status: i32,
+        duration: Duration,
+    ) -> Self {
+        Output {
+            schema_version: SCHEMA_VERSION,
+            command,
+            files,
+            status,
+            duration_ms: millis(duration),
+        }
+    }
+
+    pub fn print(&self) {
+        println!("{}", serde_json::to_string_pretty(self).unwrap());
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRecord {
+    /// As given on the command line, `<stdin>` for stdin.
+    pub file: String,
+    /// The diagram's header keyword, e.g. `flowchart` or `sequenceDiagram`;
+    /// `null` when there is no header or the file could not be read.
+    pub diagram_type: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// What `render` wrote, `-` for stdout.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// What `info` reports about the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<FileInfo>,
+    /// Why the file could not be processed: unreadable, or failed to render.
+    pub error: Option<String>,
+    pub duration_ms: f64,
+}
+
+impl FileRecord {
+    /// A record for `source` with its syntax diagnostics.
+    pub fn new(file: String, source: &str) -> Self {
+        FileRecord {
+            file,
+            diagram_type: diagram_type(source),
+            diagnostics: crate::check::diagnostics(source),
+            output: None,
+            info: None,
+            error: None,
+            duration_ms: 0.0,
+        }
+    }
+
+    /// A record for a file that could not be processed at all.
+    pub fn failed(file: String, error: String) -> Self {
+        FileRecord {
+            file,
+            diagram_type: None,
+            diagnostics: Vec::new(),
+            output: None,
+            info: None,
+            error: Some(error),
+            duration_ms: 0.0,
+        }
+    }
+
+    pub fn timed(mut self, duration: Duration) -> Self {
+        self.duration_ms = millis(duration);
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub lines: usize,
+    pub bytes: usize,
+    /// Top-level statements in the diagram body.
+    pub statements: usize,
+    /// `LR`, `TD` and so on, for flowcharts that set one.
+    pub direction: Option<&'static str>,
+}
+
+impl FileInfo {
+    pub fn new(source: &str) -> Self {
+        let parsed = parse::parse(source);
+        let diagram = parsed.document.map(|document| document.diagram);
+        let (statements, direction) = match &diagram {
+            Some(parse::Diagram::Flowchart(chart)) => (
+                chart.statements.len(),
+                chart.direction.as_ref().map(|d| d.value.as_str()),
+            ),
+            Some(parse::Diagram::Sequence(diagram)) => (diagram.statements.len(), None),
+            Some(parse::Diagram::Class(diagram)) => (diagram.statements.len(), None),
+            Some(parse::Diagram::State(diagram)) => (diagram.statements.len(), None),
+            Some(parse::Diagram::Er(diagram)) => (diagram.statements.len(), None),
+            Some(parse::Diagram::Unsupported { .. }) | None => (0, None),
+        };
+        FileInfo {
+            lines: source.lines().count(),
+            bytes: source.len(),
+            statements,
+            direction,
+        }
+    }
+}
+
+fn diagram_type(source: &str) -> Option<String> {
+    let document = parse::parse(source).document?;
+    Some(document.diagram.keyword().value.clone())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Pins the JSON other programs parse: a failing assertion here means
+    /// the change needs a new [`SCHEMA_VERSION`].
+    #[test]
+    fn json_output() {
+        let mut rendered = FileRecord::new("a.mmd".to_string(), "flowchart LR\n  A --> B\n")
+            .timed(Duration::from_millis(2));
+        rendered.output = Some("a.svg".to_string());
+        let mut info = FileRecord::new("<stdin>".to_string(), "flowchart LR\n  A --> B\n");
+        info.info = Some(FileInfo::new("flowchart LR\n  A --> B\n"));
+        let files = vec![
+            rendered,
+            info,
+            FileRecord::new("b.mmd".to_string(), "flowhcart TD\n"),
+            FileRecord::failed("c.mmd".to_string(), "c.mmd: not found".to_string()),
+        ];
+        let output = Output::new("render", files, 1, Duration::from_micros(1500));
+
+        assert_eq!(
+            serde_json::to_value(&output).unwrap(),
+            json!({
+                "schemaVersion": 1,
+                "command": "render",
+                "files": [
+                    {
+                        "file": "a.mmd",
+                        "diagramType": "flowchart",
+                        "diagnostics": [],
+                        "output": "a.svg",
+                        "error": null,
+                        "durationMs": 2.0,
+                    },
+                    {
+                        "file": "<stdin>",
+                        "diagramType": "flowchart",
+                        "diagnostics": [],
+                        "info": {
+                            "lines": 2,
+                            "bytes": 23,
+                            "statements": 1,
+                            "direction": "LR",
+                        },
+                        "error": null,
+                        "durationMs": 0.0,
+                    },
+                    {
+                        "file": "b.mmd",
+                        "diagramType": null,
+                        "diagnostics": [{
+                            "severity": "error",
+                            "code": "syntax",
+                            "line": 1,
+                            "column": 1,
+                            "endLine": 1,
+                            "endColumn": 10,
+                            "message": "unknown diagram type `flowhcart`",
+                            "fix": {
+                                "title": "Replace with `flowchart`",
+                                "line": 1,
+                                "column": 1,
+                                "endLine": 1,
+                                "endColumn": 10,
+                                "replacement": "flowchart",
+                            },
+                        }],
+                        "error": null,
+                        "durationMs": 0.0,
+                    },
+                    {
+                        "file": "c.mmd",
+                        "diagramType": null,
+                        "diagnostics": [],
+                        "error": "c.mmd: not found",
+                        "durationMs": 0.0,
+                    },
+                ],
+                "status": 1,
+                "durationMs": 1.5,
+            })
+        );
+    }
+
+    #[test]
+    fn cli_args_round_trip() {
+        let args = CliArgs {
+            file: Some("a.mmd".to_string()),
+            line: Some(3),
+            ..CliArgs::default()
+        };
+        let value = serde_json::to_value(&args).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "file": "a.mmd",
+                "theme": null,
+                "source": null,
+                "line": 3,
+                "column": null,
+            })
+        );
+        let back: CliArgs = serde_json::from_value(value).unwrap();
+        assert_eq!(back.file.as_deref(), Some("a.mmd"));
+        assert_eq!(back.line, Some(3));
+    }
+}
@@ -0,0 +1,550 @@
+//! Cutting a TrueType font down to the characters a diagram uses, for
+//! embedding in exported SVGs.
+//!
+//! Only fonts with `glyf` outlines can be cut down. The subset keeps the
+//! outlines, metrics and hinting of the glyphs it needs, composite glyphs'
+//! components included, and maps each character to its glyph; kerning,
+//! ligatures and font variations are left out.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ttf_parser::{Face, GlyphId, Tag};
+
+/// Tables copied as they are.
+const COPIED_TABLES: &[&[u8; 4]] = &[b"OS/2", b"cvt ", b"fpgm", b"gasp", b"prep"];
+
+/// Windows `name` records kept: copyright, family, subfamily, unique id,
+/// full name, version and PostScript name. The license text, often most of
+/// the table, is left out.
+const NAME_IDS: std::ops::RangeInclusive<u16> = 0..=6;
+
+// Composite glyph flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// A TrueType font with the glyphs of `chars` from face `index` of `data`.
+/// Characters the font lacks are left out.
+pub fn subset(data: &[u8], index: u32, chars: &BTreeSet<char>) -> Result<Vec<u8>, String> {
+    let face = Face::parse(data, index).map_err(|e| format!("unreadable font: {e}"))?;
+    let table = |tag: &[u8; 4]| face.raw_face().table(Tag::from_bytes(tag));
+    let (Some(head), Some(hhea), Some(maxp), Some(loca), Some(glyf)) = (
+        table(b"head"),
+        table(b"hhea"),
+        table(b"maxp"),
+        table(b"loca"),
+        table(b"glyf"),
+    ) else {
+        return Err("the font has no TrueType outlines".to_string());
+    };
+    let malformed = || "malformed font".to_string();
+    let long_loca = read_u16(head, 50).ok_or_else(malformed)? == 1;
+    let glyph = |id: u16| -> Option<&[u8]> {
+        let offset = |i: usize| match long_loca {
+            true => read_u32(loca, i * 4).map(|offset| offset as usize),
+            false => read_u16(loca, i * 2).map(|offset| offset as usize * 2),
+        };
+        glyf.get(offset(id as usize)?..offset(id as usize + 1)?)
+    };
+
+    // .notdef, the glyphs of `chars`, and the components of those.
+    let mut mapping = BTreeMap::new();
+    let mut keep = BTreeSet::from([0]);
+    for &c in chars {
+        if let Some(id) = face.glyph_index(c) {
+            mapping.insert(c, id.0);
+            keep.insert(id.0);
+        }
+    }
+    let mut queue: Vec<u16> = keep.iter().copied().collect();
+    while let Some(id) = queue.pop() {
+        let data = glyph(id).ok_or_else(malformed)?;
+        for offset in component_offsets(data).ok_or_else(malformed)? {
+            let component = read_u16(data, offset).ok_or_else(malformed)?;
+            if keep.insert(component) {
+                queue.push(component);
+            }
+        }
+    }
+    let new_ids: BTreeMap<u16, u16> = keep
+        .iter()
+        .enumerate()
+        .map(|(new, &old)| (old, new as u16))
+        .collect();
+
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::new();
+    let mut hmtx = Vec::new();
+    for &old in &keep {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        let mut data = glyph(old).ok_or_else(malformed)?.to_vec();
+        for offset in component_offsets(&data).ok_or_else(malformed)? {
+            let component = read_u16(&data, offset).ok_or_else(malformed)?;
+            data[offset..offset + 2].copy_from_slice(&new_ids[&component].to_be_bytes());
+        }
+        new_glyf.extend_from_slice(&data);
+        pad(&mut new_glyf);
+
+        let id = GlyphId(old);
+        hmtx.extend_from_slice(&face.glyph_hor_advance(id).unwrap_or(0).to_be_bytes());
+        hmtx.extend_from_slice(&face.glyph_hor_side_bearing(id).unwrap_or(0).to_be_bytes());
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let count = (keep.len() as u16).to_be_bytes();
+    let mut head = head.to_vec();
+    let mut hhea = hhea.to_vec();
+    let mut maxp = maxp.to_vec();
+    if head.len() < 54 || hhea.len() < 36 || maxp.len() < 6 {
+        return Err(malformed());
+    }
+    // Checksum adjustment, filled in once the file is complete, and long
+    // `loca` offsets.
+    head[8..12].fill(0);
+    head[50..52].copy_from_slice(&1u16.to_be_bytes());
+    // Every glyph gets its own advance.
+    hhea[34..36].copy_from_slice(&count);
+    maxp[4..6].copy_from_slice(&count);
+
+    // Version 3: no glyph names.
+    let mut post = table(b"post")
+        .and_then(|post| post.get(..32))
+        .map_or_else(|| vec![0; 32], <[u8]>::to_vec);
+    post[..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+
+    let cmap = cmap(
+        &mapping
+            .into_iter()
+            .map(|(c, id)| (c, new_ids[&id]))
+            .collect(),
+    )?;
+
+    let mut tables = vec![
+        (*b"cmap", cmap),
+        (*b"glyf", new_glyf),
+        (*b"head", head),
+        (*b"hhea", hhea),
+        (*b"hmtx", hmtx),
+        (*b"loca", new_loca),
+        (*b"maxp", maxp),
+        (*b"post", post),
+    ];
+    if let Some(name) = table(b"name").and_then(name) {
+        tables.push((*b"name", name));
+    }
+    for tag in COPIED_TABLES {
+        if let Some(data) = table(tag) {
+            tables.push((**tag, data.to_vec()));
+        }
+    }
+    Ok(font_file(tables))
+}
+
+/// The offsets of the component glyph ids in a composite glyph; none for a
+/// simple or empty glyph.
+fn component_offsets(glyph: &[u8]) -> Option<Vec<usize>> {
+    let mut offsets = Vec::new();
+    if glyph.is_empty() || read_u16(glyph, 0)? as i16 >= 0 {
+        return Some(offsets);
+    }
+    let mut pos = 10;
+    loop {
+        let flags = read_u16(glyph, pos)?;
+        read_u16(glyph, pos + 2)?;
+        offsets.push(pos + 2);
+        pos += 4;
+        pos += if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            4
+        } else {
+            2
+        };
+        pos += if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(offsets);
+        }
+    }
+}
+
+/// The [`NAME_IDS`] records of a `name` table.
+fn name(table: &[u8]) -> Option<Vec<u8>> {
+    let count = read_u16(table, 2)? as usize;
+    let strings = read_u16(table, 4)? as usize;
+    let mut records = Vec::new();
+    let mut data = Vec::new();
+    for i in 0..count {
+        let record = table.get(6 + i * 12..18 + i * 12)?;
+        let platform = read_u16(record, 0)?;
+        let id = read_u16(record, 6)?;
+        if platform != 3 || !NAME_IDS.contains(&id) {
+            continue;
+        }
+        let len = read_u16(record, 8)? as usize;
+        let offset = strings + read_u16(record, 10)? as usize;
+        records.extend_from_slice(&record[..8]);
+        records.extend_from_slice(&(len as u16).to_be_bytes());
+        records.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        data.extend_from_slice(table.get(offset..offset + len)?);
+    }
+    let count = records.len() / 12;
+    let mut out = Vec::with_capacity(6 + records.len() + data.len());
+    for value in [0, count as u16, (6 + records.len()) as u16] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    out.extend_from_slice(&records);
+    out.extend_from_slice(&data);
+    Some(out)
+}
+
+/// A `cmap` with a format 4 subtable for the Basic Multilingual Plane and,
+/// when characters beyond it are mapped, a format 12 one for all of them.
+fn cmap(mapping: &BTreeMap<char, u16>) -> Result<Vec<u8>, String> {
+    // Runs of consecutive characters with consecutive glyphs.
+    let mut groups: Vec<(u32, u32, u16)> = Vec::new();
+    for (&c, &id) in mapping {
+        match groups.last_mut() {
+            Some((start, end, first))
+                if *end + 1 == c as u32 && u32::from(*first) + (c as u32 - *start) == id as u32 =>
+            {
+                *end = c as u32;
+            }
+            _ => groups.push((c as u32, c as u32, id)),
+        }
+    }
+
+    let bmp: Vec<_> = groups
+        .iter()
+        .filter(|(start, _, _)| *start < 0xffff)
+        .map(|&(start, end, id)| (start, end.min(0xfffe), id))
+        .collect();
+    // The last segment maps 0xFFFF to .notdef, as the format requires.
+    let segments = bmp.len() + 1;
+    let length = 16 + segments * 8;
+    if length > u16::MAX as usize {
+        return Err("too many characters to embed".to_string());
+    }
+    let mut format4 = Vec::with_capacity(length);
+    let log2 = segments.ilog2();
+    let search_range = 2u16 << log2;
+    for value in [
+        4,
+        length as u16,
+        0,
+        segments as u16 * 2,
+        search_range,
+        log2 as u16,
+        segments as u16 * 2 - search_range,
+    ] {
+        format4.extend_from_slice(&value.to_be_bytes());
+    }
+    for &(_, end, _) in &bmp {
+        format4.extend_from_slice(&(end as u16).to_be_bytes());
+    }
+    format4.extend_from_slice(&[0xff, 0xff, 0, 0]);
+    for &(start, _, _) in &bmp {
+        format4.extend_from_slice(&(start as u16).to_be_bytes());
+    }
+    format4.extend_from_slice(&[0xff, 0xff]);
+    for &(start, _, id) in &bmp {
+        format4.extend_from_slice(&id.wrapping_sub(start as u16).to_be_bytes());
+    }
+    format4.extend_from_slice(&1u16.to_be_bytes());
+    format4.resize(length, 0);
+
+    let mut subtables = vec![((3u16, 1u16), format4)];
+    if groups.iter().any(|&(_, end, _)| end > 0xffff) {
+        let mut format12 = Vec::with_capacity(16 + groups.len() * 12);
+        format12.extend_from_slice(&12u16.to_be_bytes());
+        format12.extend_from_slice(&0u16.to_be_bytes());
+        format12.extend_from_slice(&(16 + groups.len() as u32 * 12).to_be_bytes());
+        format12.extend_from_slice(&0u32.to_be_bytes());
+        format12.extend_from_slice(&(groups.len() as u32).to_be_bytes());
+        for &(start, end, id) in &groups {
+            for value in [start, end, id as u32] {
+                format12.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        subtables.push(((3, 10), format12));
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&(subtables.len() as u16).to_be_bytes());
+    let mut offset = 4 + subtables.len() as u32 * 8;
+    for ((platform, encoding), data) in &subtables {
+        out.extend_from_slice(&platform.to_be_bytes());
+        out.extend_from_slice(&encoding.to_be_bytes());
+        out.extend_from_slice(&offset.to_be_bytes());
+        offset += data.len() as u32;
+    }
+    for (_, data) in subtables {
+        out.extend_from_slice(&data);
+    }
+    Ok(out)
+}
+
+/// Writes `tables` out as a font file with a table directory and
+/// checksums.
+fn font_file(mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let count = tables.len() as u16;
+    let log2 = count.ilog2() as u16;
+    let search_range = 16 << log2;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    for value in [count, search_range, log2, count * 16 - search_range] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut offset = 12 + tables.len() * 16;
+    let mut head = None;
+    for (tag, data) in &tables {
+        if tag == b"head" {
+            head = Some(offset);
+        }
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&checksum(data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in &tables {
+        out.extend_from_slice(data);
+        pad(&mut out);
+    }
+    if let Some(head) = head {
+        let adjustment = 0xb1b0_afbau32.wrapping_sub(checksum(&out));
+        out[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    out
+}
+
+/// The sum of the big-endian words of `data`, padded with zeros.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(4), 0);
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use ttf_parser::OutlineBuilder;
+
+    use super::*;
+
+    /// A simple glyph: a triangle with its corner at `(x, y)`.
+    fn triangle(x: i16, y: i16) -> Vec<u8> {
+        let mut glyph = Vec::new();
+        for value in [1, x, y, x + 10, y + 10, 2, 0] {
+            glyph.extend_from_slice(&value.to_be_bytes());
+        }
+        glyph.extend_from_slice(&[0x01; 3]);
+        // Coordinates are deltas from the previous point.
+        for value in [x, 10, -10, y, 0, 10] {
+            glyph.extend_from_slice(&value.to_be_bytes());
+        }
+        glyph
+    }
+
+    /// A composite glyph of `components`, each with word offsets and the
+    /// first with a scale, to exercise the flag arithmetic.
+    fn composite(components: &[u16]) -> Vec<u8> {
+        let mut glyph = Vec::new();
+        for value in [-1i16, 0, 0, 100, 100] {
+            glyph.extend_from_slice(&value.to_be_bytes());
+        }
+        for (i, &id) in components.iter().enumerate() {
+            let mut flags = ARG_1_AND_2_ARE_WORDS | 0x0002;
+            if i == 0 {
+                flags |= WE_HAVE_A_SCALE;
+            }
+            if i + 1 < components.len() {
+                flags |= MORE_COMPONENTS;
+            }
+            for value in [flags, id, 10, 20] {
+                glyph.extend_from_slice(&value.to_be_bytes());
+            }
+            if i == 0 {
+                glyph.extend_from_slice(&0x4000u16.to_be_bytes());
+            }
+        }
+        glyph
+    }
+
+    /// A font with .notdef, `A`, `B`, an unmapped accent, `Ä` built from
+    /// `A` and the accent, and `𝔸` beyond the Basic Multilingual Plane.
+    fn font() -> Vec<u8> {
+        let glyphs = [
+            Vec::new(),
+            triangle(10, 10),
+            triangle(20, 20),
+            triangle(30, 90),
+            composite(&[1, 3]),
+            triangle(40, 40),
+        ];
+        let mapping = BTreeMap::from([('A', 1), ('B', 2), ('Ä', 4), ('𝔸', 5)]);
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        let mut hmtx = Vec::new();
+        for (i, glyph) in glyphs.iter().enumerate() {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            glyf.extend_from_slice(glyph);
+            pad(&mut glyf);
+            hmtx.extend_from_slice(&(500 + i as u16).to_be_bytes());
+            hmtx.extend_from_slice(&(i as i16).to_be_bytes());
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+        let count = (glyphs.len() as u16).to_be_bytes();
+        let mut head = vec![0; 54];
+        head[..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        head[12..16].copy_from_slice(&0x5f0f_3cf5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[50..52].copy_from_slice(&1u16.to_be_bytes());
+        let mut hhea = vec![0; 36];
+        hhea[..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        hhea[34..36].copy_from_slice(&count);
+        let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec();
+        maxp.extend_from_slice(&count);
+
+        font_file(vec![
+            (*b"cmap", cmap(&mapping).unwrap()),
+            (*b"glyf", glyf),
+            (*b"head", head),
+            (*b"hhea", hhea),
+            (*b"hmtx", hmtx),
+            (*b"loca", loca),
+            (*b"maxp", maxp),
+        ])
+    }
+
+    fn chars(text: &str) -> BTreeSet<char> {
+        text.chars().collect()
+    }
+
+    /// The characters the font's `cmap` maps, from every subtable.
+    fn mapped(face: &Face) -> BTreeSet<char> {
+        let mut mapped = BTreeSet::new();
+        for subtable in face.tables().cmap.unwrap().subtables {
+            subtable.codepoints(|c| {
+                if subtable.glyph_index(c).is_some_and(|id| id.0 != 0) {
+                    mapped.extend(char::from_u32(c));
+                }
+            });
+        }
+        mapped
+    }
+
+    /// Counts the points of an outline.
+    #[derive(Default)]
+    struct Points(usize);
+
+    impl OutlineBuilder for Points {
+        fn move_to(&mut self, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn line_to(&mut self, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn close(&mut self) {}
+    }
+
+    fn outline(face: &Face, c: char) -> (ttf_parser::Rect, usize) {
+        let mut points = Points::default();
+        let rect = face
+            .outline_glyph(face.glyph_index(c).unwrap(), &mut points)
+            .unwrap();
+        (rect, points.0)
+    }
+
+    #[test]
+    fn maps_exactly_the_requested_characters() {
+        let original = font();
+        let data = subset(&original, 0, &chars("A𝔸Z")).unwrap();
+        let face = Face::parse(&data, 0).unwrap();
+        assert_eq!(mapped(&face), chars("A𝔸"));
+        assert_eq!(face.number_of_glyphs(), 3);
+
+        let original = Face::parse(&original, 0).unwrap();
+        for c in ['A', '𝔸'] {
+            let (old, new) = (
+                original.glyph_index(c).unwrap(),
+                face.glyph_index(c).unwrap(),
+            );
+            assert_eq!(original.glyph_hor_advance(old), face.glyph_hor_advance(new));
+            assert_eq!(outline(&original, c), outline(&face, c));
+        }
+    }
+
+    #[test]
+    fn keeps_composite_components() {
+        let original = font();
+        let data = subset(&original, 0, &chars("Ä")).unwrap();
+        let face = Face::parse(&data, 0).unwrap();
+        assert_eq!(mapped(&face), chars("Ä"));
+        // .notdef, `Ä` and both of its components.
+        assert_eq!(face.number_of_glyphs(), 4);
+        let original = Face::parse(&original, 0).unwrap();
+        assert_eq!(outline(&original, 'Ä'), outline(&face, 'Ä'));
+    }
+
+    #[test]
+    fn loca_and_checksums_are_consistent() {
+        let data = subset(&font(), 0, &chars("BÄ")).unwrap();
+        let face = Face::parse(&data, 0).unwrap();
+        let table = |tag: &[u8; 4]| face.raw_face().table(Tag::from_bytes(tag)).unwrap();
+        let (head, loca, glyf) = (table(b"head"), table(b"loca"), table(b"glyf"));
+
+        assert_eq!(read_u16(head, 50), Some(1));
+        let offsets: Vec<u32> = loca
+            .chunks(4)
+            .map(|chunk| u32::from_be_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(offsets.len(), face.number_of_glyphs() as usize + 1);
+        assert_eq!(offsets[0], 0);
+        assert!(offsets.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(offsets.iter().all(|offset| offset % 4 == 0));
+        assert_eq!(*offsets.last().unwrap() as usize, glyf.len());
+
+        assert_eq!(checksum(&data), 0xb1b0_afba);
+    }
+
+    #[test]
+    fn rejects_fonts_without_outlines() {
+        assert!(subset(b"not a font", 0, &chars("A")).is_err());
+    }
+}
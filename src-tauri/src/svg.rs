@@ -0,0 +1,229 @@
+//! Loading the renderer's SVG for conversion to other formats.
+//!
+//! beautiful-mermaid colours its diagrams with CSS custom properties and
+//! `color-mix()`, which usvg does not evaluate, so both are replaced with
+//! plain colours before parsing. Text is laid out with the system fonts,
+//! loaded once per process: the same SVG converts to the same bytes on the
+//! same machine.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use resvg::usvg::{self, fontdb, TreeParsing, TreePostProc};
+
+/// How often `var()` references may refer to further variables.
+const MAX_VAR_DEPTH: usize = 8;
+
+/// Families tried, in order, for `sans-serif`; fontdb's default, Arial, is
+/// missing from most Linux servers.
+const SANS_SERIF: &[&str] = &[
+    "Inter",
+    "Arial",
+    "Helvetica",
+    "Liberation Sans",
+    "DejaVu Sans",
+    "Noto Sans",
+];
+
+/// Parses `svg` into a tree ready to rasterize or convert.
+pub fn tree(svg: &str) -> Result<usvg::Tree, String> {
+    let options = usvg::Options {
+        font_family: "sans-serif".to_string(),
+        ..usvg::Options::default()
+    };
+    let mut tree = usvg::Tree::from_str(&resolve_css(svg), &options)
+        .map_err(|e| format!("invalid SVG: {e}"))?;
+    tree.postprocess(usvg::PostProcessingSteps::default(), fonts());
+    Ok(tree)
+}
+
+/// The system fonts, with `sans-serif` set to the first of [`SANS_SERIF`]
+/// that is installed.
+pub fn fonts() -> &'static fontdb::Database {
+    static FONTS: OnceLock<fontdb::Database> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let mut fonts = fontdb::Database::new();
+        fonts.load_system_fonts();
+        let installed = |family: &str| {
+            fonts
+                .faces()
+                .any(|face| face.families.iter().any(|(name, _)| name == family))
+        };
+        let sans_serif = SANS_SERIF
+            .iter()
+            .find(|family| installed(family))
+            .map(|family| family.to_string())
+            .or_else(|| {
+                let face = fonts.faces().next()?;
+                Some(face.families.first()?.0.clone())
+            });
+        if let Some(family) = sans_serif {
+            fonts.set_sans_serif_family(family);
+        }
+        fonts
+    })
+}
+
+/// Substitutes custom properties declared anywhere in the document into
+/// their `var()` uses, then evaluates `color-mix()` in sRGB.
+fn resolve_css(svg: &str) -> String {
+    let properties = custom_properties(svg);
+    let mut text = svg.to_string();
+    for _ in 0..MAX_VAR_DEPTH {
+        match replace_calls(&text, "var(", |args| {
+            let (name, fallback) = match args.split_once(',') {
+                Some((name, fallback)) => (name.trim(), Some(fallback.trim())),
+                None => (args.trim(), None),
+            };
+            properties
+                .iter()
+                .rev()
+                .find(|(property, _)| property == name)
+                .map(|(_, value)| value.as_str())
+                .or(fallback)
+                .map(str::to_string)
+        }) {
+            Some(replaced) => text = replaced,
+            None => break,
+        }
+    }
+    replace_calls(&text, "color-mix(", color_mix).unwrap_or(text)
+}
+
+/// `--name: value` declarations in document order.
+fn custom_properties(svg: &str) -> Vec<(String, String)> {
+    let mut properties = Vec::new();
+    let mut rest = svg;
+    while let Some(start) = rest.find("--") {
+        let after = &rest[start..];
+        let name_len = after[2..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .map_or(after.len(), |len| len + 2);
+        let name = &after[..name_len];
+        let tail = after[name_len..].trim_start();
+        rest = &after[name_len..];
+        let Some(value) = tail.strip_prefix(':') else {
+            continue;
+        };
+        if name.len() == 2 {
+            continue;
+        }
+        let end = value
+            .find([';', '"', '\'', '}', '<'])
+            .unwrap_or(value.len());
+        properties.push((name.to_string(), value[..end].trim().to_string()));
+    }
+    properties
+}
+
+/// Replaces every `name...)` call whose arguments `evaluate` accepts.
+/// Returns `None` when nothing was replaced.
+fn replace_calls(
+    text: &str,
+    name: &str,
+    mut evaluate: impl FnMut(&str) -> Option<String>,
+) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut replaced = false;
+    while let Some(start) = rest.find(name) {
+        let args_start = start + name.len();
+        let Some(args_len) = closing_paren(&rest[args_start..]) else {
+            break;
+        };
+        let args = &rest[args_start..args_start + args_len];
+        match evaluate(args) {
+            Some(value) => {
+                out.push_str(&rest[..start]);
+                out.push_str(&value);
+                replaced = true;
+            }
+            None => out.push_str(&rest[..args_start + args_len + 1]),
+        }
+        rest = &rest[args_start + args_len + 1..];
+    }
+    out.push_str(rest);
+    replaced.then_some(out)
+}
+
+/// The offset of the `)` closing a call whose arguments start `text`.
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `in srgb, <color> [<percentage>], <color> [<percentage>]`.
+fn color_mix(args: &str) -> Option<String> {
+    let mut parts = split_args(args).into_iter();
+    if parts.next()?.trim() != "in srgb" {
+        return None;
+    }
+    let (first, p1) = mix_stop(&parts.next()?)?;
+    let (second, p2) = mix_stop(&parts.next()?)?;
+    let (p1, p2) = match (p1, p2) {
+        (Some(p1), Some(p2)) if p1 + p2 > 0.0 => (p1 / (p1 + p2), p2 / (p1 + p2)),
+        (Some(p1), None) => (p1, 1.0 - p1),
+        (None, Some(p2)) => (1.0 - p2, p2),
+        _ => (0.5, 0.5),
+    };
+    let mix = |a: u8, b: u8| (a as f32 * p1 + b as f32 * p2).round() as u8;
+    let color = svgtypes::Color::new_rgba(
+        mix(first.red, second.red),
+        mix(first.green, second.green),
+        mix(first.blue, second.blue),
+        mix(first.alpha, second.alpha),
+    );
+    Some(if color.alpha == u8::MAX {
+        format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
+    } else {
+        format!(
+            "rgba({},{},{},{:.3})",
+            color.red,
+            color.green,
+            color.blue,
+            color.alpha as f32 / 255.0
+        )
+    })
+}
+
+/// A colour with an optional trailing percentage, as a fraction.
+fn mix_stop(stop: &str) -> Option<(svgtypes::Color, Option<f32>)> {
+    let stop = stop.trim();
+    if let Some((color, percentage)) = stop.rsplit_once(char::is_whitespace) {
+        if let Some(percentage) = percentage.strip_suffix('%') {
+            let percentage = percentage.parse::<f32>().ok()?.clamp(0.0, 100.0) / 100.0;
+            return Some((
+                svgtypes::Color::from_str(color.trim()).ok()?,
+                Some(percentage),
+            ));
+        }
+    }
+    Some((svgtypes::Color::from_str(stop).ok()?, None))
+}
+
+/// Splits on the commas outside nested parentheses.
+fn split_args(args: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut depth = 0;
+    for c in args.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    parts
+}
@@ -0,0 +1,327 @@
+//! Notices when an open document changes on disk behind the editor's back.
+//!
+//! Each window watches at most one file. A background thread polls it and
+//! emits [`EXTERNAL_CHANGE_EVENT`] to that window when the contents change,
+//! the file disappears or it is renamed within its directory. Writes made by
+//! the editor itself go through [`Watchers::write`] and are not reported.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{Emitter, WebviewWindow};
+
+pub const EXTERNAL_CHANGE_EVENT: &str = "external-change";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ExternalChange {
+    Modified { path: PathBuf },
+    Deleted { path: PathBuf },
+    Renamed { path: PathBuf, new_path: PathBuf },
+}
+
+/// What the file looked like when last seen.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl Snapshot {
+    fn read(path: &Path, metadata: &Metadata) -> io::Result<Snapshot> {
+        let mut hasher = DefaultHasher::new();
+        fs::read(path)?.hash(&mut hasher);
+        Ok(Snapshot {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            hash: hasher.finish(),
+        })
+    }
+
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.len == metadata.len() && self.modified == metadata.modified().ok()
+    }
+}
+
+struct Watched {
+    path: PathBuf,
+    /// `None` while the file is missing.
+    snapshot: Option<Snapshot>,
+    id: Option<FileId>,
+    /// The file was missing on the previous poll. Editors that save by
+    /// renaming a temp file over the original leave it missing for a moment,
+    /// so a deletion is only reported once it is seen twice.
+    missing: bool,
+}
+
+impl Watched {
+    fn new(path: PathBuf) -> Self {
+        let mut watched = Watched {
+            path,
+            snapshot: None,
+            id: None,
+            missing: false,
+        };
+        watched.refresh();
+        watched
+    }
+
+    /// Takes the file as it is now as the known state.
+    fn refresh(&mut self) {
+        let metadata = fs::metadata(&self.path).ok();
+        self.id = metadata.as_ref().and_then(file_id);
+        self.snapshot = metadata.and_then(|m| Snapshot::read(&self.path, &m).ok());
+        self.missing = false;
+    }
+
+    fn check(&mut self) -> Option<ExternalChange> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return self.check_missing(),
+            Err(_) => return None,
+        };
+        self.missing = false;
+        if self.snapshot.as_ref().is_some_and(|s| s.matches(&metadata)) {
+            return None;
+        }
+
+        let snapshot = Snapshot::read(&self.path, &metadata).ok()?;
+        let changed = self
+            .snapshot
+            .as_ref()
+            .is_none_or(|s| s.hash != snapshot.hash);
+        self.snapshot = Some(snapshot);
+        self.id = file_id(&metadata);
+        changed.then(|| ExternalChange::Modified {
+            path: self.path.clone(),
+        })
+    }
+
+    fn check_missing(&mut self) -> Option<ExternalChange> {
+        // Already reported.
+        self.snapshot.as_ref()?;
+        if !self.missing {
+            self.missing = true;
+            return None;
+        }
+
+        let path = self.path.clone();
+        if let Some(new_path) = self.find_renamed() {
+            self.path = new_path.clone();
+            self.refresh();
+            return Some(ExternalChange::Renamed { path, new_path });
+        }
+        self.snapshot = None;
+        Some(ExternalChange::Deleted { path })
+    }
+
+    /// Looks for the same file under another name in the same directory.
+    fn find_renamed(&self) -> Option<PathBuf> {
+        let id = self.id?;
+        let dir = self.path.parent()?;
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .find(|entry| {
+                entry
+                    .metadata()
+                    .ok()
+                    .and_then(|m| file_id(&m))
+                    .is_some_and(|other| other == id)
+            })
+            .map(|entry| entry.path())
+    }
+}
+
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = ();
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Renames can only be told apart from deletions where files have a stable
+/// identity.
+#[cfg(not(unix))]
+fn file_id(_: &Metadata) -> Option<FileId> {
+    None
+}
+
+struct Watcher {
+    watched: Arc<Mutex<Watched>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Watchers keyed by window label, kept in Tauri's managed state.
+#[derive(Default)]
+pub struct Watchers(Mutex<HashMap<String, Watcher>>);
+
+impl Watchers {
+    /// Starts watching `path` for `window`, replacing what it watched before.
+    pub fn watch(&self, window: &WebviewWindow, path: PathBuf) {
+        let watched = Arc::new(Mutex::new(Watched::new(path)));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_window = window.clone();
+        let thread_watched = Arc::clone(&watched);
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            poll(thread_window, thread_watched, thread_stop);
+        });
+
+        self.0
+            .lock()
+            .unwrap()
+            .insert(window.label().to_string(), Watcher { watched, stop });
+    }
+
+    pub fn unwatch(&self, label: &str) {
+        self.0.lock().unwrap().remove(label);
+    }
+
+    /// Runs `write`, which saves the window's document, without the write
+    /// being reported back as an external change.
+    pub fn write<T>(&self, label: &str, write: impl FnOnce() -> T) -> T {
+        let watchers = self.0.lock().unwrap();
+        let Some(watcher) = watchers.get(label) else {
+            return write();
+        };
+        let mut watched = watcher.watched.lock().unwrap();
+        let result = write();
+        watched.refresh();
+        result
+    }
+}
+
+fn poll(window: WebviewWindow, watched: Arc<Mutex<Watched>>, stop: Arc<AtomicBool>) {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let change = watched.lock().unwrap().check();
+        if let Some(change) = change {
+            let _ = window.emit_to(window.label(), EXTERNAL_CHANGE_EVENT, change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory holding `name` with `content`.
+    fn file(test: &str, name: &str, content: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bmermaid-watch-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Writes `content` and moves the modification time on, so the change
+    /// shows even where timestamps are coarse.
+    fn rewrite(path: &Path, content: &str, seconds: u64) {
+        fs::write(path, content).unwrap();
+        let modified = SystemTime::now() + Duration::from_secs(seconds);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn reports_changed_contents_only() {
+        let path = file("modify", "a.mmd", "flowchart TD\n");
+        let mut watched = Watched::new(path.clone());
+        assert!(watched.check().is_none());
+
+        // Touched, but the same bytes.
+        rewrite(&path, "flowchart TD\n", 10);
+        assert!(watched.check().is_none());
+
+        // Same length, so only the hash tells.
+        rewrite(&path, "flowchart LR\n", 20);
+        match watched.check() {
+            Some(ExternalChange::Modified { path: changed }) => assert_eq!(changed, path),
+            other => panic!("{other:?}"),
+        }
+        assert!(watched.check().is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reports_renames_and_deletions() {
+        let path = file("rename", "a.mmd", "flowchart TD\n");
+        let renamed = path.with_file_name("b.mmd");
+        let mut watched = Watched::new(path.clone());
+
+        fs::rename(&path, &renamed).unwrap();
+        // A save by rename leaves the file missing for a moment.
+        assert!(watched.check().is_none());
+        match watched.check() {
+            Some(ExternalChange::Renamed {
+                path: old,
+                new_path,
+            }) if cfg!(unix) => {
+                assert_eq!((old, &new_path), (path.clone(), &renamed));
+                assert_eq!(watched.path, renamed);
+            }
+            Some(ExternalChange::Deleted { path: old }) if !cfg!(unix) => assert_eq!(old, path),
+            other => panic!("{other:?}"),
+        }
+        assert!(watched.check().is_none());
+
+        fs::remove_file(&renamed).unwrap();
+        assert!(watched.check().is_none());
+        match watched.check() {
+            Some(ExternalChange::Deleted { path: old }) if cfg!(unix) => assert_eq!(old, renamed),
+            None if !cfg!(unix) => {}
+            other => panic!("{other:?}"),
+        }
+        assert!(watched.check().is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_for_one_poll_is_not_a_deletion() {
+        let path = file("replace", "a.mmd", "flowchart TD\n");
+        let mut watched = Watched::new(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert!(watched.check().is_none());
+        rewrite(&path, "flowchart TD\n", 10);
+        assert!(watched.check().is_none());
+        assert!(!watched.missing);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
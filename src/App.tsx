@@ -1,16 +1,20 @@
 import { useState, useEffect, useRef, useCallback } from 'react'
 import { EditorView, basicSetup } from 'codemirror'
-import { EditorState, Compartment } from '@codemirror/state'
+import { EditorState, Compartment, Text } from '@codemirror/state'
 import { mermaid } from 'codemirror-lang-mermaid'
 import { autocompletion, CompletionContext, CompletionResult } from '@codemirror/autocomplete'
 import { keymap } from '@codemirror/view'
 import { defaultKeymap, history, historyKeymap } from '@codemirror/commands'
+import { linter, lintGutter } from '@codemirror/lint'
 import { syntaxHighlighting, HighlightStyle } from '@codemirror/language'
 import { tags } from '@lezer/highlight'
 import { renderMermaid, THEMES } from 'beautiful-mermaid'
 import { open, save, ask } from '@tauri-apps/plugin-dialog'
-import { readTextFile, writeTextFile } from '@tauri-apps/plugin-fs'
+import { readTextFile } from '@tauri-apps/plugin-fs'
 import { readText } from '@tauri-apps/plugin-clipboard-manager'
+import { emit } from '@tauri-apps/api/event'
+import { invoke } from '@tauri-apps/api/core'
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow'
 import { 
   FilePlus, 
   FolderOpen, 
@@ -22,7 +26,9 @@ import {
   Palette,
   ZoomIn,
   ZoomOut,
-  RotateCcw
+  RotateCcw,
+  AlertCircle,
+  AlignLeft
 } from 'lucide-react'
 import { Button } from '@/components/ui/button'
 import {
@@ -53,6 +59,85 @@ interface ThemeColors {
   transparent?: boolean
 }
 
+interface CliArgs {
+  command?: 'render'
+  file?: string
+  sources?: string[]
+  source?: string
+  line?: number | null
+  column?: number | null
+  theme?: string
+}
+
+const getCliArgs = () => (window as { __CLI_ARGS__?: CliArgs }).__CLI_ARGS__
+
+// Exported images carry their Mermaid source; see `embed` in the backend.
+const IMAGE_PATH = /\.(svg|png)$/i
+
+// Mirrors `check::Diagnostic` in the backend; positions are 1-based.
+interface SuggestedFix {
+  title: string
+  line: number
+  column: number
+  endLine: number
+  endColumn: number
+  replacement: string
+}
+
+interface Diagnostic {
+  severity: 'error' | 'warning' | 'info'
+  code: string
+  line: number
+  column: number
+  endLine: number
+  endColumn: number
+  message: string
+  fix: SuggestedFix | null
+}
+
+// Mirrors `save::SaveError` in the backend.
+type SaveError =
+  | { kind: 'permissionDenied'; path: string }
+  | { kind: 'diskFull'; path: string }
+  | { kind: 'isADirectory'; path: string }
+  | { kind: 'io'; path: string; message: string }
+
+function describeSaveError(error: unknown): string {
+  if (typeof error !== 'object' || error === null || !('kind' in error)) {
+    return error instanceof Error ? error.message : String(error)
+  }
+  const saveError = error as SaveError
+  switch (saveError.kind) {
+    case 'permissionDenied':
+      return `permission denied for ${saveError.path}`
+    case 'diskFull':
+      return 'the disk is full'
+    case 'isADirectory':
+      return `${saveError.path} is a directory`
+    case 'io':
+      return saveError.message
+  }
+}
+
+// Payload of the backend's `external-change` event.
+type ExternalChange =
+  | { kind: 'modified'; path: string }
+  | { kind: 'deleted'; path: string }
+  | { kind: 'renamed'; path: string; newPath: string }
+
+// Mirrors `autosave::Snapshot` in the backend.
+interface RecoveredSnapshot {
+  id: string
+  path: string | null
+  content: string
+  savedAt: number
+}
+
+interface DiffLine {
+  change: 'unchanged' | 'added' | 'removed'
+  text: string
+}
+
 interface AppState {
   currentFile: string | null
   isDirty: boolean
@@ -104,6 +189,89 @@ function darkenColor(hex: string, amount: number): string {
   return `#${r.toString(16).padStart(2, '0')}${g.toString(16).padStart(2, '0')}${b.toString(16).padStart(2, '0')}`
 }
 
+function toOffset(doc: Text, line: number, column: number): number {
+  const docLine = doc.line(Math.min(Math.max(line, 1), doc.lines))
+  return Math.min(docLine.from + column - 1, docLine.to)
+}
+
+function applyFix(view: EditorView, fix: SuggestedFix) {
+  const doc = view.state.doc
+  view.dispatch({
+    changes: {
+      from: toOffset(doc, fix.line, fix.column),
+      to: toOffset(doc, fix.endLine, fix.endColumn),
+      insert: fix.replacement,
+    },
+  })
+}
+
+// Replaces the document with the backend's canonical formatting, keeping the
+// cursor on the same line. Resolves to an error message when the document
+// cannot be formatted.
+async function formatView(view: EditorView): Promise<string | null> {
+  const source = view.state.doc.toString()
+  let formatted: string
+  try {
+    formatted = await invoke<string>('format_document', { source })
+  } catch (error) {
+    return error instanceof Error ? error.message : String(error)
+  }
+  if (formatted === source || view.state.doc.toString() !== source) return null
+
+  const head = view.state.selection.main.head
+  const line = view.state.doc.lineAt(head)
+  const newDoc = Text.of(formatted.split(/\r?\n/))
+  view.dispatch({
+    changes: { from: 0, to: view.state.doc.length, insert: formatted },
+    selection: { anchor: toOffset(newDoc, line.number, head - line.from + 1) },
+  })
+  return null
+}
+
+// Squiggles from the backend's `parse_diagnostics` and `lint_diagnostics`;
+// `onResults` receives the raw list for the problems panel. `getPath` names
+// the open file so lint levels come from its `.bmermaid.toml`. Outside Tauri
+// the commands are unavailable and the editor simply shows no diagnostics.
+function diagnosticsLinter(
+  onResults: (diagnostics: Diagnostic[]) => void,
+  getPath: () => string | null,
+) {
+  return linter(async (view) => {
+    const doc = view.state.doc
+    const source = doc.toString()
+    if (!source.trim()) {
+      onResults([])
+      return []
+    }
+
+    let diagnostics: Diagnostic[]
+    try {
+      const [syntax, lint] = await Promise.all([
+        invoke<Diagnostic[]>('parse_diagnostics', { source }),
+        invoke<Diagnostic[]>('lint_diagnostics', { source, path: getPath() }).catch(() => []),
+      ])
+      diagnostics = [...syntax, ...lint]
+    } catch {
+      return []
+    }
+    onResults(diagnostics)
+
+    return diagnostics.map((d) => {
+      const from = toOffset(doc, d.line, d.column)
+      const to = Math.max(from, toOffset(doc, d.endLine, d.endColumn))
+      const fix = d.fix
+      return {
+        from,
+        to,
+        severity: d.severity,
+        source: d.code,
+        message: d.message,
+        actions: fix ? [{ name: fix.title, apply: (view: EditorView) => applyFix(view, fix) }] : [],
+      }
+    })
+  })
+}
+
 function getEditorTheme(themeName: string) {
   const colors = getThemeColors(themeName)
   const isDark = colors.bg === '#1e1e1e' || parseInt(colors.bg.slice(1, 3), 16) < 128
@@ -215,8 +383,15 @@ function App() {
   const [pan, setPan] = useState({ x: 0, y: 0 })
   const [isDragging, setIsDragging] = useState(false)
   const [dragStart, setDragStart] = useState({ x: 0, y: 0 })
+  const [problems, setProblems] = useState<Diagnostic[]>([])
+  const [externalChange, setExternalChange] = useState<ExternalChange | null>(null)
+  const [diffLines, setDiffLines] = useState<DiffLine[] | null>(null)
   
   const editorViewRef = useRef<EditorView | null>(null)
+  const currentFileRef = useRef<string | null>(null)
+  const isDirtyRef = useRef(false)
+  const autosaveTimeoutRef = useRef<ReturnType<typeof setTimeout> | null>(null)
+  const menuActionRef = useRef<(action: string) => void>(() => {})
   const renderTimeoutRef = useRef<ReturnType<typeof setTimeout> | null>(null)
   const themeCompartmentRef = useRef(new Compartment())
   const updateListenerCompartmentRef = useRef(new Compartment())
@@ -325,6 +500,22 @@ function App() {
     }
   }, [state.currentTheme])
 
+  // Hands the buffer to the backend's crash-recovery snapshots once typing
+  // pauses. A buffer that turned out clean (e.g. a file was just loaded)
+  // needs no snapshot.
+  const scheduleAutosave = useCallback(() => {
+    if (!('__TAURI__' in window)) return
+    if (autosaveTimeoutRef.current) {
+      clearTimeout(autosaveTimeoutRef.current)
+    }
+    autosaveTimeoutRef.current = setTimeout(() => {
+      if (!isDirtyRef.current) return
+      const content = editorViewRef.current?.state.doc.toString() ?? ''
+      invoke('autosave_update', { path: currentFileRef.current, content })
+        .catch((error) => console.error('Autosave failed:', error))
+    }, 1000)
+  }, [])
+
   const debouncedRender = useCallback(() => {
     if (renderTimeoutRef.current) {
       clearTimeout(renderTimeoutRef.current)
@@ -334,6 +525,17 @@ function App() {
     }, 300)
   }, [renderDiagram])
 
+  useEffect(() => {
+    currentFileRef.current = state.currentFile
+  }, [state.currentFile])
+
+  useEffect(() => {
+    isDirtyRef.current = state.isDirty
+    if (!state.isDirty && '__TAURI__' in window) {
+      invoke('autosave_clear').catch(() => {})
+    }
+  }, [state.isDirty])
+
   useEffect(() => {
     const editorEl = document.getElementById('editor')
     if (!editorEl) return
@@ -343,14 +545,29 @@ function App() {
       extensions: [
         basicSetup,
         mermaid(),
+        diagnosticsLinter(setProblems, () => currentFileRef.current),
+        lintGutter(),
         autocompletion({ override: [mermaidCompletions] }),
-        keymap.of([...defaultKeymap, ...historyKeymap]),
+        keymap.of([
+          {
+            key: 'Shift-Alt-f',
+            run: (view) => {
+              formatView(view).then((error) => {
+                if (error) setStatus(error)
+              })
+              return true
+            },
+          },
+          ...defaultKeymap,
+          ...historyKeymap,
+        ]),
         history(),
         updateListenerCompartmentRef.current.of(
           EditorView.updateListener.of((update) => {
             if (update.docChanged) {
               setState(prev => ({ ...prev, isDirty: true }))
               debouncedRender()
+              scheduleAutosave()
             }
           })
         ),
@@ -400,6 +617,7 @@ function App() {
             if (update.docChanged) {
               setState(prev => ({ ...prev, isDirty: true }))
               debouncedRender()
+              scheduleAutosave()
             }
           })
         ),
@@ -416,22 +634,47 @@ function App() {
 
     checkTauri()
 
-    const args = (window as { __CLI_ARGS__?: { file?: string; theme?: string } }).__CLI_ARGS__
+    const args = getCliArgs()
     if (!args) return
 
+    if (args.command === 'render') {
+      // Headless `bmermaid render`: the window stays hidden and the backend
+      // writes each SVG as its result comes in, exiting after the last one.
+      const renderAll = async () => {
+        for (const source of args.sources ?? []) {
+          try {
+            const svg = await renderMermaid(source, getThemeColors(args.theme ?? 'default'))
+            await emit('render-finished', { svg })
+          } catch (error) {
+            await emit('render-finished', {
+              error: error instanceof Error ? error.message : String(error),
+            })
+          }
+        }
+      }
+      renderAll()
+      return
+    }
+
     if (args.theme) {
-      const validThemes = ['default', 'dark', 'tokyo-night', 'dracula', 'github-dark', 'nord', 'one-dark', 'solarized-light', 'solarized-dark', 'monokai']
-      if (validThemes.includes(args.theme)) {
+      if (Object.keys(themeMap).includes(args.theme)) {
         setState(prev => ({ ...prev, currentTheme: args.theme! }))
       }
     }
 
-    if (args.file) {
+    if (args.source) {
+      // Piped in on the command line: an unsaved buffer until saved.
+      setCode(args.source)
+      setState(prev => ({ ...prev, currentFile: null, isDirty: true }))
+      setStatus('Read from stdin')
+      if (args.line) goTo(args.line, args.column ?? 1)
+    } else if (args.file) {
       readTextFile(args.file)
         .then((content: string) => {
           setCode(content)
           setState(prev => ({ ...prev, currentFile: args.file!, isDirty: false }))
           setStatus(`Opened: ${args.file}`)
+          if (args.line) goTo(args.line, args.column ?? 1)
         })
         .catch((error: Error) => {
           setStatus(`Failed to open: ${error.message}`)
@@ -440,8 +683,103 @@ function App() {
     }
   }, [])
 
+  useEffect(() => {
+    if (!('__TAURI__' in window)) return
+    const command = state.currentFile
+      ? invoke('watch_document', { path: state.currentFile })
+      : invoke('unwatch_document')
+    command.catch((error) => console.error('Failed to watch file:', error))
+    if (state.currentFile) {
+      invoke('add_recent_file', { path: state.currentFile }).catch(() => {})
+    }
+  }, [state.currentFile])
+
+  useEffect(() => {
+    if (!('__TAURI__' in window)) return
+    const currentWindow = getCurrentWebviewWindow()
+    const unlisteners = [
+      currentWindow.listen<string>('menu', ({ payload }) => {
+        menuActionRef.current(payload)
+      }),
+      currentWindow.listen('save-and-close', () => {
+        menuActionRef.current('save-and-close')
+      }),
+      currentWindow.listen<{ line: number, column: number }>('go-to', ({ payload }) => {
+        goTo(payload.line, payload.column)
+      }),
+    ]
+    return () => {
+      unlisteners.forEach((unlisten) => unlisten.then((stop) => stop()))
+    }
+  }, [])
+
+  useEffect(() => {
+    if (!('__TAURI__' in window) || getCliArgs()?.command) return
+    invoke('update_document', { path: state.currentFile, dirty: state.isDirty })
+      .catch((error) => console.error('Failed to update document:', error))
+  }, [state.currentFile, state.isDirty])
+
+  useEffect(() => {
+    if (!('__TAURI__' in window)) return
+    const unlisten = getCurrentWebviewWindow().listen<string>('open-recent', ({ payload }) => {
+      openPath(payload)
+    })
+    return () => {
+      unlisten.then((stop) => stop())
+    }
+  }, [])
+
+  useEffect(() => {
+    if (!('__TAURI__' in window)) return
+    const unlisten = getCurrentWebviewWindow().listen<ExternalChange>('external-change', async ({ payload }) => {
+      if (payload.kind === 'renamed') {
+        setState(prev => ({ ...prev, currentFile: payload.newPath }))
+        setStatus(`Renamed on disk: ${payload.newPath}`)
+        return
+      }
+      if (payload.kind === 'modified') {
+        // Nothing to decide when the disk already matches the buffer.
+        const content = await readTextFile(payload.path).catch(() => null)
+        if (content === getCode()) return
+      }
+      setDiffLines(null)
+      setExternalChange(payload)
+    })
+    return () => {
+      unlisten.then((stop) => stop())
+    }
+  }, [])
+
+  useEffect(() => {
+    if (!('__TAURI__' in window) || getCliArgs()?.command) return
+    // Only the first window speaks for the previous session.
+    if (getCurrentWebviewWindow().label !== 'main') return
+    // Offer snapshots a crashed session left behind, newest first. Declined
+    // ones are deleted; after a restore the rest wait for the next start.
+    const restoreSnapshots = async () => {
+      const snapshots = await invoke<RecoveredSnapshot[]>('recovered_snapshots')
+      for (const snapshot of snapshots) {
+        const savedAt = new Date(snapshot.savedAt * 1000).toLocaleString()
+        const restore = await ask(
+          `Restore unsaved changes${snapshot.path ? ` to ${snapshot.path}` : ''} from ${savedAt}?`,
+          { title: 'Recover Unsaved Changes' },
+        )
+        await invoke('discard_snapshot', { id: snapshot.id })
+        if (restore) {
+          setCode(snapshot.content)
+          setState(prev => ({ ...prev, currentFile: snapshot.path, isDirty: true }))
+          setStatus('Restored unsaved changes')
+          return
+        }
+      }
+    }
+    restoreSnapshots().catch((error) => console.error('Failed to restore snapshots:', error))
+  }, [])
+
   useEffect(() => {
     const checkClipboard = async () => {
+      if (getCliArgs()?.command) return
+      if ('__TAURI__' in window && getCurrentWebviewWindow().label !== 'main') return
       try {
         const clipboardText = await readText()
         if (!clipboardText || clipboardText.trim().length === 0) return
@@ -480,6 +818,15 @@ function App() {
 
   const getCode = () => editorViewRef.current?.state.doc.toString() || ''
 
+  // Places the cursor at a 1-based position and scrolls it into view.
+  const goTo = (line: number, column = 1) => {
+    const editorView = editorViewRef.current
+    if (!editorView) return
+    const pos = toOffset(editorView.state.doc, line, column)
+    editorView.dispatch({ selection: { anchor: pos }, scrollIntoView: true })
+    editorView.focus()
+  }
+
   const setCode = (code: string) => {
     const editorView = editorViewRef.current
     if (!editorView) return
@@ -488,21 +835,52 @@ function App() {
     })
   }
 
+  // A window keeps the document it has; anything else opens in a new one.
+  const hasDocument = () => currentFileRef.current !== null || isDirtyRef.current
+
   const handleNew = () => {
+    if ('__TAURI__' in window && hasDocument()) {
+      invoke('open_document', { path: null }).catch((error) => {
+        setStatus(`Failed to open window: ${String(error)}`)
+        setIsError(true)
+      })
+      return
+    }
     setCode(DEFAULT_CODE)
     setState(prev => ({ currentFile: null, isDirty: false, currentTheme: prev.currentTheme }))
     setStatus('New file')
     renderDiagram()
   }
 
+  const openPath = async (filePath: string) => {
+    try {
+      if ('__TAURI__' in window && hasDocument() && filePath !== currentFileRef.current) {
+        await invoke('open_document', { path: filePath })
+        return
+      }
+      if (IMAGE_PATH.test(filePath)) {
+        // An exported image opens its embedded source as an unsaved buffer.
+        setCode(await invoke<string>('image_source', { path: filePath }))
+        setState(prev => ({ ...prev, currentFile: null, isDirty: true }))
+        setStatus(`Opened the diagram embedded in ${filePath}`)
+        return
+      }
+      const content = await readTextFile(filePath)
+      setCode(content)
+      setState(prev => ({ ...prev, currentFile: filePath, isDirty: false }))
+      setStatus(`Opened: ${filePath}`)
+    } catch (error) {
+      const errorMessage = error instanceof Error ? error.message : String(error)
+      setStatus(`Failed to open: ${errorMessage}`)
+      setIsError(true)
+    }
+  }
+
   const handleOpen = async () => {
     try {
       const filePath = await open({ multiple: false })
       if (filePath && typeof filePath === 'string') {
-        const content = await readTextFile(filePath)
-        setCode(content)
-        setState(prev => ({ ...prev, currentFile: filePath, isDirty: false }))
-        setStatus(`Opened: ${filePath}`)
+        await openPath(filePath)
       }
     } catch (error) {
       const errorMessage = error instanceof Error ? error.message : String(error)
@@ -511,65 +889,42 @@ function App() {
     }
   }
 
-  const handleSave = async () => {
+  const handleSave = async (saveAs = false): Promise<string | null> => {
     try {
-      let filePath = state.currentFile
+      let filePath = saveAs ? null : state.currentFile
 
       if (!filePath) {
-        const savePath = await save({ defaultPath: 'diagram.mmd' })
-        if (!savePath) return
+        const savePath = await save({ defaultPath: state.currentFile ?? 'diagram.mmd' })
+        if (!savePath) return null
         filePath = savePath
       }
 
       const content = getCode()
-      await writeTextFile(filePath, content)
+      await invoke('save_document', { path: filePath, content })
+      // Saving counts as use: the document moves to the top of the list.
+      invoke('add_recent_file', { path: filePath }).catch(() => {})
       setState(prev => ({ ...prev, currentFile: filePath, isDirty: false }))
       setStatus(`Saved: ${filePath}`)
+      return filePath
     } catch (error) {
-      const errorMessage = error instanceof Error ? error.message : String(error)
-      setStatus(`Failed to save: ${errorMessage}`)
+      setStatus(`Failed to save: ${describeSaveError(error)}`)
       setIsError(true)
+      return null
     }
   }
 
-  const svgToPng = (svgEl: SVGSVGElement): Promise<Blob> => {
-    return new Promise((resolve, reject) => {
-      const canvas = document.createElement('canvas')
-      const ctx = canvas.getContext('2d')
-      if (!ctx) {
-        reject(new Error('Failed to get canvas context'))
-        return
-      }
-
-      const svgData = new XMLSerializer().serializeToString(svgEl)
-      const svgBlob = new Blob([svgData], { type: 'image/svg+xml;charset=utf-8' })
-      const url = URL.createObjectURL(svgBlob)
-
-      const img = new Image()
-      img.onload = () => {
-        canvas.width = img.width * 2
-        canvas.height = img.height * 2
-        ctx.scale(2, 2)
-        ctx.drawImage(img, 0, 0)
-        URL.revokeObjectURL(url)
-        
-        canvas.toBlob((blob) => {
-          if (blob) {
-            resolve(blob)
-          } else {
-            reject(new Error('Failed to create PNG blob'))
-          }
-        }, 'image/png')
-      }
-      img.onerror = () => {
-        URL.revokeObjectURL(url)
-        reject(new Error('Failed to load SVG'))
-      }
-      img.src = url
-    })
+  // Answers the close guard's "Save": the window closes once the save went
+  // through and the backend knows the document is clean.
+  const handleSaveAndClose = async () => {
+    const path = await handleSave()
+    if (!path) return
+    await invoke('update_document', { path, dirty: false })
+    await getCurrentWebviewWindow().close()
   }
 
-  const handleExport = (format: 'svg' | 'png') => {
+  // The backend asks where to save, proposing `<document>.<format>` in the
+  // directory this document was last exported to.
+  const handleExport = async (format: 'svg' | 'png' | 'pdf') => {
     const previewEl = document.getElementById('preview')
     const svgEl = previewEl?.querySelector('svg')
     if (!svgEl) {
@@ -578,35 +933,88 @@ function App() {
       return
     }
 
-    if (format === 'svg') {
-      const svgData = new XMLSerializer().serializeToString(svgEl)
-      const blob = new Blob([svgData], { type: 'image/svg+xml' })
-      const url = URL.createObjectURL(blob)
-
-      const link = document.createElement('a')
-      link.href = url
-      link.download = 'diagram.svg'
-      link.click()
-
-      URL.revokeObjectURL(url)
-      setStatus('Exported SVG')
-    } else {
-      svgToPng(svgEl)
-        .then((blob) => {
-          const url = URL.createObjectURL(blob)
-          const link = document.createElement('a')
-          link.href = url
-          link.download = 'diagram.png'
-          link.click()
-          URL.revokeObjectURL(url)
-          setStatus('Exported PNG')
-        })
-        .catch((error) => {
-          setStatus(`Export failed: ${error instanceof Error ? error.message : String(error)}`)
-          setIsError(true)
-        })
+    try {
+      const svg = new XMLSerializer().serializeToString(svgEl)
+      const path = await invoke<string | null>('export_diagram', {
+        path: currentFileRef.current,
+        format,
+        svg,
+        source: getCode(),
+      })
+      if (!path) return
+      setStatus(`Exported: ${path}`)
+      setIsError(false)
+    } catch (error) {
+      setStatus(`Export failed: ${describeSaveError(error)}`)
+      setIsError(true)
     }
-    setIsError(false)
+  }
+
+  const handleFormat = async () => {
+    const view = editorViewRef.current
+    if (!view) return
+    const error = await formatView(view)
+    setStatus(error ?? 'Formatted')
+  }
+
+  const handleReloadFromDisk = async () => {
+    if (!externalChange) return
+    try {
+      const content = await readTextFile(externalChange.path)
+      setCode(content)
+      setState(prev => ({ ...prev, isDirty: false }))
+      setStatus(`Reloaded: ${externalChange.path}`)
+    } catch (error) {
+      const errorMessage = error instanceof Error ? error.message : String(error)
+      setStatus(`Failed to reload: ${errorMessage}`)
+      setIsError(true)
+    }
+    setExternalChange(null)
+    setDiffLines(null)
+  }
+
+  // The buffer no longer matches the disk, so it stays dirty until saved.
+  const handleKeepBuffer = () => {
+    setState(prev => ({ ...prev, isDirty: true }))
+    setExternalChange(null)
+    setDiffLines(null)
+  }
+
+  const handleShowDiff = async () => {
+    if (!externalChange) return
+    try {
+      setDiffLines(await invoke<DiffLine[]>('diff_with_disk', { path: externalChange.path, content: getCode() }))
+    } catch (error) {
+      setStatus(`Failed to diff: ${error instanceof Error ? error.message : String(error)}`)
+    }
+  }
+
+  const handleMenuAction = (action: string) => {
+    if (action.startsWith('theme:')) {
+      setState(prev => ({ ...prev, currentTheme: action.slice('theme:'.length) }))
+      return
+    }
+    switch (action) {
+      case 'new': return handleNew()
+      case 'open': return handleOpen()
+      case 'save': return handleSave()
+      case 'save-as': return handleSave(true)
+      case 'export-svg': return handleExport('svg')
+      case 'export-png': return handleExport('png')
+      case 'export-pdf': return handleExport('pdf')
+      case 'format': return handleFormat()
+      case 'zoom-in': return setZoom(z => Math.min(500, z + 25))
+      case 'zoom-out': return setZoom(z => Math.max(25, z - 25))
+      case 'zoom-reset': return handleResetView()
+      case 'save-and-close': return handleSaveAndClose()
+    }
+  }
+  // The menu and close-guard listeners are registered once; route them to
+  // this render's handlers.
+  menuActionRef.current = handleMenuAction
+
+  const handleProblemClick = (problem: Diagnostic) => {
+    goTo(problem.line, problem.column)
   }
 
   const handleLoadDemo = (demoCode: string) => {
@@ -696,7 +1104,7 @@ function App() {
 
             <Tooltip>
               <TooltipTrigger asChild>
-                <Button variant="ghost" size="sm" onClick={handleSave}>
+                <Button variant="ghost" size="sm" onClick={() => handleSave()}>
                   <Save className="w-4 h-4 mr-2" />
                   Save
                 </Button>
@@ -704,6 +1112,16 @@ function App() {
               <TooltipContent>Save (Ctrl+S)</TooltipContent>
             </Tooltip>
 
+            <Tooltip>
+              <TooltipTrigger asChild>
+                <Button variant="ghost" size="sm" onClick={handleFormat}>
+                  <AlignLeft className="w-4 h-4 mr-2" />
+                  Format
+                </Button>
+              </TooltipTrigger>
+              <TooltipContent>Format document (Shift+Alt+F)</TooltipContent>
+            </Tooltip>
+
             <Separator orientation="vertical" className="h-6" />
 
             <DropdownMenu>
@@ -720,6 +1138,9 @@ function App() {
                 <DropdownMenuItem onClick={() => handleExport('png')}>
                   Export as PNG
                 </DropdownMenuItem>
+                <DropdownMenuItem onClick={() => handleExport('pdf')}>
+                  Export as PDF
+                </DropdownMenuItem>
               </DropdownMenuContent>
             </DropdownMenu>
 
@@ -790,7 +1211,91 @@ function App() {
               <FileCode className="w-4 h-4" />
               Editor
             </div>
+            {externalChange && (
+              <div
+                className="border-b text-xs flex-shrink-0"
+                style={{
+                  borderColor: getThemeColors(state.currentTheme).border
+                    ? hexToRgba(getThemeColors(state.currentTheme).border!, 0.3)
+                    : hexToRgba(getThemeColors(state.currentTheme).fg, 0.2),
+                }}
+              >
+                <div className="flex items-center gap-2 px-3 py-1">
+                  <AlertCircle className="w-3 h-3 flex-shrink-0 text-yellow-500" />
+                  <span className="flex-1">
+                    {externalChange.kind === 'deleted'
+                      ? 'The file was deleted on disk.'
+                      : 'The file was changed on disk.'}
+                  </span>
+                  {externalChange.kind === 'modified' && (
+                    <>
+                      <Button variant="ghost" size="sm" className="h-6 px-2" onClick={handleReloadFromDisk}>
+                        Reload
+                      </Button>
+                      <Button variant="ghost" size="sm" className="h-6 px-2" onClick={handleShowDiff}>
+                        Show Diff
+                      </Button>
+                    </>
+                  )}
+                  <Button variant="ghost" size="sm" className="h-6 px-2" onClick={handleKeepBuffer}>
+                    Keep Mine
+                  </Button>
+                </div>
+                {diffLines && (
+                  <pre className="max-h-48 overflow-auto px-3 py-1 font-mono">
+                    <div className="opacity-60">- on disk, + in editor</div>
+                    {diffLines.map((line, index) => (
+                      <div
+                        key={index}
+                        className={
+                          line.change === 'added'
+                            ? 'text-green-500'
+                            : line.change === 'removed'
+                              ? 'text-red-500'
+                              : undefined
+                        }
+                      >
+                        {line.change === 'added' ? '+ ' : line.change === 'removed' ? '- ' : '  '}
+                        {line.text}
+                      </div>
+                    ))}
+                  </pre>
+                )}
+              </div>
+            )}
             <div id="editor" className="flex-1 overflow-auto" />
+            {problems.length > 0 && (
+              <div
+                className="max-h-32 overflow-auto border-t text-xs flex-shrink-0"
+                style={{
+                  borderColor: getThemeColors(state.currentTheme).border
+                    ? hexToRgba(getThemeColors(state.currentTheme).border!, 0.3)
+                    : hexToRgba(getThemeColors(state.currentTheme).fg, 0.2),
+                }}
+              >
+                {problems.map((problem, index) => (
+                  <button
+                    key={index}
+                    className="flex w-full items-start gap-2 px-3 py-1 text-left hover:opacity-80"
+                    onClick={() => handleProblemClick(problem)}
+                  >
+                    <AlertCircle
+                      className={`w-3 h-3 mt-0.5 flex-shrink-0 ${
+                        problem.severity === 'error'
+                          ? 'text-red-500'
+                          : problem.severity === 'warning'
+                            ? 'text-yellow-500'
+                            : 'text-blue-500'
+                      }`}
+                    />
+                    <span className="flex-1">{problem.message}</span>
+                    <span style={{ color: getThemeColors(state.currentTheme).muted || getThemeColors(state.currentTheme).fg }}>
+                      {problem.line}:{problem.column}
+                    </span>
+                  </button>
+                ))}
+              </div>
+            )}
           </div>
 
           {/* Preview */}